@@ -1,7 +1,10 @@
+pub mod crossfade;
+pub mod cue_sheet;
 mod from_file;
 mod output;
 mod playback;
 pub mod playback_manager;
+pub mod queue;
 use blake3::Hash;
 use time::Duration;
 #[cfg(not(target_os = "linux"))]
@@ -14,11 +17,20 @@ pub struct AudioFile {
     pub audio_title: String,
     pub album_name: String,
     pub artist_name: String,
+    pub album_artist_name: String,
+    pub genre: String,
+    pub composer: String,
+    pub lyrics: String,
     pub track_num: u8,
+    pub disc_num: u8,
     pub release_year: u16,
     pub audio_length: Duration,
     pub audio_path: std::path::PathBuf,
     pub img_path: Option<std::path::PathBuf>,
+    /// Where the audio starts within its file, non-zero for tracks of a CUE sheet image.
+    pub start_offset: Duration,
+    /// Where the audio ends within its file, None to play to the end.
+    pub end_offset: Option<Duration>,
 }
 
 impl Default for AudioFile {
@@ -28,11 +40,18 @@ impl Default for AudioFile {
             audio_title: String::default(),
             album_name: String::default(),
             artist_name: String::default(),
+            album_artist_name: String::default(),
+            genre: String::default(),
+            composer: String::default(),
+            lyrics: String::default(),
             track_num: 1,
+            disc_num: 1,
             release_year: 1,
             audio_length: Duration::default(),
             audio_path: std::path::PathBuf::default(),
             img_path: None,
+            start_offset: Duration::ZERO,
+            end_offset: None,
         }
     }
 }
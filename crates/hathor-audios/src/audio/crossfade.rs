@@ -0,0 +1,272 @@
+//! Fading one audio out over the start of the next, see
+//! [AudioManager::set_crossfade](super::playback_manager::AudioManager::set_crossfade).
+
+use super::AudioFile;
+use std::collections::VecDeque;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+use symphonia::core::audio::{AudioBuffer, Signal, SignalSpec};
+
+/// How the volumes of the two audios change over a crossfade.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum FadeCurve {
+    /// Volumes change at a steady rate, which dips in loudness halfway through.
+    Linear,
+    /// Keeps the combined loudness steady, best for unrelated audios.
+    #[default]
+    EqualPower,
+}
+
+impl FadeCurve {
+    /// Gains of the audio fading out and of the audio fading in, `progress` of the way through
+    /// the crossfade from 0 to 1.
+    pub fn gains(self, progress: f32) -> (f32, f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => (1.0 - progress, progress),
+            FadeCurve::EqualPower => {
+                let angle = progress * FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+        }
+    }
+}
+
+/// When and how queued audios crossfade.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Crossfade {
+    /// How long the audios overlap, zero to not crossfade.
+    pub duration: Duration,
+    pub curve: FadeCurve,
+    /// Play audios of the same album one after the other without a crossfade, as their
+    /// transitions are part of the album.
+    pub album_aware: bool,
+}
+
+impl Default for Crossfade {
+    fn default() -> Self {
+        Crossfade {
+            duration: Duration::ZERO,
+            curve: FadeCurve::default(),
+            album_aware: true,
+        }
+    }
+}
+
+impl Crossfade {
+    /// Whether `outgoing` crossfades into `incoming`.
+    ///
+    /// # Arguments
+    ///
+    /// * `outgoing` - The audio ending.
+    /// * `incoming` - The audio played next.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::crossfade::Crossfade;
+    /// use hathor_audios::audio::AudioFile;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// let crossfade = Crossfade {
+    ///     duration: Duration::from_secs(6),
+    ///     ..Crossfade::default()
+    /// };
+    /// let outgoing = AudioFile::from_file(Path::new("Portishead/Dummy/10 Roads.mp3")).unwrap();
+    /// let incoming = AudioFile::from_file(Path::new("Portishead/Dummy/11 Glory Box.mp3")).unwrap();
+    /// assert!(!crossfade.applies(&outgoing, &incoming));
+    /// ```
+    pub fn applies(&self, outgoing: &AudioFile, incoming: &AudioFile) -> bool {
+        if self.duration.is_zero() {
+            return false;
+        }
+        let same_album = !outgoing.album_name.is_empty()
+            && outgoing.album_name == incoming.album_name
+            && outgoing.album_artist_name == incoming.album_artist_name;
+        !(self.album_aware && same_album)
+    }
+}
+
+/// Mixes samples of the audio fading out into one channel of the audio fading in.
+///
+/// # Arguments
+///
+/// * `incoming` - Samples of a channel of the audio fading in, mixed in place.
+/// * `outgoing` - Samples of the same channel of the audio fading out, as many as there are
+///   incoming samples are taken, silence if there aren't enough.
+/// * `first_frame` - How many frames into the crossfade the first sample is.
+/// * `fade_frames` - Length of the crossfade in frames.
+/// * `curve` - How the volumes change.
+pub(crate) fn mix(
+    incoming: &mut [f32],
+    outgoing: &mut VecDeque<f32>,
+    first_frame: u64,
+    fade_frames: u64,
+    curve: FadeCurve,
+) {
+    for (frame, sample) in (first_frame..).zip(incoming.iter_mut()) {
+        let (out_gain, in_gain) = curve.gains(frame as f32 / fade_frames.max(1) as f32);
+        *sample = *sample * in_gain + outgoing.pop_front().unwrap_or(0.0) * out_gain;
+    }
+}
+
+/// Converts audio to another sample rate and channel count by linear interpolation.
+/// Good enough for the few seconds of a crossfade, where the audio is fading out anyway.
+pub(crate) struct Resampler {
+    /// Input frames per output frame.
+    step: f64,
+    /// Position of the next output frame, in input frames after `previous`.
+    position: f64,
+    /// The last frame of the previous input, one sample per input channel.
+    previous: Vec<f32>,
+    /// Channels of the output, those beyond the input's repeat its last channel.
+    channels: usize,
+}
+
+impl Resampler {
+    pub(crate) fn new(from: SignalSpec, to: SignalSpec) -> Self {
+        Resampler {
+            step: from.rate as f64 / to.rate as f64,
+            position: 1.0,
+            previous: vec![0.0; from.channels.count()],
+            channels: to.channels.count(),
+        }
+    }
+
+    /// Converts `input`, adding the converted samples to `output`, a queue per output channel.
+    /// The last input frame is held back until the next input, to interpolate towards.
+    pub(crate) fn process(&mut self, input: &AudioBuffer<f32>, output: &mut [VecDeque<f32>]) {
+        let frames = input.frames();
+        if frames == 0 {
+            return;
+        }
+        let last_channel = self.previous.len() - 1;
+        // Frame 0 is the previous input's last frame, followed by the frames of this input.
+        let sample = |channel: usize, frame: usize| match frame {
+            0 => self.previous[channel],
+            frame => input.chan(channel)[frame - 1],
+        };
+        let mut position = self.position;
+        while position < frames as f64 {
+            let frame = position as usize;
+            let fraction = (position - frame as f64) as f32;
+            for (channel, samples) in output.iter_mut().take(self.channels).enumerate() {
+                let channel = channel.min(last_channel);
+                samples.push_back(
+                    sample(channel, frame) * (1.0 - fraction)
+                        + sample(channel, frame + 1) * fraction,
+                );
+            }
+            position += self.step;
+        }
+        self.position = position - frames as f64;
+        for (channel, previous) in self.previous.iter_mut().enumerate() {
+            *previous = input.chan(channel)[frames - 1];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_crossfade {
+    use super::{mix, Crossfade, FadeCurve, Resampler};
+    use crate::audio::AudioFile;
+    use rstest::rstest;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+    use symphonia::core::audio::{AudioBuffer, Channels, Signal, SignalSpec};
+
+    fn buffer(spec: SignalSpec, samples: &[f32]) -> AudioBuffer<f32> {
+        let mut buffer = AudioBuffer::new(samples.len() as u64, spec);
+        buffer.render_reserved(Some(samples.len()));
+        for channel in 0..spec.channels.count() {
+            buffer.chan_mut(channel).copy_from_slice(samples);
+        }
+        buffer
+    }
+
+    #[rstest]
+    #[case(FadeCurve::Linear, 0.0, (1.0, 0.0))]
+    #[case(FadeCurve::Linear, 0.25, (0.75, 0.25))]
+    #[case(FadeCurve::Linear, 2.0, (0.0, 1.0))]
+    #[case(FadeCurve::EqualPower, 0.0, (1.0, 0.0))]
+    #[case(FadeCurve::EqualPower, 0.5, (0.5_f32.sqrt(), 0.5_f32.sqrt()))]
+    #[case(FadeCurve::EqualPower, 1.0, (0.0, 1.0))]
+    fn test_gains(#[case] curve: FadeCurve, #[case] progress: f32, #[case] expected: (f32, f32)) {
+        let (out_gain, in_gain) = curve.gains(progress);
+        assert!((out_gain - expected.0).abs() < 1e-6);
+        assert!((in_gain - expected.1).abs() < 1e-6);
+    }
+
+    #[rstest]
+    #[case("Dummy", "Portishead", "Dummy", "Portishead", true, false)]
+    #[case("Dummy", "Portishead", "Dummy", "Portishead", false, true)]
+    #[case("Dummy", "Portishead", "Portishead", "Portishead", true, true)]
+    #[case(
+        "Greatest Hits",
+        "Portishead",
+        "Greatest Hits",
+        "Massive Attack",
+        true,
+        true
+    )]
+    #[case("", "", "", "", true, true)]
+    fn test_applies(
+        #[case] outgoing_album: &str,
+        #[case] outgoing_album_artist: &str,
+        #[case] incoming_album: &str,
+        #[case] incoming_album_artist: &str,
+        #[case] album_aware: bool,
+        #[case] expected: bool,
+    ) {
+        let crossfade = Crossfade {
+            duration: Duration::from_secs(5),
+            album_aware,
+            ..Crossfade::default()
+        };
+        let outgoing = AudioFile {
+            album_name: String::from(outgoing_album),
+            album_artist_name: String::from(outgoing_album_artist),
+            ..AudioFile::default()
+        };
+        let incoming = AudioFile {
+            album_name: String::from(incoming_album),
+            album_artist_name: String::from(incoming_album_artist),
+            ..AudioFile::default()
+        };
+        assert_eq!(crossfade.applies(&outgoing, &incoming), expected);
+        assert!(!Crossfade::default().applies(&outgoing, &incoming));
+    }
+
+    #[rstest]
+    fn test_mix() {
+        let mut incoming = vec![1.0; 6];
+        let mut outgoing = VecDeque::from(vec![0.5; 4]);
+        mix(&mut incoming, &mut outgoing, 2, 4, FadeCurve::Linear);
+        assert!(outgoing.is_empty());
+        assert_eq!(incoming, [0.75, 0.875, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[rstest]
+    #[case(22050, 44100, vec![0.0, 0.5, 1.0, 1.5, 2.0, 2.5], vec![3.0, 3.5, 4.0, 4.5])]
+    #[case(44100, 44100, vec![0.0, 1.0, 2.0], vec![3.0, 4.0])]
+    #[case(44100, 22050, vec![0.0, 2.0], vec![4.0])]
+    fn test_resample(
+        #[case] from_rate: u32,
+        #[case] to_rate: u32,
+        #[case] first: Vec<f32>,
+        #[case] second: Vec<f32>,
+    ) {
+        let from = SignalSpec::new(from_rate, Channels::FRONT_LEFT);
+        let to = SignalSpec::new(to_rate, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut resampler = Resampler::new(from, to);
+        let mut output = vec![VecDeque::new(), VecDeque::new()];
+        resampler.process(&buffer(from, &[0.0, 1.0, 2.0, 3.0]), &mut output);
+        assert_eq!(output[0], first);
+        output[0].clear();
+        resampler.process(&buffer(from, &[4.0, 5.0]), &mut output);
+        assert_eq!(output[0], second);
+        // Mono is played on both channels.
+        assert_eq!(output[1], [first, second].concat());
+    }
+}
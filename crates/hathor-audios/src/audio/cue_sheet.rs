@@ -0,0 +1,443 @@
+//! CUE sheets, which split a single image file (e.g. a whole album in one FLAC) into tracks.
+
+use super::AudioFile;
+use crate::playlist_files::read_text_file;
+use blake3::Hash;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use time::Duration;
+
+/// CD frames per second, the unit of the last field of `INDEX` times.
+const FRAMES_PER_SECOND: i64 = 75;
+
+/// A parsed CUE sheet. Fields missing from the sheet fall back to the image's own tags.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct CueSheet {
+    /// The album title.
+    pub title: Option<String>,
+    /// The album artist.
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub genre: Option<String>,
+    pub release_year: Option<u16>,
+    pub disc_num: Option<u8>,
+    pub files: Vec<CueFile>,
+}
+
+/// An image file and the tracks within it.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CueFile {
+    /// As written in the sheet, relative paths are relative to the sheet's directory.
+    pub path: PathBuf,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A track within an image file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CueTrack {
+    pub track_num: u8,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    /// Time of the track's `INDEX 01` in the image.
+    pub start: Duration,
+}
+
+impl CueSheet {
+    /// Reads and parses a CUE sheet.
+    /// Sheets that aren't UTF-8 are read as Latin-1, like most ripping software wrote them.
+    ///
+    /// # Arguments
+    ///
+    /// * `cue_path` - Path to the `.cue` file.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use hathor_audios::audio::cue_sheet::CueSheet;
+    /// use std::path::Path;
+    ///
+    /// let cue_sheet = CueSheet::from_file(Path::new("Mezzanine.cue"));
+    pub fn from_file(cue_path: &Path) -> Result<CueSheet, Box<dyn Error>> {
+        CueSheet::parse(&read_text_file(cue_path)?)
+    }
+
+    /// Parses the text of a CUE sheet. Commands Hathor has no use for are skipped,
+    /// as are tracks that aren't audio.
+    ///
+    /// # Examples
+    /// ```
+    /// use hathor_audios::audio::cue_sheet::CueSheet;
+    /// use time::Duration;
+    ///
+    /// let cue_sheet = CueSheet::parse(
+    ///     "PERFORMER \"Massive Attack\"\n\
+    ///      TITLE Mezzanine\n\
+    ///      FILE \"Mezzanine.flac\" WAVE\n\
+    ///      \x20 TRACK 01 AUDIO\n\
+    ///      \x20   TITLE Angel\n\
+    ///      \x20   INDEX 01 00:00:00\n\
+    ///      \x20 TRACK 02 AUDIO\n\
+    ///      \x20   TITLE \"Risingson\"\n\
+    ///      \x20   INDEX 01 06:18:30\n",
+    /// )
+    /// .unwrap();
+    /// let tracks = &cue_sheet.files[0].tracks;
+    /// assert_eq!(tracks[1].start, Duration::seconds(378) + Duration::milliseconds(400));
+    pub fn parse(text: &str) -> Result<CueSheet, Box<dyn Error>> {
+        let mut cue_sheet = CueSheet::default();
+        // Tracks of other types, e.g. data, are parsed into this then dropped.
+        let mut track: Option<(CueTrack, bool)> = None;
+        for (line_num, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", line_num + 1, message);
+            let args = split_args(line);
+            let Some((command, args)) = args.split_first() else {
+                continue;
+            };
+            let arg = |n: usize| {
+                args.get(n)
+                    .cloned()
+                    .ok_or_else(|| error("missing argument"))
+            };
+            match (command.to_ascii_uppercase().as_str(), &mut track) {
+                ("FILE", _) => {
+                    finish_track(&mut cue_sheet, track.take())?;
+                    cue_sheet.files.push(CueFile {
+                        path: PathBuf::from(arg(0)?),
+                        tracks: Vec::new(),
+                    });
+                }
+                ("TRACK", _) => {
+                    finish_track(&mut cue_sheet, track.take())?;
+                    if cue_sheet.files.is_empty() {
+                        return Err(error("TRACK before any FILE").into());
+                    }
+                    let track_num = arg(0)?.parse().map_err(|_| error("bad track number"))?;
+                    let is_audio = arg(1)?.eq_ignore_ascii_case("AUDIO");
+                    let start = Duration::MIN;
+                    track = Some((
+                        CueTrack {
+                            track_num,
+                            title: None,
+                            performer: None,
+                            songwriter: None,
+                            start,
+                        },
+                        is_audio,
+                    ));
+                }
+                ("INDEX", Some((track, _))) if arg(0)?.parse::<u8>() == Ok(1) => {
+                    track.start = parse_time(&arg(1)?).ok_or_else(|| error("bad time"))?;
+                }
+                ("TITLE", Some((track, _))) => track.title = Some(arg(0)?),
+                ("PERFORMER", Some((track, _))) => track.performer = Some(arg(0)?),
+                ("SONGWRITER", Some((track, _))) => track.songwriter = Some(arg(0)?),
+                ("TITLE", None) => cue_sheet.title = Some(arg(0)?),
+                ("PERFORMER", None) => cue_sheet.performer = Some(arg(0)?),
+                ("SONGWRITER", None) => cue_sheet.songwriter = Some(arg(0)?),
+                ("REM", None) => {
+                    let key = arg(0)?;
+                    let value = args.get(1..).unwrap_or_default().join(" ");
+                    match key.to_ascii_uppercase().as_str() {
+                        "GENRE" => cue_sheet.genre = Some(value),
+                        "DATE" => {
+                            cue_sheet.release_year = value.get(..4).and_then(|y| y.parse().ok())
+                        }
+                        "DISCNUMBER" => cue_sheet.disc_num = value.parse().ok(),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        finish_track(&mut cue_sheet, track)?;
+        Ok(cue_sheet)
+    }
+
+    /// One [AudioFile] per track, playing its part of the image.
+    /// Each track ends where the next in the same file starts, the last plays to the end.
+    /// Fails if a track doesn't start before the next one and before the end of the image.
+    /// Tracks are identified by a hash derived from the image's hash and the track number.
+    ///
+    /// # Arguments
+    ///
+    /// * `cue_path` - Path of the sheet, relative image paths start from its directory.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use hathor_audios::audio::cue_sheet::CueSheet;
+    /// use std::path::Path;
+    ///
+    /// let cue_path = Path::new("Mezzanine.cue");
+    /// let audios = CueSheet::from_file(cue_path).unwrap().audios(cue_path);
+    pub fn audios(&self, cue_path: &Path) -> Result<Vec<AudioFile>, Box<dyn Error>> {
+        let cue_dir = cue_path.parent().unwrap_or(Path::new(""));
+        let mut audios = Vec::new();
+        for file in &self.files {
+            let image_path = cue_dir.join(&file.path).canonicalize()?;
+            let image = AudioFile::from_file(&image_path)?;
+            for (i, track) in file.tracks.iter().enumerate() {
+                let end_offset = file.tracks.get(i + 1).map(|next| next.start);
+                let end = end_offset.unwrap_or(image.audio_length);
+                if track.start >= end {
+                    return Err(format!(
+                        "track {} starts at {}, not before the next track or the end of {}",
+                        track.track_num,
+                        track.start,
+                        file.path.display()
+                    )
+                    .into());
+                }
+                let or_image = |field: Option<&String>, image_field: &String| {
+                    field.unwrap_or(image_field).clone()
+                };
+                audios.push(AudioFile {
+                    file_hash: virtual_track_hash(&image.file_hash, track.track_num),
+                    audio_title: track.title.clone().unwrap_or_default(),
+                    album_name: or_image(self.title.as_ref(), &image.album_name),
+                    artist_name: or_image(
+                        track.performer.as_ref().or(self.performer.as_ref()),
+                        &image.artist_name,
+                    ),
+                    album_artist_name: or_image(self.performer.as_ref(), &image.album_artist_name),
+                    genre: or_image(self.genre.as_ref(), &image.genre),
+                    composer: or_image(
+                        track.songwriter.as_ref().or(self.songwriter.as_ref()),
+                        &image.composer,
+                    ),
+                    lyrics: String::new(),
+                    track_num: track.track_num,
+                    disc_num: self.disc_num.unwrap_or(image.disc_num),
+                    release_year: self.release_year.unwrap_or(image.release_year),
+                    audio_length: end - track.start,
+                    audio_path: image.audio_path.clone(),
+                    img_path: image.img_path.clone(),
+                    start_offset: track.start,
+                    end_offset,
+                });
+            }
+        }
+        Ok(audios)
+    }
+}
+
+/// Adds a finished track to the last file, dropping tracks that aren't audio.
+fn finish_track(
+    cue_sheet: &mut CueSheet,
+    track: Option<(CueTrack, bool)>,
+) -> Result<(), Box<dyn Error>> {
+    let Some((track, is_audio)) = track else {
+        return Ok(());
+    };
+    if track.start == Duration::MIN {
+        return Err(format!("track {} has no INDEX 01", track.track_num).into());
+    }
+    if let (true, Some(file)) = (is_audio, cue_sheet.files.last_mut()) {
+        file.tracks.push(track);
+    }
+    Ok(())
+}
+
+/// Splits a line into its command and arguments, keeping quoted arguments whole.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let (arg, remainder) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        args.push(arg.to_string());
+        rest = remainder.trim_start();
+    }
+    args
+}
+
+/// Parses `mm:ss:ff`, where `ff` is CD frames.
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut fields = time
+        .split(':')
+        .map(|field| field.parse::<i64>().ok().filter(|n| *n >= 0));
+    let (minutes, seconds, frames) = (fields.next()??, fields.next()??, fields.next()??);
+    if fields.next().is_some() || seconds >= 60 || frames >= FRAMES_PER_SECOND {
+        return None;
+    }
+    Duration::seconds(minutes.checked_mul(60)?.checked_add(seconds)?).checked_add(
+        Duration::nanoseconds(frames * 1_000_000_000 / FRAMES_PER_SECOND),
+    )
+}
+
+/// A stable hash for a track of an image, as the tracks share the image's file hash.
+fn virtual_track_hash(image_hash: &Hash, track_num: u8) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(image_hash.as_bytes());
+    hasher.update(b"cue track");
+    hasher.update(&[track_num]);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod cue_sheet_tests {
+    use super::{parse_time, CueSheet};
+    use crate::database::audio_files::{get_audio_by_hash, insert_audios};
+    use crate::file_management::get_all_audios_at_path;
+    use crate::fixtures::{temp_audios_context, TestInMemoryDBContext};
+    use rstest::rstest;
+    use std::fs;
+    use std::path::PathBuf;
+    use time::Duration;
+
+    const TEST_AUDIO: &str = r"/../../test_media_files/audio/albums/album/test.mp3";
+
+    #[rstest]
+    #[case("00:00:00", Some(Duration::ZERO))]
+    #[case("01:02:74", Some(Duration::seconds(62) + Duration::nanoseconds(986_666_666)))]
+    #[case("99:59:00", Some(Duration::seconds(5999)))]
+    #[case("00:60:00", None)]
+    #[case("00:00:75", None)]
+    #[case("9223372036854775807:00:00", None)]
+    #[case("153722867280912930:08:00", None)]
+    #[case("00:00", None)]
+    #[case("-01:00:00", None)]
+    #[case("00:-1:00", None)]
+    fn test_parse_time(#[case] time: &str, #[case] expected: Option<Duration>) {
+        assert_eq!(parse_time(time), expected);
+    }
+
+    #[test]
+    fn test_parse_cue_sheet() {
+        let cue_sheet = CueSheet::parse(concat!(
+            "\u{feff}REM GENRE \"Trip Hop\"\r\n",
+            "REM DATE 1998/04/20\r\n",
+            "REM DISCNUMBER 2\r\n",
+            "PERFORMER \"Massive Attack\"\r\n",
+            "TITLE \"Mezzanine\"\r\n",
+            "FILE \"Disc 2.flac\" WAVE\r\n",
+            "  TRACK 01 DATA\r\n",
+            "    INDEX 01 00:00:00\r\n",
+            "  TRACK 02 AUDIO\r\n",
+            "    TITLE \"Angel\"\r\n",
+            "    SONGWRITER \"Del Naja\"\r\n",
+            "    INDEX 00 00:10:00\r\n",
+            "    INDEX 01 00:12:00\r\n",
+            "FILE other.wav WAVE\r\n",
+            "  TRACK 03 AUDIO\r\n",
+            "    PERFORMER Guest\r\n",
+            "    INDEX 01 00:00:00\r\n",
+        ))
+        .unwrap();
+        assert_eq!(cue_sheet.genre.as_deref(), Some("Trip Hop"));
+        assert_eq!(cue_sheet.release_year, Some(1998));
+        assert_eq!(cue_sheet.disc_num, Some(2));
+        assert_eq!(cue_sheet.performer.as_deref(), Some("Massive Attack"));
+        assert_eq!(cue_sheet.title.as_deref(), Some("Mezzanine"));
+        assert_eq!(cue_sheet.files.len(), 2);
+        assert_eq!(cue_sheet.files[0].path, PathBuf::from("Disc 2.flac"));
+        let angel = &cue_sheet.files[0].tracks[..];
+        assert_eq!(angel.len(), 1);
+        assert_eq!(angel[0].track_num, 2);
+        assert_eq!(angel[0].songwriter.as_deref(), Some("Del Naja"));
+        assert_eq!(angel[0].start, Duration::seconds(12));
+        assert_eq!(
+            cue_sheet.files[1].tracks[0].performer.as_deref(),
+            Some("Guest")
+        );
+    }
+
+    #[rstest]
+    #[case("TRACK 01 AUDIO\nINDEX 01 00:00:00\n")]
+    #[case("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 00 00:00:00\n")]
+    #[case("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:99:00\n")]
+    #[case("FILE a.wav WAVE\nTRACK one AUDIO\nINDEX 01 00:00:00\n")]
+    #[case("REM\nFILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\n")]
+    #[case("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 9223372036854775807:00:00\n")]
+    fn test_parse_bad_cue_sheet(#[case] cue: &str) {
+        assert!(CueSheet::parse(cue).is_err());
+    }
+
+    /// Split a copy of the 20 second test audio into two tracks,
+    /// and check they're found instead of the image and round-trip through the DB.
+    #[rstest]
+    fn test_cue_sheet_tracks(mut temp_audios_context: TestInMemoryDBContext) {
+        let dir = temp_audios_context.temp_audio_dir.join("cue");
+        fs::create_dir_all(&dir).unwrap();
+        let image_path = dir.join("image.mp3");
+        fs::copy(
+            env!("CARGO_MANIFEST_DIR").to_owned() + TEST_AUDIO,
+            &image_path,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("image.cue"),
+            b"PERFORMER \"Sigur R\xf3s\"\nTITLE Live\nFILE image.mp3 MP3\n\
+              TRACK 01 AUDIO\nTITLE One\nINDEX 01 00:00:00\n\
+              TRACK 02 AUDIO\nTITLE Two\nINDEX 01 00:12:30\n",
+        )
+        .unwrap();
+
+        let audios = get_all_audios_at_path(&dir).unwrap();
+        assert_eq!(audios.len(), 2);
+        let (one, two) = (&audios[0], &audios[1]);
+        assert_eq!([one.track_num, two.track_num], [1, 2]);
+        assert_eq!([&one.audio_title, &two.audio_title], ["One", "Two"]);
+        assert_eq!(one.artist_name, "Sigur Rós");
+        assert_eq!(one.album_artist_name, "Sigur Rós");
+        assert_eq!(one.album_name, "Live");
+        assert_eq!(one.audio_path, image_path.canonicalize().unwrap());
+        assert_ne!(one.file_hash, two.file_hash);
+        let two_start = Duration::seconds(12) + Duration::milliseconds(400);
+        assert_eq!(one.start_offset, Duration::ZERO);
+        assert_eq!(one.end_offset, Some(two_start));
+        assert_eq!(one.audio_length, two_start);
+        assert_eq!(two.start_offset, two_start);
+        assert_eq!(two.end_offset, None);
+        assert_eq!(two.audio_length, Duration::seconds(20) - two_start);
+
+        let conn = &mut temp_audios_context.connection;
+        insert_audios(conn, &audios).unwrap();
+        let mut two_from_db = get_audio_by_hash(conn, &two.file_hash);
+        assert_eq!(two_from_db.start_offset, two.start_offset);
+        assert_eq!(two_from_db.end_offset, None);
+        // Lengths are stored in whole seconds.
+        two_from_db.audio_length = two.audio_length;
+        assert_eq!(&two_from_db, two);
+        let one_from_db = get_audio_by_hash(conn, &one.file_hash);
+        assert_eq!(one_from_db.end_offset, Some(two_start));
+    }
+
+    /// Sheets whose tracks don't start in order within the image are skipped,
+    /// leaving the image to be found as a single audio.
+    #[rstest]
+    #[case("00:12:00", "00:05:00")]
+    #[case("00:12:00", "00:12:00")]
+    #[case("00:00:00", "00:30:00")]
+    fn test_cue_sheet_with_bad_starts(
+        temp_audios_context: TestInMemoryDBContext,
+        #[case] first_start: &str,
+        #[case] second_start: &str,
+    ) {
+        let dir = temp_audios_context.temp_audio_dir.join("cue");
+        fs::create_dir_all(&dir).unwrap();
+        let image_path = dir.join("image.mp3");
+        fs::copy(
+            env!("CARGO_MANIFEST_DIR").to_owned() + TEST_AUDIO,
+            &image_path,
+        )
+        .unwrap();
+        let cue_path = dir.join("image.cue");
+        fs::write(
+            &cue_path,
+            format!(
+                "FILE image.mp3 MP3\nTRACK 01 AUDIO\nINDEX 01 {}\nTRACK 02 AUDIO\nINDEX 01 {}\n",
+                first_start, second_start
+            ),
+        )
+        .unwrap();
+
+        let cue_sheet = CueSheet::from_file(&cue_path).unwrap();
+        assert!(cue_sheet.audios(&cue_path).is_err());
+        let audios = get_all_audios_at_path(&dir).unwrap();
+        assert_eq!(audios.len(), 1);
+        assert_eq!(audios[0].start_offset, Duration::ZERO);
+        assert_eq!(audios[0].end_offset, None);
+    }
+}
@@ -11,6 +11,7 @@ use time::Duration;
 
 impl AudioFile {
     /// Returns an [AudioFile](super::audio::AudioFile) populated from the file at the given path.
+    /// Fails if the file can't be opened or isn't audio of a known length.
     ///
     /// # Arguments
     ///
@@ -27,10 +28,10 @@ impl AudioFile {
         let mut audio_file = AudioFile::default();
         // Open file.
 
-        let mut probe = AudioFile::get_audio_probe(audio_path);
+        let mut probe = AudioFile::get_audio_probe(audio_path)?;
 
         // Add the metadata we already have
-        audio_file.audio_path = audio_path.to_path_buf().canonicalize().unwrap();
+        audio_file.audio_path = audio_path.to_path_buf().canonicalize()?;
 
         // Add metadata from within the file itself.
         if let Some(metadata_rev) = probe.format.metadata().current() {
@@ -41,8 +42,9 @@ impl AudioFile {
 
         // Add metadata from processing the file.
         // Length.
-        let track = &probe.format.tracks()[0];
-        audio_file.audio_length = AudioFile::get_audio_length(track);
+        let track = probe.format.tracks().first().ok_or("no audio track")?;
+        audio_file.audio_length =
+            AudioFile::get_audio_length(track).ok_or("audio length is unknown")?;
 
         // File hash.
         audio_file.file_hash = AudioFile::get_file_hash(audio_path)?;
@@ -51,18 +53,17 @@ impl AudioFile {
 
     /// Not intended for external use as it has to read entire track.
     /// After initialisation via from_file, self.audio_length will contain this.
-    fn get_audio_length(track: &Track) -> Duration {
+    fn get_audio_length(track: &Track) -> Option<Duration> {
         let track_length = track
             .codec_params
-            .time_base
-            .unwrap()
-            .calc_time(track.codec_params.n_frames.unwrap());
-        Duration::seconds(track_length.seconds as i64)
+            .time_base?
+            .calc_time(track.codec_params.n_frames?);
+        Some(Duration::seconds(track_length.seconds as i64))
     }
 
     /// Not intended for external use as it has to read entire file.
     /// After initialisation via from_file, self.file_hash will contain this.
-    fn get_file_hash(audio_path: &std::path::Path) -> Result<Hash, Box<dyn Error>> {
+    pub(crate) fn get_file_hash(audio_path: &std::path::Path) -> Result<Hash, Box<dyn Error>> {
         let mut hasher = blake3::Hasher::new();
         let file = std::fs::File::open(audio_path)?;
         hasher.update_reader(file)?;
@@ -80,11 +81,29 @@ impl AudioFile {
                     StandardTagKey::TrackTitle => self.audio_title = tag.value.to_string(),
                     StandardTagKey::Album => self.album_name = tag.value.to_string(),
                     StandardTagKey::Artist => self.artist_name = tag.value.to_string(),
+                    StandardTagKey::AlbumArtist => self.album_artist_name = tag.value.to_string(),
+                    StandardTagKey::Genre => self.genre = tag.value.to_string(),
+                    StandardTagKey::Composer => self.composer = tag.value.to_string(),
+                    StandardTagKey::Lyrics => self.lyrics = tag.value.to_string(),
                     StandardTagKey::TrackNumber => {
-                        self.track_num = tag.value.to_string().parse::<u8>().unwrap()
+                        // Also written as "3/12", like disc numbers.
+                        if let Some(track_num) = tag.value.to_string().split('/').next() {
+                            self.track_num = track_num.trim().parse::<u8>().unwrap_or_default()
+                        }
+                    }
+                    StandardTagKey::DiscNumber => {
+                        // Often written as "1/2", keep the disc and drop the total.
+                        if let Some(disc_num) = tag.value.to_string().split('/').next() {
+                            self.disc_num = disc_num.trim().parse::<u8>().unwrap_or(1)
+                        }
                     }
                     StandardTagKey::Date => {
-                        self.release_year = tag.value.to_string().parse::<u16>().unwrap()
+                        // Keep the year of full dates, e.g. "1998-04-20".
+                        let date = tag.value.to_string();
+                        self.release_year = date
+                            .get(..4)
+                            .and_then(|year| year.parse::<u16>().ok())
+                            .unwrap_or_default()
                     }
                     _ => (),
                 }
@@ -93,10 +112,10 @@ impl AudioFile {
         self
     }
 
-    fn get_audio_probe(audio_path: &std::path::Path) -> symphonia::core::probe::ProbeResult {
-        let file = std::fs::File::open(audio_path).unwrap_or_else(|_| {
-            panic!("failed to open file {}", audio_path.to_str().unwrap());
-        });
+    fn get_audio_probe(
+        audio_path: &std::path::Path,
+    ) -> Result<symphonia::core::probe::ProbeResult, Box<dyn Error>> {
+        let file = std::fs::File::open(audio_path)?;
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
         let mut hint = Hint::new();
         // Provide the file extension as a hint.
@@ -108,9 +127,7 @@ impl AudioFile {
         let meta_opts: MetadataOptions = Default::default();
         let fmt_opts: FormatOptions = Default::default();
 
-        symphonia::default::get_probe()
-            .format(&hint, mss, &fmt_opts, &meta_opts)
-            .expect("unsupported format")
+        Ok(symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?)
     }
 }
 
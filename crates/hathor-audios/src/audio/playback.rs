@@ -1,12 +1,20 @@
-use super::playback_manager::AudioCommand;
+use super::crossfade::{self, Crossfade, Resampler};
+use super::playback_manager::{
+    AudioCommand, AudioReply, AudioRequest, PlayerEvent, PlayerState, PlayerStatus, SeekTarget,
+};
+use super::queue::{Queue, QueueItem};
 use super::AudioFile;
 use crate::audio::output;
+use crate::database::plays::{record_play, Play, PlayOutcome, PlaySource};
 use log::info;
-use log::warn;
-use std::borrow::BorrowMut;
+use log::{error, warn};
+use rusqlite::Connection;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::sync::mpsc::{Receiver, Sender};
-use std::{thread, time::Duration};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, Signal, SignalSpec};
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::codecs::{Decoder, CODEC_TYPE_NULL};
 use symphonia::core::formats::FormatOptions;
@@ -16,31 +24,47 @@ use symphonia::core::formats::{SeekMode, SeekTo};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use symphonia::core::units::Time;
+use symphonia::core::units::{Time, TimeBase};
+
+/// How long before the end of an audio the next queued audio is opened.
+const PREPARE_AHEAD: Duration = Duration::from_secs(10);
+
+/// How often [PlayerEvent::Position] is sent while playing.
+const POSITION_TICK: Duration = Duration::from_millis(250);
 
 /// Main loop of the audio playback thread.
 pub(crate) fn do_play_loop(
-    receiver_from_audio_manager: Receiver<AudioCommand>,
-    sender_to_audio_manager: Sender<eyre::Result<()>>,
+    receiver_from_audio_manager: Receiver<AudioRequest>,
+    play_history: Option<Connection>,
+    queue: Arc<Mutex<Queue>>,
+    status: Arc<Mutex<PlayerStatus>>,
 ) -> eyre::Result<()> {
-    let mut playback = Playback::new(receiver_from_audio_manager, sender_to_audio_manager);
-    // Handle incoming commands from other threads.
-    // Repeat until we get an audio file and can start the playback loop.
-    while playback.format_reader.is_none() && playback.decoder.is_none() {
-        thread::sleep(Duration::from_millis(10));
-        playback.try_consume_next_audio_command()?;
-    }
+    let mut playback = Playback::new(receiver_from_audio_manager);
+    playback.play_history = play_history;
+    playback.queue = queue;
+    playback.status = status;
     loop {
-        // Handle incoming commands from other threads.
-        playback.try_consume_next_audio_command()?;
-        if !playback.play {
-            thread::sleep(Duration::from_millis(10));
+        // Handle incoming commands from other threads, the thread ends along with its
+        // AudioManager.
+        if !playback.consume_audio_commands() {
+            break Ok(());
+        }
+        if !playback.play || playback.format_reader.is_none() {
             continue;
         }
         // Get the next packet from the format reader.
         let packet = match playback.format_reader.as_mut().unwrap().next_packet() {
             Ok(packet) => packet,
-            Err(err) => break Err(err.into()),
+            Err(symphonia::core::errors::Error::IoError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                playback.end_audio();
+                continue;
+            }
+            Err(err) => {
+                playback.read_failed(err);
+                continue;
+            }
         };
 
         // If the packet does not belong to the selected track, skip it.
@@ -48,164 +72,663 @@ pub(crate) fn do_play_loop(
             continue;
         }
 
+        // A CUE sheet track stops where the next track of its image starts.
+        if playback.end_ts.is_some_and(|end_ts| packet.ts() >= end_ts) {
+            playback.end_audio();
+            continue;
+        }
+
         // Decode the packet into audio samples.
         match playback.decoder.as_mut().unwrap().decode(&packet) {
             Ok(decoded) => {
+                // Get the audio buffer specification. This is a description of the decoded
+                // audio buffer's sample format and sample rate.
+                let spec = *decoded.spec();
+
+                // Get the capacity of the decoded buffer. Note that this is capacity, not
+                // length! The capacity of the decoded buffer is constant for the life of the
+                // decoder, but the length is not.
+                let duration = decoded.capacity() as u64;
+
+                // The output stays open from one audio to the next, unless the next audio has
+                // another spec or bigger buffers. Then the output plays out what it was given
+                // and is opened again.
+                if playback.output_spec.is_some_and(|(output_spec, capacity)| {
+                    output_spec != spec || capacity < duration
+                }) {
+                    if let Some(mut audio_output) = playback.audio_output.take() {
+                        audio_output.flush();
+                    }
+                }
+
                 // If the audio output is not open, try to open it.
                 if playback.audio_output.is_none() {
-                    // Get the audio buffer specification. This is a description of the decoded
-                    // audio buffer's sample format and sample rate.
-                    let spec = *decoded.spec();
-
-                    // Get the capacity of the decoded buffer. Note that this is capacity, not
-                    // length! The capacity of the decoded buffer is constant for the life of the
-                    // decoder, but the length is not.
-                    let duration = decoded.capacity() as u64;
-
-                    // Try to open the audio output.
-                    playback
-                        .audio_output
-                        .replace(output::try_open(spec, duration).unwrap());
-                } else {
-                    // TODO: Check the audio spec. and duration hasn't changed.
+                    match output::try_open(spec, duration) {
+                        Ok(audio_output) => {
+                            playback.audio_output = Some(audio_output);
+                            playback.output_spec = Some((spec, duration));
+                        }
+                        Err(err) => {
+                            playback.output_failed(err);
+                            continue;
+                        }
+                    }
                 }
 
                 // Write the decoded audio samples to the audio output if the presentation timestamp
                 // for the packet is >= the seeked position (0 if not seeking).
-                if packet.ts() >= playback.seek_ts_seconds {
-                    if let Some(audio_output) = playback.audio_output.as_mut() {
-                        audio_output.write(decoded).unwrap()
+                if packet.ts() >= playback.required_ts {
+                    let written = if playback.fading_out.is_some() || playback.volume < 1.0 {
+                        let mut buffer = decoded.make_equivalent::<f32>();
+                        decoded.convert(&mut buffer);
+                        playback.mix_fading_out(&mut buffer);
+                        let volume = playback.volume;
+                        buffer.transform(|sample| sample * volume);
+                        playback
+                            .audio_output
+                            .as_mut()
+                            .map_or(Ok(()), |audio_output| {
+                                audio_output.write(buffer.as_audio_buffer_ref())
+                            })
+                    } else {
+                        playback
+                            .audio_output
+                            .as_mut()
+                            .map_or(Ok(()), |audio_output| audio_output.write(decoded))
+                    };
+                    if let Err(err) = written {
+                        playback.output_failed(err);
                     }
+                    playback.add_listened(packet.dur());
+                    playback.update_position(packet.ts());
                 }
             }
             Err(symphonia::core::errors::Error::DecodeError(err)) => {
                 // Decode errors are not fatal. Print the error message and try to decode the next
                 // packet as usual.
                 warn!("decode error: {}", err);
+                playback.emit(PlayerEvent::DecodeWarning(err.to_string()));
             }
-            Err(err) => break Err(eyre::Error::from(err)),
+            Err(err) => playback.read_failed(err),
         }
     }
 }
 
 struct Playback {
-    receiver_from_audio_manager: Receiver<AudioCommand>,
-    sender_to_audio_manager: Sender<Result<(), eyre::Error>>,
+    receiver_from_audio_manager: Receiver<AudioRequest>,
     format_reader: Option<Box<dyn FormatReader>>,
     decoder: Option<Box<dyn Decoder>>,
     audio_output: Option<Box<dyn output::AudioOutput>>,
+    /// Spec and buffer capacity the audio output was opened with.
+    output_spec: Option<(SignalSpec, u64)>,
     play: bool,
-    seek_ts_seconds: u64,
+    /// Packets before this timestamp, in time base units, are decoded but not played, so
+    /// playback starts exactly where seeked to.
+    required_ts: u64,
     track_id: u32,
+    /// Where the audio starts in its file, non-zero for CUE sheet tracks.
+    start_offset: time::Duration,
+    /// Timestamp the audio ends at, for CUE sheet tracks that aren't last in their image.
+    end_ts: Option<u64>,
+    time_base: Option<TimeBase>,
+    /// Where finished plays are recorded, None to keep no history.
+    play_history: Option<Connection>,
+    /// The play of the current audio, recorded once it ends or another audio is played.
+    current_play: Option<Play>,
+    /// Where the next audio comes from once the current one ends.
+    queue: Arc<Mutex<Queue>>,
+    /// The audio playing, None once it ended.
+    current_audio: Option<Box<AudioFile>>,
+    subscribers: Vec<Sender<PlayerEvent>>,
+    /// Set once told to shut down, ending the thread.
+    shut_down: bool,
+    /// The audio the queue advances to next, opened ahead of time so it starts without a gap.
+    /// None in the pair if it couldn't be opened.
+    prepared: Option<(QueueItem, Option<OpenedAudio>)>,
+    crossfade: Crossfade,
+    /// The previous audio while it fades out over the start of the current one.
+    fading_out: Option<FadingOut>,
+    /// Shared with the AudioManager, which reads it for [status](super::playback_manager::AudioManager::status).
+    status: Arc<Mutex<PlayerStatus>>,
+    /// Gain applied to every sample, from 0 for silence to 1 for unchanged.
+    volume: f32,
+    /// Position of the last [PlayerEvent::Position] sent.
+    last_tick: Duration,
 }
 
 impl Playback {
-    pub fn new(
-        receiver_from_audio_manager: Receiver<AudioCommand>,
-        sender_to_audio_manager: Sender<Result<(), eyre::Error>>,
-    ) -> Self {
+    pub fn new(receiver_from_audio_manager: Receiver<AudioRequest>) -> Self {
         Playback {
             receiver_from_audio_manager,
-            sender_to_audio_manager,
             format_reader: None,
             decoder: None,
             audio_output: None,
+            output_spec: None,
             play: true,
-            seek_ts_seconds: 0,
+            required_ts: 0,
             track_id: 0,
+            start_offset: time::Duration::ZERO,
+            end_ts: None,
+            time_base: None,
+            play_history: None,
+            current_play: None,
+            queue: Arc::default(),
+            current_audio: None,
+            subscribers: Vec::new(),
+            shut_down: false,
+            prepared: None,
+            crossfade: Crossfade::default(),
+            fading_out: None,
+            status: Arc::default(),
+            volume: 1.0,
+            last_tick: Duration::ZERO,
         }
     }
 
-    /// Checks for the next command in self.receiver_from_audio_manager.
-    /// If a command is in the queue:
-    ///     Handle it and return Ok(()).
-    ///     Send Ok(()) to the AudioManager thread.
-    /// Else if no errors:
-    ///     Do nothing and return Ok(()).
-    ///     Send Ok(()) to the AudioManager thread.
-    /// Else:
-    ///     Return the error to the AudioManager thread.
-    pub fn try_consume_next_audio_command(&mut self) -> Result<(), eyre::Error> {
-        if let Ok(cmd) = self.receiver_from_audio_manager.try_recv() {
-            let response = match cmd {
-                AudioCommand::Pause => {
-                    self.play = false;
-                    Ok::<(), symphonia::core::errors::Error>(())
-                }
-                AudioCommand::Play => {
-                    self.play = true;
-                    Ok(())
-                }
-                AudioCommand::Seek(ts) => {
-                    self.seek(ts);
-                    Ok(())
-                }
-                AudioCommand::ResetPlayback => {
-                    self.seek(0);
-                    Ok(())
-                }
-                AudioCommand::ChangeAudio(audio) => {
-                    self.change_audio(audio)?;
-                    Ok(())
-                }
+    /// Handles every request the AudioManager sent since the last call, first sleeping until
+    /// one arrives if there's nothing to play.
+    /// Returns false once shut down or the AudioManager is gone.
+    pub fn consume_audio_commands(&mut self) -> bool {
+        if !self.play || self.format_reader.is_none() {
+            let Ok(request) = self.receiver_from_audio_manager.recv() else {
+                return false;
             };
-            if response.is_ok() {
-                self.sender_to_audio_manager.send(Ok(())).unwrap();
-            } else {
-                self.sender_to_audio_manager
-                    .send(Err(eyre::Error::from(response.err().unwrap())))
-                    .unwrap();
+            self.handle_request(request);
+        }
+        while !self.shut_down {
+            match self.receiver_from_audio_manager.try_recv() {
+                Ok(request) => self.handle_request(request),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
             }
-        };
-        Ok(())
+        }
+        false
+    }
+
+    /// Carries out a request, replying with its outcome.
+    fn handle_request(&mut self, request: AudioRequest) {
+        let outcome = self.handle_command(request.command);
+        // The AudioManager may have timed out and stopped waiting.
+        let _ = request.reply.send(outcome);
+    }
+
+    fn handle_command(&mut self, command: AudioCommand) -> eyre::Result<AudioReply> {
+        match command {
+            AudioCommand::Pause => self.set_play(false),
+            AudioCommand::Play => self.set_play(true),
+            AudioCommand::Seek(target) => {
+                let position = self.seek_to(target)?;
+                self.emit(PlayerEvent::Seeked(position));
+                return Ok(AudioReply::Seeked(position));
+            }
+            AudioCommand::ResetPlayback => {
+                let position = self.seek(Duration::ZERO)?;
+                self.emit(PlayerEvent::Seeked(position));
+                return Ok(AudioReply::Seeked(position));
+            }
+            AudioCommand::SetVolume(volume) => {
+                self.volume = volume;
+                self.status.lock().unwrap().volume = volume;
+            }
+            AudioCommand::ChangeAudio(audio, source) => self.change_audio(audio, source)?,
+            AudioCommand::Subscribe(subscriber) => self.subscribers.push(subscriber),
+            AudioCommand::SetCrossfade(crossfade) => self.crossfade = crossfade,
+            AudioCommand::Stop => {
+                self.finish_play(PlayOutcome::Skipped);
+                self.stop();
+            }
+            AudioCommand::Shutdown => {
+                self.finish_play(PlayOutcome::Skipped);
+                self.stop();
+                // Let what was already written play out before closing the output.
+                if let Some(mut audio_output) = self.audio_output.take() {
+                    audio_output.flush();
+                }
+                self.shut_down = true;
+            }
+        }
+        Ok(AudioReply::Done)
     }
 
     fn change_audio(
         &mut self,
         audio: Box<AudioFile>,
+        source: PlaySource,
     ) -> Result<(), symphonia::core::errors::Error> {
-        let format_reader = get_format_reader(&audio);
-        if let Ok(mut format_reader) = format_reader {
-            let decoder = get_decoder(&mut format_reader);
-            if let Ok(decoder) = decoder {
-                self.track_id = get_first_supported_track(format_reader.tracks())
-                    .unwrap()
-                    .id;
-                self.format_reader = Some(format_reader);
-                self.decoder = Some(decoder);
-                Ok(())
-            } else {
-                Err(decoder.err().unwrap())
+        let opened = open_audio(&audio)?;
+        self.start_audio(audio, opened, source);
+        Ok(())
+    }
+
+    /// Plays `audio` from its start, ending the previous audio, and resumes if paused or stopped.
+    fn start_audio(&mut self, audio: Box<AudioFile>, opened: OpenedAudio, source: PlaySource) {
+        self.track_id = opened.track_id;
+        self.end_ts = audio.end_offset.and_then(|end_offset| {
+            let time_base = opened.time_base?;
+            Some(time_base.calc_timestamp(offset_time(end_offset, Duration::ZERO)))
+        });
+        self.time_base = opened.time_base;
+        self.start_offset = audio.start_offset;
+        self.required_ts = 0;
+        self.fading_out = None;
+        // The previous audio, if still playing, was skipped.
+        self.finish_play(PlayOutcome::Skipped);
+        self.current_play = Some(Play {
+            file_hash: audio.file_hash,
+            started_at: time::OffsetDateTime::now_utc(),
+            listened: time::Duration::ZERO,
+            outcome: PlayOutcome::Skipped,
+            source,
+        });
+        self.format_reader = Some(opened.format_reader);
+        self.decoder = Some(opened.decoder);
+        self.current_audio = Some(audio.clone());
+        // CUE sheet tracks start at their INDEX 01 in the image.
+        if !self.start_offset.is_zero() {
+            if let Err(err) = self.seek(Duration::ZERO) {
+                warn!("seek error: {}", err);
+            }
+        }
+        self.last_tick = Duration::ZERO;
+        self.play = true;
+        {
+            let mut status = self.status.lock().unwrap();
+            status.duration = Duration::try_from(audio.audio_length).unwrap_or_default();
+            status.audio = Some(*audio.clone());
+            status.position = Duration::ZERO;
+        }
+        self.publish_state();
+        self.emit(PlayerEvent::AudioChanged(audio));
+    }
+
+    /// Seeks to `target`, no further than the end of the audio.
+    /// Returns the position reached.
+    fn seek_to(&mut self, target: SeekTarget) -> eyre::Result<Duration> {
+        let (current, duration) = {
+            let status = self.status.lock().unwrap();
+            (status.position, status.duration)
+        };
+        let position = match target {
+            SeekTarget::Position(position) => position,
+            SeekTarget::Forward(by) => current.saturating_add(by),
+            SeekTarget::Backward(by) => current.saturating_sub(by),
+            SeekTarget::Percentage(percentage) if !percentage.is_finite() => {
+                return Err(eyre::eyre!("can't seek to {}%", percentage));
+            }
+            SeekTarget::Percentage(percentage) => {
+                duration.mul_f64(percentage.clamp(0.0, 100.0) / 100.0)
             }
+        };
+        if duration.is_zero() {
+            self.seek(position)
         } else {
-            Err(format_reader.err().unwrap())
+            self.seek(position.min(duration))
         }
     }
 
-    fn seek(&mut self, ts: u64) {
+    /// Seeks to `position` in the audio, which is after its start offset in the file.
+    /// Returns the position reached.
+    fn seek(&mut self, position: Duration) -> eyre::Result<Duration> {
+        let (Some(format_reader), Some(decoder)) =
+            (self.format_reader.as_mut(), self.decoder.as_mut())
+        else {
+            return Err(eyre::eyre!("no audio to seek in"));
+        };
         let seek_to = SeekTo::Time {
-            time: Time::from(ts),
+            time: offset_time(self.start_offset, position),
             track_id: Some(self.track_id),
         };
-        self.seek_ts_seconds = if let (Some(format_reader), Some(decoder)) =
-            (self.format_reader.borrow_mut(), self.decoder.borrow_mut())
+        let seeked_to = format_reader.seek(SeekMode::Accurate, seek_to)?;
+        decoder.reset();
+        self.required_ts = seeked_to.required_ts;
+        let position = self.position_at(self.required_ts);
+        self.last_tick = position;
+        self.status.lock().unwrap().position = position;
+        Ok(position)
+    }
+
+    /// Position in the audio of the packet at `ts`, in time base units.
+    fn position_at(&self, ts: u64) -> Duration {
+        let Some(time_base) = self.time_base else {
+            return Duration::ZERO;
+        };
+        let time = time_base.calc_time(ts);
+        let start_offset = Duration::try_from(self.start_offset).unwrap_or_default();
+        (Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+            .saturating_sub(start_offset)
+    }
+
+    /// Moves the position on to the packet at `ts` once it's been played, sending a
+    /// [PlayerEvent::Position] every [POSITION_TICK].
+    fn update_position(&mut self, ts: u64) {
+        let position = self.position_at(ts);
+        let duration = {
+            let mut status = self.status.lock().unwrap();
+            status.position = position;
+            status.duration
+        };
+        if position >= self.last_tick + POSITION_TICK || position < self.last_tick {
+            self.last_tick = position;
+            self.emit(PlayerEvent::Position(position));
+        }
+        // Audios of unknown length play to their end, the next neither prepared nor faded in.
+        if duration.is_zero() {
+            return;
+        }
+        if position + PREPARE_AHEAD + self.crossfade.duration >= duration {
+            self.prepare_next_queued();
+        }
+        if !self.crossfade.duration.is_zero()
+            && self.fading_out.is_none()
+            && position + self.crossfade.duration >= duration
         {
-            decoder.reset();
-            match format_reader.seek(SeekMode::Accurate, seek_to) {
-                Ok(seeked_to) => seeked_to.required_ts,
-                Err(symphonia::core::errors::Error::ResetRequired) => 0,
-                Err(err) => {
-                    // Don't give-up on a seek error.
-                    warn!("seek error: {}", err);
-                    0
+            self.start_crossfade();
+        }
+    }
+
+    /// Starts the next queued audio early, the current audio fading out over it, if it's open
+    /// already and the crossfade rules allow.
+    fn start_crossfade(&mut self) {
+        let Some(current_audio) = self.current_audio.as_deref() else {
+            return;
+        };
+        let prepared = {
+            let queue = self.queue.lock().unwrap();
+            match (queue.upcoming(), &self.prepared) {
+                (Some(upcoming), Some((prepared_item, Some(_)))) => {
+                    upcoming == prepared_item
+                        && self.crossfade.applies(current_audio, &upcoming.audio)
                 }
+                _ => false,
             }
+        };
+        if !prepared {
+            return;
+        }
+        let (Some(format_reader), Some(decoder)) = (self.format_reader.take(), self.decoder.take())
+        else {
+            return;
+        };
+        let outgoing = OpenedAudio {
+            format_reader,
+            decoder,
+            track_id: self.track_id,
+            time_base: self.time_base,
+        };
+        let end_ts = self.end_ts;
+        self.finish_play(PlayOutcome::Completed);
+        let audio = self.current_audio.take();
+        if let Some(audio) = audio.clone() {
+            self.emit(PlayerEvent::Finished(audio));
+        }
+        if self.play_next_queued() {
+            self.fading_out = Some(FadingOut {
+                audio: outgoing,
+                end_ts,
+                pending: Vec::new(),
+                resampler: None,
+                mixed_frames: 0,
+                ended: false,
+            });
         } else {
-            0
+            // The queue changed since it was checked, carry on with the current audio.
+            self.format_reader = Some(outgoing.format_reader);
+            self.decoder = Some(outgoing.decoder);
+            self.current_audio = audio;
+        }
+    }
+
+    /// Mixes the audio fading out into `buffer` of the current audio, fading the current audio
+    /// in as it does.
+    fn mix_fading_out(&mut self, buffer: &mut AudioBuffer<f32>) {
+        let Some(fading_out) = self.fading_out.as_mut() else {
+            return;
+        };
+        let spec = *buffer.spec();
+        let frames = buffer.frames();
+        fading_out.decode(spec, frames);
+        let fade_frames = (self.crossfade.duration.as_secs_f64() * spec.rate as f64) as u64;
+        for (channel, pending) in fading_out.pending.iter_mut().enumerate() {
+            crossfade::mix(
+                buffer.chan_mut(channel),
+                pending,
+                fading_out.mixed_frames,
+                fade_frames,
+                self.crossfade.curve,
+            );
+        }
+        fading_out.mixed_frames += frames as u64;
+        if fading_out.mixed_frames >= fade_frames {
+            self.fading_out = None;
+        }
+    }
+
+    /// Pauses or resumes, telling subscribers if that changed anything.
+    fn set_play(&mut self, play: bool) {
+        if self.play == play {
+            return;
+        }
+        self.play = play;
+        self.publish_state();
+        if self.current_audio.is_some() {
+            self.emit(if play {
+                PlayerEvent::Resumed
+            } else {
+                PlayerEvent::Paused
+            });
+        }
+    }
+
+    fn publish_state(&self) {
+        self.status.lock().unwrap().state = match (&self.current_audio, self.play) {
+            (None, _) => PlayerState::Stopped,
+            (Some(_), true) => PlayerState::Playing,
+            (Some(_), false) => PlayerState::Paused,
+        };
+    }
+
+    /// Drops an audio output that failed so it's opened again on play, and pauses rather than
+    /// fail on every packet until then.
+    fn output_failed(&mut self, err: output::AudioOutputError) {
+        error!("audio output error: {:?}", err);
+        self.audio_output = None;
+        self.output_spec = None;
+        self.emit(PlayerEvent::OutputError(format!("{:?}", err)));
+        self.set_play(false);
+    }
+
+    /// Counts a packet of `duration` time base units as heard.
+    fn add_listened(&mut self, duration: u64) {
+        if let (Some(play), Some(time_base)) = (self.current_play.as_mut(), self.time_base) {
+            let time = time_base.calc_time(duration);
+            play.listened += time::Duration::seconds(time.seconds as i64)
+                + time::Duration::seconds_f64(time.frac);
+        }
+    }
+
+    /// Records the current play, if any, in the play history.
+    fn finish_play(&mut self, outcome: PlayOutcome) {
+        let Some(play) = self.current_play.take() else {
+            return;
+        };
+        if let Some(conn) = self.play_history.as_mut() {
+            // Losing a play isn't worth stopping playback over.
+            if let Err(err) = record_play(conn, &Play { outcome, ..play }) {
+                warn!("failed to record play: {}", err);
+            }
+        }
+    }
+
+    /// Handles the current audio reaching its end: lets the output play out what it was given,
+    /// then moves on to the next queued audio, or stops until another audio is played.
+    fn end_audio(&mut self) {
+        self.finish_play(PlayOutcome::Completed);
+        if let Some(audio) = self.current_audio.take() {
+            self.emit(PlayerEvent::Finished(audio));
+        }
+        // The next audio is written to the same output, straight after the samples of this one.
+        if !self.play_next_queued() {
+            // Flushing pauses some outputs, so the next audio played opens a new one.
+            if let Some(mut audio_output) = self.audio_output.take() {
+                audio_output.flush();
+            }
+            self.stop();
+        }
+    }
+
+    /// Ends an audio that can't be read any further, so a broken file doesn't stop the queue.
+    fn read_failed(&mut self, err: symphonia::core::errors::Error) {
+        error!("read error: {}", err);
+        self.emit(PlayerEvent::ReadError(err.to_string()));
+        self.finish_play(PlayOutcome::Skipped);
+        self.end_audio();
+    }
+
+    /// Sends `event` to every subscriber, forgetting those that hung up.
+    fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Changes to the audio the queue advances to, skipping audios that can't be played.
+    /// Returns false if the queue ran out.
+    fn play_next_queued(&mut self) -> bool {
+        let attempts = self.queue.lock().unwrap().len();
+        for _ in 0..attempts {
+            let Some(item) = self.queue.lock().unwrap().advance().cloned() else {
+                return false;
+            };
+            let prepared = match self.prepared.take() {
+                Some((prepared_item, opened)) if prepared_item == item => opened,
+                _ => None,
+            };
+            match prepared.map_or_else(|| open_audio(&item.audio), Ok) {
+                Ok(opened) => {
+                    self.start_audio(Box::new(item.audio), opened, item.source);
+                    return true;
+                }
+                Err(err) => warn!("skipping queued audio: {}", err),
+            }
+        }
+        false
+    }
+
+    /// Opens the audio the queue advances to next, unless it's already open.
+    fn prepare_next_queued(&mut self) {
+        let upcoming = {
+            let queue = self.queue.lock().unwrap();
+            let Some(upcoming) = queue.upcoming() else {
+                self.prepared = None;
+                return;
+            };
+            if self
+                .prepared
+                .as_ref()
+                .is_some_and(|(prepared_item, _)| prepared_item == upcoming)
+            {
+                return;
+            }
+            upcoming.clone()
+        };
+        let opened = open_audio(&upcoming.audio)
+            .map_err(|err| warn!("can't open queued audio: {}", err))
+            .ok();
+        self.prepared = Some((upcoming, opened));
+    }
+
+    /// Stops at the end of the audio, until another audio is played.
+    fn stop(&mut self) {
+        self.format_reader = None;
+        self.decoder = None;
+        self.fading_out = None;
+        self.play = false;
+        self.current_audio = None;
+        let mut status = self.status.lock().unwrap();
+        status.audio = None;
+        status.position = Duration::ZERO;
+        status.duration = Duration::ZERO;
+        status.state = PlayerState::Stopped;
+    }
+}
+
+/// A format reader and decoder ready to play an audio from its start.
+struct OpenedAudio {
+    format_reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+}
+
+fn open_audio(audio: &AudioFile) -> symphonia::core::errors::Result<OpenedAudio> {
+    let mut format_reader = get_format_reader(audio)?;
+    let decoder = get_decoder(&mut format_reader)?;
+    let track = get_first_supported_track(format_reader.tracks()).unwrap();
+    let (track_id, time_base) = (track.id, track.codec_params.time_base);
+    Ok(OpenedAudio {
+        format_reader,
+        decoder,
+        track_id,
+        time_base,
+    })
+}
+
+/// An audio fading out, decoded alongside the audio fading in.
+struct FadingOut {
+    audio: OpenedAudio,
+    end_ts: Option<u64>,
+    /// Samples decoded but not mixed in yet, a queue per channel of the audio fading in.
+    pending: Vec<VecDeque<f32>>,
+    /// Converts samples to the spec of the audio fading in, created with the first samples.
+    resampler: Option<Resampler>,
+    /// Frames mixed in so far.
+    mixed_frames: u64,
+    /// Set once the audio has no more samples.
+    ended: bool,
+}
+
+impl FadingOut {
+    /// Decodes until there are `frames` samples pending per channel, or the audio ends.
+    fn decode(&mut self, spec: SignalSpec, frames: usize) {
+        self.pending
+            .resize_with(spec.channels.count(), VecDeque::new);
+        while !self.ended && self.pending[0].len() < frames {
+            let packet = match self.audio.format_reader.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => {
+                    self.ended = true;
+                    break;
+                }
+            };
+            if packet.track_id() != self.audio.track_id {
+                continue;
+            }
+            if self.end_ts.is_some_and(|end_ts| packet.ts() >= end_ts) {
+                self.ended = true;
+                break;
+            }
+            match self.audio.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let mut buffer = decoded.make_equivalent::<f32>();
+                    decoded.convert(&mut buffer);
+                    self.resampler
+                        .get_or_insert_with(|| Resampler::new(*buffer.spec(), spec))
+                        .process(&buffer, &mut self.pending);
+                }
+                Err(symphonia::core::errors::Error::DecodeError(err)) => {
+                    warn!("decode error: {}", err);
+                }
+                Err(_) => self.ended = true,
+            }
         }
     }
 }
 
+/// The time `position` after `offset`.
+fn offset_time(offset: time::Duration, position: Duration) -> Time {
+    let time = Duration::try_from(offset).unwrap_or_default() + position;
+    Time::new(time.as_secs(), time.subsec_nanos() as f64 / 1e9)
+}
+
 fn get_format_reader(audio: &AudioFile) -> symphonia::core::errors::Result<Box<dyn FormatReader>> {
     // Create a hint to help the format registry guess what format reader is appropriate.
     let mut hint = Hint::new();
@@ -256,3 +779,206 @@ fn get_first_supported_track(tracks: &[Track]) -> Option<&Track> {
         .iter()
         .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
 }
+
+#[cfg(test)]
+mod test_playback {
+    use super::Playback;
+    use crate::audio::crossfade::Crossfade;
+    use crate::audio::playback_manager::{AudioCommand, PlayerEvent, PlayerState, SeekTarget};
+    use crate::audio::queue::Queue;
+    use crate::audio::AudioFile;
+    use crate::database::plays::PlaySource;
+    use rstest::{fixture, rstest};
+    use std::path::Path;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use symphonia::core::audio::{AudioBuffer, Channels, Signal, SignalSpec};
+    use symphonia::core::errors::Error;
+
+    const TEST_AUDIO: &str = r"/../../test_media_files/audio/albums/album/test.mp3";
+
+    /// Playback of the test audio, which never reaches an audio output as no packets are played.
+    #[fixture]
+    fn playback() -> Playback {
+        let (_, receiver) = mpsc::channel();
+        let mut playback = Playback::new(receiver);
+        let audio_path = env!("CARGO_MANIFEST_DIR").to_owned() + TEST_AUDIO;
+        let audio = AudioFile::from_file(Path::new(&audio_path)).unwrap();
+        playback
+            .change_audio(Box::new(audio), PlaySource::Library)
+            .unwrap();
+        playback
+    }
+
+    #[rstest]
+    #[case(vec![SeekTarget::Position(Duration::from_millis(1500))], 1.5)]
+    #[case(vec![SeekTarget::Position(Duration::from_millis(1500)), SeekTarget::Forward(Duration::from_millis(250))], 1.75)]
+    #[case(vec![SeekTarget::Position(Duration::from_millis(1500)), SeekTarget::Backward(Duration::from_millis(501))], 0.999)]
+    #[case(vec![SeekTarget::Position(Duration::from_millis(1500)), SeekTarget::Backward(Duration::from_secs(2))], 0.0)]
+    #[case(vec![SeekTarget::Position(Duration::from_millis(1500)), SeekTarget::Forward(Duration::MAX), SeekTarget::Backward(Duration::from_secs(19))], 1.0)]
+    fn test_seek_to(
+        mut playback: Playback,
+        #[case] targets: Vec<SeekTarget>,
+        #[case] expected_seconds: f64,
+    ) {
+        let mut position = Duration::ZERO;
+        for target in targets {
+            position = playback.seek_to(target).unwrap();
+        }
+        assert!((position.as_secs_f64() - expected_seconds).abs() < 0.0001);
+        assert_eq!(playback.status.lock().unwrap().position, position);
+    }
+
+    #[rstest]
+    #[case(50.0, 0.5)]
+    #[case(-10.0, 0.0)]
+    fn test_seek_to_percentage(
+        mut playback: Playback,
+        #[case] percentage: f64,
+        #[case] fraction: f64,
+    ) {
+        let duration = playback.status.lock().unwrap().duration;
+        let position = playback
+            .seek_to(SeekTarget::Percentage(percentage))
+            .unwrap();
+        assert!((position.as_secs_f64() - duration.as_secs_f64() * fraction).abs() < 0.0001);
+    }
+
+    /// Queued audios follow on from each other, the next one opened ahead of time.
+    #[rstest]
+    fn test_end_audio_plays_next_queued(mut playback: Playback) {
+        let second = queue_next(&mut playback, "album");
+        playback.seek_to(SeekTarget::Percentage(95.0)).unwrap();
+        playback.prepare_next_queued();
+        assert!(playback
+            .prepared
+            .as_ref()
+            .is_some_and(|(item, opened)| item.audio == second && opened.is_some()));
+
+        playback.end_audio();
+        assert!(playback.prepared.is_none());
+        assert_eq!(playback.current_audio.as_deref(), Some(&second));
+        assert_eq!(playback.required_ts, 0);
+        assert_eq!(playback.status.lock().unwrap().state, PlayerState::Playing);
+
+        // The queue ran out.
+        playback.end_audio();
+        assert!(playback.current_audio.is_none());
+        assert_eq!(playback.status.lock().unwrap().state, PlayerState::Stopped);
+    }
+
+    /// An audio that can't be read any further ends early, and the queue moves on.
+    #[rstest]
+    fn test_read_error_plays_next_queued(mut playback: Playback) {
+        let second = queue_next(&mut playback, "album");
+        let (subscriber, events) = mpsc::channel();
+        playback.subscribers.push(subscriber);
+        playback.read_failed(Error::Unsupported("broken stream"));
+        assert!(matches!(events.try_recv(), Ok(PlayerEvent::ReadError(_))));
+        assert_eq!(playback.current_audio.as_deref(), Some(&second));
+        assert_eq!(playback.status.lock().unwrap().state, PlayerState::Playing);
+    }
+
+    #[rstest]
+    #[case(f64::NAN)]
+    #[case(f64::INFINITY)]
+    fn test_seek_to_bad_percentage(mut playback: Playback, #[case] percentage: f64) {
+        assert!(playback
+            .seek_to(SeekTarget::Percentage(percentage))
+            .is_err());
+    }
+
+    /// Playing an audio resumes playback, even after a stop or the queue running out.
+    #[rstest]
+    fn test_change_audio_after_stop_plays(mut playback: Playback) {
+        let audio = playback.current_audio.as_deref().unwrap().clone();
+        playback.handle_command(AudioCommand::Stop).unwrap();
+        assert_eq!(playback.status.lock().unwrap().state, PlayerState::Stopped);
+        playback
+            .handle_command(AudioCommand::ChangeAudio(
+                Box::new(audio),
+                PlaySource::Library,
+            ))
+            .unwrap();
+        assert!(playback.play);
+        assert_eq!(playback.status.lock().unwrap().state, PlayerState::Playing);
+    }
+
+    /// Queues another audio after the current one, with `album_name` as its album.
+    fn queue_next(playback: &mut Playback, album_name: &str) -> AudioFile {
+        let first = playback.current_audio.as_deref().unwrap().clone();
+        let second = AudioFile {
+            audio_title: String::from("Second"),
+            album_name: String::from(album_name),
+            ..first.clone()
+        };
+        let mut queue = playback.queue.lock().unwrap();
+        *queue = Queue::new();
+        queue.enqueue(vec![first, second.clone()], PlaySource::Queue);
+        queue.jump(0);
+        second
+    }
+
+    #[rstest]
+    #[case("Another album", true)]
+    #[case("album", false)]
+    fn test_crossfade(mut playback: Playback, #[case] album_name: &str, #[case] crossfades: bool) {
+        playback.current_audio.as_mut().unwrap().album_name = String::from("album");
+        let second = queue_next(&mut playback, album_name);
+        playback.crossfade = Crossfade {
+            duration: Duration::from_secs(2),
+            ..Crossfade::default()
+        };
+        playback.seek_to(SeekTarget::Percentage(90.0)).unwrap();
+        playback.prepare_next_queued();
+        playback.start_crossfade();
+        assert_eq!(playback.fading_out.is_some(), crossfades);
+        if !crossfades {
+            // The current audio carries on playing.
+            assert_ne!(playback.current_audio.as_deref(), Some(&second));
+            assert!(playback.format_reader.is_some() && playback.decoder.is_some());
+            return;
+        }
+        assert_eq!(playback.current_audio.as_deref(), Some(&second));
+
+        // The audio fading in starts silent, under the audio fading out.
+        let spec = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut buffer = AudioBuffer::<f32>::new(1152, spec);
+        buffer.render_reserved(Some(1152));
+        buffer.transform(|_| 2.0);
+        playback.mix_fading_out(&mut buffer);
+        assert!(buffer.chan(0)[0].abs() <= 1.0);
+        // Once the crossfade is over only the current audio plays.
+        while playback.fading_out.is_some() {
+            buffer.transform(|_| 2.0);
+            playback.mix_fading_out(&mut buffer);
+        }
+        buffer.transform(|_| 2.0);
+        playback.mix_fading_out(&mut buffer);
+        assert!(buffer.chan(1).iter().all(|&sample| sample == 2.0));
+    }
+
+    /// Audios of unknown length play to their end, without opening the next early.
+    #[rstest]
+    fn test_no_crossfade_without_duration(mut playback: Playback) {
+        queue_next(&mut playback, "Another album");
+        playback.crossfade = Crossfade {
+            duration: Duration::from_secs(2),
+            ..Crossfade::default()
+        };
+        playback.status.lock().unwrap().duration = Duration::ZERO;
+        playback.update_position(0);
+        assert!(playback.prepared.is_none());
+        assert!(playback.fading_out.is_none());
+        assert!(playback.format_reader.is_some());
+    }
+
+    #[rstest]
+    fn test_seek_without_audio() {
+        let (_, receiver) = mpsc::channel();
+        let mut playback = Playback::new(receiver);
+        assert!(playback
+            .seek_to(SeekTarget::Position(Duration::ZERO))
+            .is_err());
+    }
+}
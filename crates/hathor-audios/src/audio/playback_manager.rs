@@ -1,64 +1,488 @@
+use super::crossfade::Crossfade;
 use super::playback::do_play_loop;
+use super::queue::{Queue, QueueItem, RepeatMode};
 use super::AudioFile;
+use crate::database::plays::PlaySource;
 use eyre::Result;
+use log::error;
+use rusqlite::Connection;
 use std::error::Error;
 use std::sync::mpsc;
-use std::sync::mpsc::SendError;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// An [AudioCommand] along with where the playback thread sends its outcome.
+pub(crate) struct AudioRequest {
+    pub(crate) command: AudioCommand,
+    pub(crate) reply: Sender<eyre::Result<AudioReply>>,
+}
+
+/// What the playback thread replies with once a command is carried out.
+pub(crate) enum AudioReply {
+    Done,
+    /// The position reached by a seek.
+    Seeked(Duration),
+}
 
 pub enum AudioCommand {
-    ChangeAudio(Box<AudioFile>),
+    ChangeAudio(Box<AudioFile>, PlaySource),
     Pause,
     Play,
     ResetPlayback,
-    Seek(u64),
+    Seek(SeekTarget),
+    SetVolume(f32),
+    SetCrossfade(Crossfade),
+    Subscribe(Sender<PlayerEvent>),
+    Stop,
+    Shutdown,
+}
+
+/// Where in the current audio to seek to.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SeekTarget {
+    /// A position from the start of the audio.
+    Position(Duration),
+    /// Ahead of the current position.
+    Forward(Duration),
+    /// Back from the current position, stopping at the start.
+    Backward(Duration),
+    /// A percentage of the audio's length, from 0 to 100.
+    Percentage(f64),
+}
+
+/// Something that happened on the playback thread, see [AudioManager::subscribe].
+#[derive(PartialEq, Debug, Clone)]
+pub enum PlayerEvent {
+    /// Another audio started, whether played directly or from the queue.
+    AudioChanged(Box<AudioFile>),
+    /// Sent regularly while playing, with how far into the audio playback is.
+    Position(Duration),
+    Paused,
+    Resumed,
+    /// Playback moved to the position given.
+    Seeked(Duration),
+    /// The audio played to its end.
+    Finished(Box<AudioFile>),
+    /// A packet couldn't be decoded and was skipped, playback carries on.
+    DecodeWarning(String),
+    /// The audio couldn't be read any further, playback moves on to the next queued audio.
+    ReadError(String),
+    /// The audio output couldn't be opened or written to, playback is paused.
+    OutputError(String),
+}
+
+/// Whether anything is playing.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum PlayerState {
+    /// No audio, either none was played yet or the last one ended.
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// What the playback thread is doing, see [AudioManager::status].
+#[derive(PartialEq, Debug, Clone)]
+pub struct PlayerStatus {
+    pub audio: Option<AudioFile>,
+    /// How far into the audio playback is.
+    pub position: Duration,
+    /// Length of the audio.
+    pub duration: Duration,
+    /// From 0 for silence to 1 for full volume.
+    pub volume: f32,
+    pub state: PlayerState,
 }
 
+impl Default for PlayerStatus {
+    fn default() -> Self {
+        PlayerStatus {
+            audio: None,
+            position: Duration::ZERO,
+            duration: Duration::ZERO,
+            volume: 1.0,
+            state: PlayerState::default(),
+        }
+    }
+}
+
+/// How long commands wait for the playback thread unless [AudioManager::set_timeout] is used.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// High level struct to manage an audio thread.
 pub struct AudioManager {
-    send_to_playback_tx: Sender<AudioCommand>,
-    receive_from_playback_rx: Receiver<eyre::Result<()>>,
+    send_to_playback_tx: Sender<AudioRequest>,
+    /// How long to wait for the playback thread to carry out a command.
+    timeout: Duration,
+    /// Shared with the playback thread, which advances it whenever an audio ends.
+    queue: Arc<Mutex<Queue>>,
+    /// Kept up to date by the playback thread.
+    status: Arc<Mutex<PlayerStatus>>,
+    /// None once shut down.
+    playback_thread: Option<JoinHandle<eyre::Result<()>>>,
 }
 
 impl AudioManager {
     pub fn new() -> Self {
+        AudioManager::spawn(None)
+    }
+
+    /// Like [AudioManager::new], but every play is recorded in the play history of the DB,
+    /// see [plays](crate::database::plays).
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A connection the playback thread writes plays with.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    /// use hathor_audios::database::get_connection;
+    /// use std::path::Path;
+    ///
+    /// let conn = get_connection(Path::new("hathor.sqlite3")).unwrap();
+    /// let audio_manager = AudioManager::with_play_history(conn);
+    pub fn with_play_history(conn: Connection) -> Self {
+        AudioManager::spawn(Some(conn))
+    }
+
+    fn spawn(play_history: Option<Connection>) -> Self {
         let (sender_to_playback, receiver_from_audio_manager) = mpsc::channel();
-        let (sender_to_audio_manager, receiver_from_playback) = mpsc::channel();
-        thread::spawn(move || do_play_loop(receiver_from_audio_manager, sender_to_audio_manager));
+        let queue = Arc::new(Mutex::new(Queue::new()));
+        let playback_queue = Arc::clone(&queue);
+        let status = Arc::new(Mutex::new(PlayerStatus::default()));
+        let playback_status = Arc::clone(&status);
+        let playback_thread = thread::spawn(move || {
+            do_play_loop(
+                receiver_from_audio_manager,
+                play_history,
+                playback_queue,
+                playback_status,
+            )
+        });
         AudioManager {
             send_to_playback_tx: sender_to_playback,
-            receive_from_playback_rx: receiver_from_playback,
+            timeout: DEFAULT_TIMEOUT,
+            queue,
+            status,
+            playback_thread: Some(playback_thread),
         }
     }
 
+    /// Set how long commands wait for the playback thread before failing, 5 seconds by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Longest wait for each command.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    /// use std::time::Duration;
+    ///
+    /// let mut audio_manager = AudioManager::new();
+    /// audio_manager.set_timeout(Duration::from_millis(500));
+    /// if let Err(err) = audio_manager.pause() {
+    ///     println!("couldn't pause: {}", err);
+    /// }
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
     /// Pause audio playback.
     pub fn pause(&self) -> Result<(), Box<dyn Error>> {
-        self.send_to_playback_tx.send(AudioCommand::Pause)?;
-        self.receive_from_playback_rx.recv()?.unwrap();
-        Ok(())
+        self.command(AudioCommand::Pause)
     }
 
     /// Continue/start audio playback.
-    pub fn play(&self) -> Result<(), SendError<AudioCommand>> {
-        self.send_to_playback_tx.send(AudioCommand::Play)
+    pub fn play(&self) -> Result<(), Box<dyn Error>> {
+        self.command(AudioCommand::Play)
     }
 
-    /// Go to the given timestamp in the current audio file.
-    pub fn seek(&self, seek_ts_seconds: u64) -> Result<(), SendError<AudioCommand>> {
-        self.send_to_playback_tx
-            .send(AudioCommand::Seek(seek_ts_seconds))
+    /// Go to `target` in the current audio file, positions past its end go to the end.
+    /// Returns the position reached, accurate to the audio's sample rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Where to go.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::{AudioManager, SeekTarget};
+    /// use std::time::Duration;
+    ///
+    /// let audio_manager = AudioManager::new();
+    /// audio_manager.seek(SeekTarget::Position(Duration::from_millis(61_250))).unwrap();
+    /// let position = audio_manager.seek(SeekTarget::Backward(Duration::from_secs(10))).unwrap();
+    /// println!("now at {:?}", position);
+    pub fn seek(&self, target: SeekTarget) -> Result<Duration, Box<dyn Error>> {
+        match self.request(AudioCommand::Seek(target))? {
+            AudioReply::Seeked(position) => Ok(position),
+            AudioReply::Done => Err("the playback thread didn't seek".into()),
+        }
     }
 
     /// Go to the start of the current audio file.
-    pub fn reset_playback(&self) -> Result<(), SendError<AudioCommand>> {
-        self.send_to_playback_tx.send(AudioCommand::Seek(0))
+    pub fn reset_playback(&self) -> Result<(), Box<dyn Error>> {
+        self.command(AudioCommand::ResetPlayback)
     }
 
     /// Change the audio to another track.
-    pub fn change_audio(&self, audio: Box<AudioFile>) -> Result<(), SendError<AudioCommand>> {
+    /// Fails if the audio's file can't be opened or decoded, leaving the previous audio playing.
+    pub fn change_audio(&self, audio: Box<AudioFile>) -> Result<(), Box<dyn Error>> {
+        self.change_audio_from(audio, PlaySource::Library)
+    }
+
+    /// Change the audio to another track, played from `source` as far as the play history goes.
+    pub fn change_audio_from(
+        &self,
+        audio: Box<AudioFile>,
+        source: PlaySource,
+    ) -> Result<(), Box<dyn Error>> {
+        self.command(AudioCommand::ChangeAudio(audio, source))
+    }
+
+    /// Stop playback, the current audio is dropped rather than paused.
+    pub fn stop(&self) -> Result<(), Box<dyn Error>> {
+        self.command(AudioCommand::Stop)
+    }
+
+    /// Stop playback and end the playback thread once the audio output has played out, after
+    /// which every command fails. Dropping the AudioManager does the same, but can only log
+    /// errors.
+    /// Returns the error playback ended with, if it ended with one before being shut down.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    ///
+    /// let mut audio_manager = AudioManager::new();
+    /// if let Err(err) = audio_manager.shutdown() {
+    ///     println!("playback failed: {}", err);
+    /// }
+    pub fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(playback_thread) = self.playback_thread.take() else {
+            return Ok(());
+        };
+        // The thread may have already ended, in which case joining it tells us why.
+        let (reply, _) = mpsc::channel();
+        let _ = self.send_to_playback_tx.send(AudioRequest {
+            command: AudioCommand::Shutdown,
+            reply,
+        });
+        match playback_thread.join() {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(err.to_string().into()),
+            Err(_) => Err("the playback thread panicked".into()),
+        }
+    }
+
+    /// Set the volume, from 0 for silence to 1 for full volume.
+    /// Volumes out of that range are clamped to it, those that aren't finite are refused.
+    pub fn set_volume(&self, volume: f32) -> Result<(), Box<dyn Error>> {
+        if !volume.is_finite() {
+            return Err(format!("can't set the volume to {}", volume).into());
+        }
+        self.command(AudioCommand::SetVolume(volume.clamp(0.0, 1.0)))
+    }
+
+    /// Choose how queued audios crossfade into each other, they don't by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `crossfade` - Length of the crossfades and their rules.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::crossfade::{Crossfade, FadeCurve};
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    /// use std::time::Duration;
+    ///
+    /// let audio_manager = AudioManager::new();
+    /// let crossfade = Crossfade {
+    ///     duration: Duration::from_secs(6),
+    ///     curve: FadeCurve::Linear,
+    ///     album_aware: true,
+    /// };
+    /// audio_manager.set_crossfade(crossfade).unwrap();
+    pub fn set_crossfade(&self, crossfade: Crossfade) -> Result<(), Box<dyn Error>> {
+        self.command(AudioCommand::SetCrossfade(crossfade))
+    }
+
+    /// What is playing, how far into it and whether it's paused.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    ///
+    /// let audio_manager = AudioManager::new();
+    /// let status = audio_manager.status();
+    /// if let Some(audio) = status.audio {
+    ///     println!("{} {:?}/{:?}", audio.audio_title, status.position, status.duration);
+    /// }
+    pub fn status(&self) -> PlayerStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Receive every [PlayerEvent] from now on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::{AudioManager, PlayerEvent};
+    ///
+    /// let audio_manager = AudioManager::new();
+    /// let events = audio_manager.subscribe().unwrap();
+    /// for event in events {
+    ///     match event {
+    ///         PlayerEvent::Position(position) => println!("{:?}", position),
+    ///         PlayerEvent::Finished(audio) => println!("{} finished", audio.audio_title),
+    ///         _ => {}
+    ///     }
+    /// }
+    pub fn subscribe(&self) -> Result<Receiver<PlayerEvent>, Box<dyn Error>> {
+        let (subscriber, events) = mpsc::channel();
+        self.command(AudioCommand::Subscribe(subscriber))?;
+        Ok(events)
+    }
+
+    /// A snapshot of the play queue.
+    pub fn queue(&self) -> Queue {
+        self.queue.lock().unwrap().clone()
+    }
+
+    /// Add audios to the end of the queue.
+    pub fn enqueue(&self, audios: Vec<AudioFile>) {
+        self.enqueue_from(audios, PlaySource::Queue)
+    }
+
+    /// Add audios to the end of the queue, played from `source` as far as the play history goes.
+    ///
+    /// # Arguments
+    ///
+    /// * `audios` - The audios to queue, in order.
+    /// * `source` - What the audios were queued from.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::playback_manager::AudioManager;
+    /// use hathor_audios::database::get_connection;
+    /// use hathor_audios::database::listing::ListOptions;
+    /// use hathor_audios::database::plays::PlaySource;
+    /// use hathor_audios::database::playlists::get_playlist_items;
+    /// use std::path::Path;
+    ///
+    /// let mut conn = get_connection(Path::new("hathor.sqlite3")).unwrap();
+    /// let audios = get_playlist_items(&mut conn, "Bristol", &ListOptions::default())
+    ///     .unwrap()
+    ///     .items
+    ///     .into_iter()
+    ///     .filter_map(|item| item.audio)
+    ///     .collect();
+    /// let audio_manager = AudioManager::new();
+    /// audio_manager.enqueue_from(audios, PlaySource::Playlist(String::from("Bristol")));
+    /// audio_manager.next().unwrap();
+    pub fn enqueue_from(&self, audios: Vec<AudioFile>, source: PlaySource) {
+        self.queue.lock().unwrap().enqueue(audios, source);
+    }
+
+    /// Add audios to the queue right after the current audio.
+    pub fn enqueue_next(&self, audios: Vec<AudioFile>, source: PlaySource) {
+        self.queue.lock().unwrap().enqueue_next(audios, source);
+    }
+
+    /// Remove the audio at `index` from the queue. Removing the current audio doesn't stop it.
+    pub fn remove_from_queue(&self, index: usize) -> Option<QueueItem> {
+        self.queue.lock().unwrap().remove(index)
+    }
+
+    /// Move the audio at `from` in the queue to `to`.
+    pub fn move_in_queue(&self, from: usize, to: usize) -> Result<(), Box<dyn Error>> {
+        self.queue.lock().unwrap().move_item(from, to)
+    }
+
+    /// Remove every audio from the queue. The current audio keeps playing.
+    pub fn clear_queue(&self) {
+        self.queue.lock().unwrap().clear();
+    }
+
+    /// Play the audio at `index` in the queue.
+    pub fn jump_to(&self, index: usize) -> Result<QueueItem, Box<dyn Error>> {
+        let item = self.queue.lock().unwrap().jump(index).cloned();
+        let item = item.ok_or_else(|| format!("queue has no item {}", index))?;
+        self.play_queue_item(&item)?;
+        Ok(item)
+    }
+
+    /// Play the next audio in the queue, returning it.
+    /// Returns None at the end of the queue, leaving the current audio playing.
+    pub fn next(&self) -> Result<Option<QueueItem>, Box<dyn Error>> {
+        let item = self.queue.lock().unwrap().next().cloned();
+        if let Some(item) = &item {
+            self.play_queue_item(item)?;
+        }
+        Ok(item)
+    }
+
+    /// Play the previous audio in the queue, returning it.
+    /// At the start of the queue the current audio restarts instead and None is returned.
+    pub fn previous(&self) -> Result<Option<QueueItem>, Box<dyn Error>> {
+        let item = self.queue.lock().unwrap().previous().cloned();
+        match &item {
+            Some(item) => self.play_queue_item(item)?,
+            None => self.reset_playback()?,
+        }
+        Ok(item)
+    }
+
+    /// Shuffle the queue, or put it back in the order audios were queued in.
+    pub fn set_shuffle(&self, shuffle: bool) {
+        let mut queue = self.queue.lock().unwrap();
+        if shuffle {
+            queue.shuffle();
+        } else {
+            queue.unshuffle();
+        }
+    }
+
+    /// Choose what happens when the current audio ends.
+    pub fn set_repeat(&self, repeat: RepeatMode) {
+        self.queue.lock().unwrap().set_repeat(repeat);
+    }
+
+    fn play_queue_item(&self, item: &QueueItem) -> Result<(), Box<dyn Error>> {
+        self.change_audio_from(Box::new(item.audio.clone()), item.source.clone())?;
+        self.play()
+    }
+
+    /// Sends `command` to the playback thread, then waits for it to be carried out.
+    fn command(&self, command: AudioCommand) -> Result<(), Box<dyn Error>> {
+        self.request(command).map(|_| ())
+    }
+
+    /// Sends `command` to the playback thread, then waits for its outcome.
+    fn request(&self, command: AudioCommand) -> Result<AudioReply, Box<dyn Error>> {
+        let (reply, outcome) = mpsc::channel();
         self.send_to_playback_tx
-            .send(AudioCommand::ChangeAudio(audio))
+            .send(AudioRequest { command, reply })
+            .map_err(|_| "the playback thread has ended")?;
+        match outcome.recv_timeout(self.timeout) {
+            Ok(outcome) => Ok(outcome.map_err(|err| err.to_string())?),
+            Err(RecvTimeoutError::Timeout) => {
+                Err(format!("the playback thread didn't reply within {:?}", self.timeout).into())
+            }
+            Err(RecvTimeoutError::Disconnected) => Err("the playback thread has ended".into()),
+        }
     }
 }
 
@@ -67,3 +491,63 @@ impl Default for AudioManager {
         Self::new()
     }
 }
+
+impl Drop for AudioManager {
+    fn drop(&mut self) {
+        if let Err(err) = self.shutdown() {
+            error!("audio playback ended with an error: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_audio_manager {
+    use super::{AudioManager, PlayerState};
+    use crate::audio::AudioFile;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    #[rstest]
+    fn test_change_audio_reports_failure() {
+        let audio_manager = AudioManager::new();
+        let audio = AudioFile {
+            audio_path: PathBuf::from("does/not/exist.mp3"),
+            ..AudioFile::default()
+        };
+        assert!(audio_manager.change_audio(Box::new(audio)).is_err());
+        // The playback thread is still there to take later commands.
+        audio_manager.pause().unwrap();
+        audio_manager.play().unwrap();
+        assert_eq!(audio_manager.status().state, PlayerState::Stopped);
+    }
+
+    #[rstest]
+    #[case(0.25, 0.25)]
+    #[case(2.0, 1.0)]
+    #[case(-1.0, 0.0)]
+    fn test_set_volume(#[case] volume: f32, #[case] expected: f32) {
+        let audio_manager = AudioManager::new();
+        audio_manager.set_volume(volume).unwrap();
+        // Replies come once the command is carried out, so the status is already updated.
+        assert_eq!(audio_manager.status().volume, expected);
+    }
+
+    #[rstest]
+    #[case(f32::NAN)]
+    #[case(f32::INFINITY)]
+    fn test_set_bad_volume(#[case] volume: f32) {
+        let audio_manager = AudioManager::new();
+        assert!(audio_manager.set_volume(volume).is_err());
+        assert_eq!(audio_manager.status().volume, 1.0);
+    }
+
+    #[rstest]
+    fn test_shutdown() {
+        let mut audio_manager = AudioManager::new();
+        audio_manager.stop().unwrap();
+        audio_manager.shutdown().unwrap();
+        assert!(audio_manager.play().is_err());
+        // Shutting down again, including on drop, does nothing.
+        audio_manager.shutdown().unwrap();
+    }
+}
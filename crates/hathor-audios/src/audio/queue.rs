@@ -0,0 +1,495 @@
+//! The play queue, what plays after the current audio.
+//!
+//! An [AudioManager](super::playback_manager::AudioManager) shares its queue with the playback
+//! thread, which moves on to the next item whenever an audio ends.
+
+use super::AudioFile;
+use crate::database::plays::PlaySource;
+use rand::seq::SliceRandom;
+use std::error::Error;
+
+/// What happens when the current audio of a queue ends.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum RepeatMode {
+    /// Stop after the last item.
+    #[default]
+    Off,
+    /// Play the current item again.
+    One,
+    /// Go back to the first item after the last.
+    All,
+}
+
+/// An audio in a queue, with what it was queued from for the play history.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QueueItem {
+    pub audio: AudioFile,
+    pub source: PlaySource,
+    /// Identifies the item across shuffles, items can be queued more than once.
+    id: u64,
+}
+
+/// Audios to play in order, along with which of them is current.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Queue {
+    /// Items in play order, shuffled or not.
+    items: Vec<QueueItem>,
+    /// Index into items of the current item, None before the first item plays.
+    current: Option<usize>,
+    /// Item ids in the order they were queued, Some while shuffled.
+    unshuffled: Option<Vec<u64>>,
+    repeat: RepeatMode,
+    next_id: u64,
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Queue::default()
+    }
+
+    /// Items in play order.
+    pub fn items(&self) -> &[QueueItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&QueueItem> {
+        self.items.get(self.current?)
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.unshuffled.is_some()
+    }
+
+    /// Adds audios to the end of the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `audios` - The audios to queue, in order.
+    /// * `source` - What the audios were queued from, e.g. their album.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::queue::Queue;
+    /// use hathor_audios::audio::AudioFile;
+    /// use hathor_audios::database::plays::PlaySource;
+    /// use std::path::Path;
+    ///
+    /// let mut queue = Queue::new();
+    /// let audio = AudioFile::from_file(Path::new("Portishead/Dummy/01 Mysterons.mp3")).unwrap();
+    /// queue.enqueue(vec![audio], PlaySource::Queue);
+    pub fn enqueue(&mut self, audios: Vec<AudioFile>, source: PlaySource) {
+        let items = self.new_items(audios, source);
+        if let Some(unshuffled) = self.unshuffled.as_mut() {
+            unshuffled.extend(items.iter().map(|item| item.id));
+        }
+        self.items.extend(items);
+    }
+
+    /// Adds audios right after the current item, or at the start if nothing played yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `audios` - The audios to queue, in order.
+    /// * `source` - What the audios were queued from, e.g. their album.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::queue::Queue;
+    /// use hathor_audios::audio::AudioFile;
+    /// use hathor_audios::database::plays::PlaySource;
+    /// use std::path::Path;
+    ///
+    /// let mut queue = Queue::new();
+    /// let audio = AudioFile::from_file(Path::new("Portishead/Dummy/02 Sour Times.mp3")).unwrap();
+    /// queue.enqueue_next(vec![audio], PlaySource::Album(String::from("Dummy")));
+    pub fn enqueue_next(&mut self, audios: Vec<AudioFile>, source: PlaySource) {
+        let items = self.new_items(audios, source);
+        let current_id = self.current().map(|item| item.id);
+        if let Some(unshuffled) = self.unshuffled.as_mut() {
+            let at = current_id
+                .and_then(|id| unshuffled.iter().position(|&other| other == id))
+                .map_or(0, |position| position + 1);
+            unshuffled.splice(at..at, items.iter().map(|item| item.id));
+        }
+        let at = self.current.map_or(0, |current| current + 1);
+        self.items.splice(at..at, items);
+    }
+
+    /// Removes the item at `index`, returning it if there was one.
+    /// Removing the current item makes the item before it current, so the next item still
+    /// plays next.
+    pub fn remove(&mut self, index: usize) -> Option<QueueItem> {
+        if index >= self.items.len() {
+            return None;
+        }
+        let item = self.items.remove(index);
+        if let Some(unshuffled) = self.unshuffled.as_mut() {
+            unshuffled.retain(|&id| id != item.id);
+        }
+        self.current = match self.current {
+            Some(current) if current >= index => current.checked_sub(1),
+            current => current,
+        };
+        Some(item)
+    }
+
+    /// Moves the item at `from` to `to`, the current item stays current.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - Index of the item to move.
+    /// * `to` - Index of the item once moved.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::queue::Queue;
+    ///
+    /// let mut queue = Queue::new();
+    /// // Play the last item next.
+    /// let current = queue.current_index().unwrap();
+    /// queue.move_item(queue.len() - 1, current + 1).unwrap();
+    pub fn move_item(&mut self, from: usize, to: usize) -> Result<(), Box<dyn Error>> {
+        if from >= self.items.len() || to >= self.items.len() {
+            return Err(format!("queue has no item {}", from.max(to)).into());
+        }
+        let current_id = self.current().map(|item| item.id);
+        let item = self.items.remove(from);
+        self.items.insert(to, item);
+        self.current = current_id.and_then(|id| self.position_of(id));
+        Ok(())
+    }
+
+    /// Removes every item, shuffle and repeat are kept.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.current = None;
+        if let Some(unshuffled) = self.unshuffled.as_mut() {
+            unshuffled.clear();
+        }
+    }
+
+    /// Makes the item at `index` current, returning it if there was one.
+    pub fn jump(&mut self, index: usize) -> Option<&QueueItem> {
+        if index >= self.items.len() {
+            return None;
+        }
+        self.current = Some(index);
+        self.current()
+    }
+
+    /// Moves on to the next item, wrapping around if repeating all.
+    /// Returns None at the end of the queue, where the last item stays current.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::queue::Queue;
+    ///
+    /// let mut queue = Queue::new();
+    /// while let Some(item) = queue.next() {
+    ///     println!("{}", item.audio.audio_title);
+    /// }
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&QueueItem> {
+        self.current = Some(self.next_index()?);
+        self.current()
+    }
+
+    /// Moves back to the previous item, wrapping around if repeating all.
+    /// Returns None at the start of the queue, where the first item stays current.
+    pub fn previous(&mut self) -> Option<&QueueItem> {
+        match self.current {
+            Some(current) if current > 0 => self.current = Some(current - 1),
+            Some(_) if self.repeat == RepeatMode::All => self.current = Some(self.items.len() - 1),
+            _ => return None,
+        }
+        self.current()
+    }
+
+    /// The item to play once the current one ends: the current item again if repeating one,
+    /// else the [next](Queue::next).
+    pub fn advance(&mut self) -> Option<&QueueItem> {
+        self.current = Some(self.upcoming_index()?);
+        self.current()
+    }
+
+    /// The item [advance](Queue::advance) would move to, without moving to it.
+    pub fn upcoming(&self) -> Option<&QueueItem> {
+        self.items.get(self.upcoming_index()?)
+    }
+
+    fn upcoming_index(&self) -> Option<usize> {
+        if self.repeat == RepeatMode::One && self.current().is_some() {
+            return self.current;
+        }
+        self.next_index()
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        let next = self.current.map_or(0, |current| current + 1);
+        if next < self.items.len() {
+            Some(next)
+        } else if self.repeat == RepeatMode::All && !self.items.is_empty() {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Shuffles the items, the current item moves to the start so everything else plays after
+    /// it. Shuffling again reshuffles, [unshuffle](Queue::unshuffle) still goes back to the
+    /// order items were queued in.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hathor_audios::audio::queue::Queue;
+    ///
+    /// let mut queue = Queue::new();
+    /// queue.shuffle();
+    /// queue.unshuffle();
+    pub fn shuffle(&mut self) {
+        if self.unshuffled.is_none() {
+            self.unshuffled = Some(self.items.iter().map(|item| item.id).collect());
+        }
+        let current = self.current.map(|current| self.items.remove(current));
+        self.items.shuffle(&mut rand::thread_rng());
+        if let Some(current) = current {
+            self.items.insert(0, current);
+            self.current = Some(0);
+        }
+    }
+
+    /// Puts the items back in the order they were queued in, the current item stays current.
+    pub fn unshuffle(&mut self) {
+        let Some(unshuffled) = self.unshuffled.take() else {
+            return;
+        };
+        let current_id = self.current().map(|item| item.id);
+        let mut items = std::mem::take(&mut self.items);
+        self.items = unshuffled
+            .into_iter()
+            .filter_map(|id| {
+                let position = items.iter().position(|item| item.id == id)?;
+                Some(items.swap_remove(position))
+            })
+            .collect();
+        self.current = current_id.and_then(|id| self.position_of(id));
+    }
+
+    fn new_items(&mut self, audios: Vec<AudioFile>, source: PlaySource) -> Vec<QueueItem> {
+        audios
+            .into_iter()
+            .map(|audio| {
+                self.next_id += 1;
+                QueueItem {
+                    audio,
+                    source: source.clone(),
+                    id: self.next_id,
+                }
+            })
+            .collect()
+    }
+
+    fn position_of(&self, id: u64) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+}
+
+#[cfg(test)]
+mod test_queue {
+    use super::{Queue, RepeatMode};
+    use crate::audio::AudioFile;
+    use crate::database::plays::PlaySource;
+    use rstest::{fixture, rstest};
+
+    fn audios(titles: &[&str]) -> Vec<AudioFile> {
+        titles
+            .iter()
+            .map(|title| AudioFile {
+                audio_title: title.to_string(),
+                ..AudioFile::default()
+            })
+            .collect()
+    }
+
+    fn titles(queue: &Queue) -> Vec<&str> {
+        queue
+            .items()
+            .iter()
+            .map(|item| item.audio.audio_title.as_str())
+            .collect()
+    }
+
+    fn current_title(queue: &Queue) -> Option<&str> {
+        queue.current().map(|item| item.audio.audio_title.as_str())
+    }
+
+    /// Five audios with the second one current.
+    #[fixture]
+    fn queue() -> Queue {
+        let mut queue = Queue::new();
+        queue.enqueue(audios(&["a", "b", "c", "d", "e"]), PlaySource::Queue);
+        queue.jump(1);
+        queue
+    }
+
+    #[rstest]
+    fn test_enqueue_next(mut queue: Queue) {
+        queue.enqueue_next(
+            audios(&["x", "y"]),
+            PlaySource::Album(String::from("Dummy")),
+        );
+        assert_eq!(titles(&queue), ["a", "b", "x", "y", "c", "d", "e"]);
+        assert_eq!(queue.next().unwrap().audio.audio_title, "x");
+        assert_eq!(
+            queue.current().unwrap().source,
+            PlaySource::Album(String::from("Dummy"))
+        );
+
+        let mut empty = Queue::new();
+        empty.enqueue_next(audios(&["x"]), PlaySource::Queue);
+        assert_eq!(current_title(&empty), None);
+        assert_eq!(empty.next().unwrap().audio.audio_title, "x");
+    }
+
+    #[rstest]
+    #[case(0, vec!["b", "c", "d", "e"], Some("b"))]
+    #[case(1, vec!["a", "c", "d", "e"], Some("a"))]
+    #[case(3, vec!["a", "b", "c", "e"], Some("b"))]
+    fn test_remove(
+        mut queue: Queue,
+        #[case] index: usize,
+        #[case] expected: Vec<&str>,
+        #[case] current: Option<&str>,
+    ) {
+        queue.remove(index).unwrap();
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(current_title(&queue), current);
+        // What was going to play next still does.
+        assert_eq!(queue.next().unwrap().audio.audio_title, "c");
+        assert!(queue.remove(10).is_none());
+    }
+
+    #[rstest]
+    #[case(4, 0, vec!["e", "a", "b", "c", "d"])]
+    #[case(1, 3, vec!["a", "c", "d", "b", "e"])]
+    #[case(0, 2, vec!["b", "c", "a", "d", "e"])]
+    fn test_move_item(
+        mut queue: Queue,
+        #[case] from: usize,
+        #[case] to: usize,
+        #[case] expected: Vec<&str>,
+    ) {
+        queue.move_item(from, to).unwrap();
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(current_title(&queue), Some("b"));
+        assert!(queue.move_item(0, 5).is_err());
+    }
+
+    #[rstest]
+    #[case(RepeatMode::Off, vec![Some("c"), Some("d"), Some("e"), None, None])]
+    #[case(RepeatMode::One, vec![Some("b"), Some("b"), Some("b"), Some("b"), Some("b")])]
+    #[case(RepeatMode::All, vec![Some("c"), Some("d"), Some("e"), Some("a"), Some("b")])]
+    fn test_advance(
+        mut queue: Queue,
+        #[case] repeat: RepeatMode,
+        #[case] expected: Vec<Option<&str>>,
+    ) {
+        queue.set_repeat(repeat);
+        let played = (0..5)
+            .map(|_| {
+                let upcoming = queue.upcoming().cloned();
+                let item = queue.advance().cloned();
+                assert_eq!(upcoming, item);
+                item.map(|item| item.audio.audio_title)
+            })
+            .collect::<Vec<Option<String>>>();
+        assert_eq!(
+            played.iter().map(Option::as_deref).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(RepeatMode::One, Some("c"), None)]
+    #[case(RepeatMode::All, Some("c"), Some("e"))]
+    fn test_next_and_previous(
+        mut queue: Queue,
+        #[case] repeat: RepeatMode,
+        #[case] next: Option<&str>,
+        #[case] before_first: Option<&str>,
+    ) {
+        queue.set_repeat(repeat);
+        assert_eq!(
+            queue.next().map(|item| item.audio.audio_title.as_str()),
+            next
+        );
+        queue.jump(0);
+        assert_eq!(
+            queue.previous().map(|item| item.audio.audio_title.as_str()),
+            before_first
+        );
+    }
+
+    #[rstest]
+    fn test_shuffle_then_unshuffle(mut queue: Queue) {
+        queue.shuffle();
+        assert!(queue.is_shuffled());
+        assert_eq!(queue.current_index(), Some(0));
+        assert_eq!(current_title(&queue), Some("b"));
+        let mut shuffled = titles(&queue);
+        shuffled.sort();
+        assert_eq!(shuffled, ["a", "b", "c", "d", "e"]);
+
+        // Changes while shuffled are kept once unshuffled.
+        queue.shuffle();
+        queue.enqueue(audios(&["f"]), PlaySource::Queue);
+        queue.enqueue_next(audios(&["x"]), PlaySource::Queue);
+        let removed = queue
+            .items()
+            .iter()
+            .position(|item| item.audio.audio_title == "d");
+        queue.remove(removed.unwrap());
+        queue.next();
+        queue.unshuffle();
+        assert!(!queue.is_shuffled());
+        assert_eq!(titles(&queue), ["a", "b", "x", "c", "e", "f"]);
+        assert_eq!(current_title(&queue), Some("x"));
+    }
+
+    #[rstest]
+    fn test_clear(mut queue: Queue) {
+        queue.set_repeat(RepeatMode::All);
+        queue.shuffle();
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.current_index(), None);
+        assert!(queue.next().is_none());
+        assert!(queue.is_shuffled());
+        assert_eq!(queue.repeat(), RepeatMode::All);
+    }
+}
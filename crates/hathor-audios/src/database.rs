@@ -1,16 +1,30 @@
+pub mod albums;
+pub mod artists;
 pub mod audio_files;
+pub mod fuzzy_index;
 pub(crate) mod initialise_db;
+pub mod listing;
+pub(crate) mod migrations;
+pub(crate) mod os_paths;
 pub mod playlists;
+pub mod plays;
+pub mod query;
+pub mod search;
+pub mod smart_playlists;
+pub mod unicode_folding;
+pub mod user_data;
 pub mod user_media_folders;
 
 use blake3::Hash;
 use initialise_db::init_db;
+use os_paths::path_from_bytes;
+use rusqlite::types::Type;
 use rusqlite::Connection;
 use rusqlite::Params;
 use rusqlite::Result;
+use rusqlite::Row;
 use std::error::Error;
-use std::path::Path;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use time::Duration;
 
@@ -19,9 +33,11 @@ use crate::audio::AudioFile;
 const INSERT_BATCH_SIZE: u16 = 64;
 
 /// Connects to SQL database and initialises Hathor tables if needed.
+/// Triggers and album views call SQL functions registered on the connection here,
+/// so other connections (e.g. the sqlite3 shell) can read audios but not change them or list albums.
 pub fn get_connection(db_path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
-    let conn = Connection::open(db_path)?;
-    init_db(&conn)?;
+    let mut conn = Connection::open(db_path)?;
+    init_db(&mut conn)?;
     Ok(conn)
 }
 
@@ -35,25 +51,53 @@ where
 {
     Ok(conn
         .prepare(sql)?
-        .query_map(parameters, |row| {
-            let audio_path = PathBuf::from_str(&row.get::<_, String>(7)?).unwrap();
-            let img_path = PathBuf::from_str(&row.get::<_, String>(8)?).ok();
-            Ok(AudioFile {
-                file_hash: Hash::from_str(&row.get::<_, String>(0)?).unwrap(),
-                audio_title: row.get(1)?,
-                album_name: row.get(2)?,
-                artist_name: row.get(3)?,
-                track_num: row.get(4)?,
-                release_year: row.get(5)?,
-                audio_length: Duration::seconds(row.get(6)?),
-                audio_path,
-                img_path,
-            })
-        })?
+        .query_map(parameters, audio_select_result_to_audiofile)?
         .filter_map(|v| v.ok())
         .collect())
 }
 
+/// Builds an [AudioFile](crate::audio::AudioFile) from a row of the standard audio select.
+/// Columns are read by name, so selects may add extra columns in any order.
+/// Paths are read back from their raw OS bytes, so they round-trip exactly.
+/// Paths stored relative to a media root are joined back onto the root's current location.
+pub(crate) fn audio_select_result_to_audiofile(row: &Row) -> Result<AudioFile, rusqlite::Error> {
+    let file_hash = Hash::from_str(&row.get::<&str, String>("file_hash")?)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))?;
+    let folder_path = row.get::<&str, Option<Vec<u8>>>("folder_path")?;
+    let img_folder_path = row.get::<&str, Option<Vec<u8>>>("img_folder_path")?;
+    Ok(AudioFile {
+        file_hash,
+        audio_title: row.get("audio_title")?,
+        album_name: row.get("album_name")?,
+        artist_name: row.get("artist_name")?,
+        album_artist_name: row.get("album_artist_name")?,
+        genre: row.get("genre")?,
+        composer: row.get("composer")?,
+        lyrics: row.get("lyrics")?,
+        track_num: row.get("track_num")?,
+        disc_num: row.get("disc_num")?,
+        release_year: row.get("release_year")?,
+        audio_length: Duration::seconds(row.get::<&str, i64>("audio_length_seconds")?),
+        audio_path: stored_path_to_absolute(folder_path, row.get("audio_path")?),
+        img_path: row
+            .get::<&str, Option<Vec<u8>>>("img_path")?
+            .map(|img_path| stored_path_to_absolute(img_folder_path, img_path)),
+        start_offset: Duration::nanoseconds(row.get("start_offset_ns")?),
+        end_offset: row
+            .get::<&str, Option<i64>>("end_offset_ns")?
+            .map(Duration::nanoseconds),
+    })
+}
+
+/// Reads a path back from its raw OS bytes,
+/// joining it onto its media root if it is stored relative to one.
+pub(crate) fn stored_path_to_absolute(folder_path: Option<Vec<u8>>, path: Vec<u8>) -> PathBuf {
+    match folder_path {
+        Some(folder_path) => path_from_bytes(folder_path).join(path_from_bytes(path)),
+        None => path_from_bytes(path),
+    }
+}
+
 #[cfg(test)]
 mod test_db_operations {
     use crate::database::get_connection;
@@ -0,0 +1,332 @@
+use crate::audio::AudioFile;
+use crate::database::listing::{query_page, query_rows_page, ListOptions, OrderKey, Page};
+use crate::database::query::{Field, SortTerm};
+use crate::database::stored_path_to_absolute;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{named_params, Connection, Row};
+use std::error::Error;
+use std::path::PathBuf;
+use time::Duration;
+
+/// An album and totals over its tracks.
+/// Albums are keyed by album artist, album name and release year, ignoring case and diacritics.
+/// Tracks without an album artist count under their artist.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Album {
+    pub album_artist_name: String,
+    pub album_name: String,
+    pub release_year: u16,
+    pub track_count: u32,
+    pub disc_count: u32,
+    pub audio_length: Duration,
+    /// Cover of the first track that has one.
+    pub img_path: Option<PathBuf>,
+}
+
+/// Retrieve a page of the albums, ordered by album artist, year and name
+/// unless `options` sorts them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
+///   Albums can be sorted by album artist, album, year, date added and length.
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::albums::list_albums;
+/// use hathor_audios::database::listing::ListOptions;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let page = list_albums(&mut conn, &ListOptions::default());
+pub fn list_albums(
+    conn: &mut Connection,
+    options: &ListOptions,
+) -> Result<Page<Album>, Box<dyn Error>> {
+    query_rows_page(
+        conn,
+        include_str!("albums/list_albums.sql"),
+        Vec::new(),
+        options,
+        &[
+            Field::AlbumArtist,
+            Field::Album,
+            Field::Year,
+            Field::Added,
+            Field::Length,
+        ],
+        &[
+            OrderKey::ascending_text("album_artist_name"),
+            OrderKey::ascending("release_year"),
+            OrderKey::ascending_text("album_name"),
+        ],
+        album_select_result_to_album,
+    )
+}
+
+/// Retrieve the albums of an album artist (exact match, ignoring case and diacritics),
+/// ordered by year and name.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `album_artist_name` - The album artist, e.g. from [Artist](super::artists::Artist).
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::albums::get_albums_by_album_artist;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let albums = get_albums_by_album_artist(&mut conn, "Artist name");
+pub fn get_albums_by_album_artist(
+    conn: &mut Connection,
+    album_artist_name: &str,
+) -> Result<Vec<Album>, Box<dyn Error>> {
+    Ok(conn
+        .prepare(include_str!("albums/get_albums_by_album_artist.sql"))?
+        .query_map(
+            named_params! {":album_artist_name": album_artist_name},
+            album_select_result_to_album,
+        )?
+        .collect::<Result<Vec<Album>, rusqlite::Error>>()?)
+}
+
+/// Retrieve the tracks of an album, ordered by disc then track number.
+/// Audios stored at several paths are only returned once.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `album` - The album, as returned by [list_albums].
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::albums::{get_album_tracks, list_albums};
+/// use hathor_audios::database::listing::ListOptions;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let albums = list_albums(&mut conn, &ListOptions::default()).unwrap();
+/// let tracks = get_album_tracks(&mut conn, &albums.items[0]);
+pub fn get_album_tracks(
+    conn: &mut Connection,
+    album: &Album,
+) -> Result<Vec<AudioFile>, Box<dyn Error>> {
+    let disc_and_track = [Field::Disc, Field::Track].map(|field| {
+        OrderKey::from(SortTerm {
+            field,
+            descending: false,
+        })
+    });
+    let page = query_page(
+        conn,
+        include_str!("albums/get_album_tracks.sql"),
+        vec![
+            SqlValue::Text(album.album_artist_name.clone()),
+            SqlValue::Text(album.album_name.clone()),
+            SqlValue::Integer(album.release_year.into()),
+        ],
+        &ListOptions::default(),
+        &disc_and_track,
+    )?;
+    Ok(page.items)
+}
+
+fn album_select_result_to_album(row: &Row) -> Result<Album, rusqlite::Error> {
+    let img_folder_path = row.get::<&str, Option<Vec<u8>>>("img_folder_path")?;
+    Ok(Album {
+        album_artist_name: row.get("album_artist_name")?,
+        album_name: row.get("album_name")?,
+        release_year: row.get("release_year")?,
+        track_count: row.get("track_count")?,
+        disc_count: row.get("disc_count")?,
+        audio_length: Duration::seconds(row.get::<&str, i64>("audio_length_seconds")?),
+        img_path: row
+            .get::<&str, Option<Vec<u8>>>("img_path")?
+            .map(|img_path| stored_path_to_absolute(img_folder_path, img_path)),
+    })
+}
+
+#[cfg(test)]
+mod test_albums_operations {
+    use super::{get_album_tracks, get_albums_by_album_artist, list_albums, Album};
+    use crate::audio::AudioFile;
+    use crate::database::audio_files::insert_audios;
+    use crate::database::listing::{ListOptions, Page};
+    use crate::database::query::{Field, SortTerm};
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+    use std::path::PathBuf;
+    use time::Duration;
+
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        let tracks = [
+            (
+                "Jigsaw Falling into Place",
+                "Radiohead",
+                "",
+                "In Rainbows",
+                1,
+                9,
+                2007,
+            ),
+            ("15 Step", "Radiohead", "", "In Rainbows", 1, 1, 2007),
+            ("MK 1", "Radiohead", "", "in rainbows", 2, 1, 2007),
+            ("Airbag", "Radiohead", "", "OK Computer", 1, 1, 1997),
+            ("Airbag", "Radiohead", "", "OK Computer", 1, 1, 2017),
+            (
+                "Teardrop",
+                "Massive Attack",
+                "Various Artists",
+                "Mixtape",
+                1,
+                2,
+                1998,
+            ),
+            (
+                "Roygbiv",
+                "Boards of Canada",
+                "Various Artists",
+                "Mixtape",
+                1,
+                1,
+                1998,
+            ),
+        ];
+        for (n, (title, artist, album_artist, album, disc, track, year)) in
+            tracks.into_iter().enumerate()
+        {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    artist_name: String::from(artist),
+                    album_artist_name: String::from(album_artist),
+                    album_name: String::from(album),
+                    disc_num: disc,
+                    track_num: track,
+                    release_year: year,
+                    audio_length: Duration::seconds(100 + n as i64),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        temp_audios_context
+    }
+
+    #[rstest]
+    fn test_list_albums(mut library: TestInMemoryDBContext) {
+        let albums = list_albums(&mut library.connection, &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|a| {
+                (
+                    a.album_artist_name,
+                    a.album_name.to_lowercase(),
+                    a.release_year,
+                    a.track_count,
+                    a.disc_count,
+                    a.audio_length.whole_seconds(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("Radiohead", "ok computer", 1997, 1, 1, 103),
+            ("Radiohead", "in rainbows", 2007, 3, 2, 303),
+            ("Radiohead", "ok computer", 2017, 1, 1, 104),
+            ("Various Artists", "mixtape", 1998, 2, 1, 211),
+        ]
+        .map(|(artist, album, year, tracks, discs, seconds)| {
+            (
+                String::from(artist),
+                String::from(album),
+                year,
+                tracks,
+                discs,
+                seconds,
+            )
+        });
+        assert_eq!(albums, expected);
+    }
+
+    #[rstest]
+    fn test_list_albums_pages(mut library: TestInMemoryDBContext) {
+        let mut options = ListOptions {
+            sort: vec![SortTerm {
+                field: Field::Year,
+                descending: true,
+            }],
+            limit: Some(3),
+            after: None,
+        };
+        let years = |page: &Page<Album>| {
+            page.items
+                .iter()
+                .map(|a| a.release_year)
+                .collect::<Vec<u16>>()
+        };
+        let page = list_albums(&mut library.connection, &options).unwrap();
+        assert_eq!(years(&page), [2017, 2007, 1998]);
+        options.after = page.next;
+        let page = list_albums(&mut library.connection, &options).unwrap();
+        assert_eq!(years(&page), [1997]);
+        assert_eq!(page.next, None);
+    }
+
+    /// Albums have no track number to sort on.
+    #[rstest]
+    fn test_list_albums_by_track_fails(mut library: TestInMemoryDBContext) {
+        let options = ListOptions {
+            sort: vec![SortTerm {
+                field: Field::Track,
+                descending: false,
+            }],
+            ..ListOptions::default()
+        };
+        assert!(list_albums(&mut library.connection, &options).is_err());
+    }
+
+    #[rstest]
+    fn test_get_album_tracks_in_disc_and_track_order(mut library: TestInMemoryDBContext) {
+        let albums = get_albums_by_album_artist(&mut library.connection, "RADIOHEAD").unwrap();
+        assert_eq!(albums.len(), 3);
+        let titles = get_album_tracks(&mut library.connection, &albums[1])
+            .unwrap()
+            .into_iter()
+            .map(|a| a.audio_title)
+            .collect::<Vec<String>>();
+        assert_eq!(titles, vec!["15 Step", "Jigsaw Falling into Place", "MK 1"]);
+    }
+
+    /// The album_split fixture has the same album in two folders, it should list as one album
+    /// with its cover taken from a track that has one.
+    #[rstest]
+    fn test_split_album_is_grouped(mut temp_audios_context: TestInMemoryDBContext) {
+        let mut albums_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        albums_path.push("../../test_media_files/audio/albums");
+        let mut audios = ["album_split/a/test.mp3", "album_split/b/test2.mp3"]
+            .map(|path| AudioFile::from_file(&albums_path.join(path)).unwrap());
+        audios[1].img_path = Some(
+            albums_path
+                .join("album_with_cover_file/cover.png")
+                .canonicalize()
+                .unwrap(),
+        );
+        insert_audios(&mut temp_audios_context.connection, &audios).unwrap();
+
+        let albums = list_albums(&mut temp_audios_context.connection, &ListOptions::default())
+            .unwrap()
+            .items;
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].album_name, audios[0].album_name);
+        assert_eq!(albums[0].img_path, audios[1].img_path);
+        let tracks = get_album_tracks(&mut temp_audios_context.connection, &albums[0]).unwrap();
+        assert_eq!(tracks.len(), albums[0].track_count as usize);
+    }
+}
@@ -0,0 +1,14 @@
+SELECT audio_listings.*
+FROM audio_listings
+WHERE
+    FOLD_TEXT(
+        IFNULL(NULLIF(audio_listings.album_artist_name, ''), audio_listings.artist_name)
+    ) = FOLD_TEXT(?1)
+    AND FOLD_TEXT(audio_listings.album_name) = FOLD_TEXT(?2)
+    AND audio_listings.release_year = ?3
+    -- One row per audio, at the first path it is stored at.
+    AND audio_listings.audio_file_id = (
+        SELECT MIN(audio_files.rowid)
+        FROM audio_files
+        WHERE audio_files.file_hash = audio_listings.file_hash
+    );
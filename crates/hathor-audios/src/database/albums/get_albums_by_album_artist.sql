@@ -0,0 +1,6 @@
+SELECT album_listings.*
+FROM album_listings
+WHERE FOLD_TEXT(album_listings.album_artist_name) = FOLD_TEXT(:album_artist_name)
+ORDER BY
+    album_listings.release_year
+    , album_listings.album_name COLLATE NOCASE;
@@ -0,0 +1,75 @@
+DROP VIEW IF EXISTS album_listings;
+
+-- One row per album, keyed by album artist, album name and year.
+-- Tracks without an album artist fall back to their artist.
+-- Keys match ignoring case and diacritics (FOLD_TEXT, as search does),
+-- so albums split across folders or tag styles group together.
+CREATE VIEW album_listings AS
+WITH audio_locations AS (
+    SELECT
+        audio_listings.*
+        -- Audios stored at several paths are counted once, preferring a path with a cover.
+        , ROW_NUMBER() OVER (
+            PARTITION BY audio_listings.file_hash
+            ORDER BY audio_listings.img_path IS NULL, audio_listings.audio_file_id
+        ) AS location_rank
+    FROM audio_listings
+)
+
+, album_audios AS (
+    SELECT
+        IFNULL(
+            NULLIF(audio_locations.album_artist_name, ''), audio_locations.artist_name
+        ) AS album_artist_name
+        , audio_locations.album_name
+        , audio_locations.release_year
+        , audio_locations.disc_num
+        , audio_locations.track_num
+        , audio_locations.audio_length_seconds
+        , audio_locations.date_added
+        , audio_locations.img_path
+        , audio_locations.img_folder_path
+    FROM audio_locations
+    WHERE audio_locations.location_rank = 1
+)
+
+, ranked_album_audios AS (
+    SELECT
+        album_audios.*
+        -- The album's cover is taken from its first track with one.
+        , ROW_NUMBER() OVER (
+            PARTITION BY
+                FOLD_TEXT(album_audios.album_artist_name)
+                , FOLD_TEXT(album_audios.album_name)
+                , album_audios.release_year
+            ORDER BY
+                album_audios.img_path IS NULL
+                , album_audios.disc_num
+                , album_audios.track_num
+        ) AS cover_rank
+    FROM album_audios
+)
+
+SELECT
+    -- Spellings differ within an album, the first in binary order is shown.
+    MIN(ranked_album_audios.album_artist_name) AS album_artist_name
+    , MIN(ranked_album_audios.album_name) AS album_name
+    , ranked_album_audios.release_year
+    , COUNT(*) AS track_count
+    , COUNT(DISTINCT ranked_album_audios.disc_num) AS disc_count
+    , SUM(ranked_album_audios.audio_length_seconds) AS audio_length_seconds
+    , MIN(ranked_album_audios.date_added) AS date_added
+    , MAX(
+        CASE WHEN ranked_album_audios.cover_rank = 1 THEN ranked_album_audios.img_path END
+    ) AS img_path
+    , MAX(
+        CASE
+            WHEN ranked_album_audios.cover_rank = 1
+                THEN ranked_album_audios.img_folder_path
+        END
+    ) AS img_folder_path
+FROM ranked_album_audios
+GROUP BY
+    FOLD_TEXT(ranked_album_audios.album_artist_name)
+    , FOLD_TEXT(ranked_album_audios.album_name)
+    , ranked_album_audios.release_year;
@@ -0,0 +1,2 @@
+SELECT album_listings.*
+FROM album_listings;
@@ -0,0 +1,115 @@
+use crate::database::listing::{query_rows_page, ListOptions, OrderKey, Page};
+use crate::database::query::Field;
+use rusqlite::{Connection, Row};
+use std::error::Error;
+use time::Duration;
+
+/// An album artist and totals over their albums, see [Album](super::albums::Album).
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Artist {
+    pub artist_name: String,
+    pub album_count: u32,
+    pub track_count: u32,
+    pub audio_length: Duration,
+    pub first_release_year: u16,
+    pub last_release_year: u16,
+}
+
+/// Retrieve a page of the album artists, ordered by name unless `options` sorts them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
+///   Artists can be sorted by name and length.
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::artists::list_artists;
+/// use hathor_audios::database::listing::ListOptions;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let page = list_artists(&mut conn, &ListOptions::default());
+pub fn list_artists(
+    conn: &mut Connection,
+    options: &ListOptions,
+) -> Result<Page<Artist>, Box<dyn Error>> {
+    query_rows_page(
+        conn,
+        include_str!("artists/list_artists.sql"),
+        Vec::new(),
+        options,
+        &[Field::Artist, Field::Length],
+        &[OrderKey::ascending_text("artist_name")],
+        artist_select_result_to_artist,
+    )
+}
+
+fn artist_select_result_to_artist(row: &Row) -> Result<Artist, rusqlite::Error> {
+    Ok(Artist {
+        artist_name: row.get("artist_name")?,
+        album_count: row.get("album_count")?,
+        track_count: row.get("track_count")?,
+        audio_length: Duration::seconds(row.get::<&str, i64>("audio_length_seconds")?),
+        first_release_year: row.get("first_release_year")?,
+        last_release_year: row.get("last_release_year")?,
+    })
+}
+
+#[cfg(test)]
+mod test_artists_operations {
+    use super::{list_artists, Artist};
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::rstest;
+    use time::Duration;
+
+    #[rstest]
+    fn test_list_artists(mut temp_audios_context: TestInMemoryDBContext) {
+        let tracks = [
+            ("Björk", "", "Debut", 1993),
+            ("BJÖRK", "", "Homogénic", 1997),
+            ("Björk", "", "Homogenic", 1997),
+            ("Thom Yorke", "Björk", "Selmasongs", 2000),
+            ("Aphex Twin", "", "Drukqs", 2001),
+        ];
+        for (n, (artist, album_artist, album, year)) in tracks.into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    artist_name: String::from(artist),
+                    album_artist_name: String::from(album_artist),
+                    album_name: String::from(album),
+                    release_year: year,
+                    audio_length: Duration::seconds(60),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let artists = list_artists(&mut temp_audios_context.connection, &ListOptions::default())
+            .unwrap()
+            .items;
+        assert_eq!(
+            artists[0],
+            Artist {
+                artist_name: String::from("Aphex Twin"),
+                album_count: 1,
+                track_count: 1,
+                audio_length: Duration::seconds(60),
+                first_release_year: 2001,
+                last_release_year: 2001,
+            }
+        );
+        assert_eq!(artists.len(), 2);
+        assert_eq!(artists[1].artist_name.to_lowercase(), "björk");
+        assert_eq!((artists[1].album_count, artists[1].track_count), (3, 4));
+        assert_eq!(
+            (artists[1].first_release_year, artists[1].last_release_year),
+            (1993, 2000)
+        );
+    }
+}
@@ -0,0 +1,13 @@
+DROP VIEW IF EXISTS artist_listings;
+
+-- One row per album artist, see album_listings for how albums are keyed.
+CREATE VIEW artist_listings AS
+SELECT
+    MIN(album_listings.album_artist_name) AS artist_name
+    , COUNT(*) AS album_count
+    , SUM(album_listings.track_count) AS track_count
+    , SUM(album_listings.audio_length_seconds) AS audio_length_seconds
+    , MIN(album_listings.release_year) AS first_release_year
+    , MAX(album_listings.release_year) AS last_release_year
+FROM album_listings
+GROUP BY FOLD_TEXT(album_listings.album_artist_name);
@@ -0,0 +1,2 @@
+SELECT artist_listings.*
+FROM artist_listings;
@@ -1,9 +1,13 @@
 use crate::audio::{self, AudioFile};
-use crate::database::{query_map_to_audiofiles, INSERT_BATCH_SIZE};
+use crate::database::listing::{query_page, ListOptions, Page};
+use crate::database::os_paths::{path_to_bytes, path_to_display};
+use crate::database::user_media_folders::{query_user_media_folders, split_at_user_media_folder};
+use crate::database::{audio_select_result_to_audiofile, INSERT_BATCH_SIZE};
 use blake3::Hash;
-use rusqlite::{named_params, Connection, Row};
-use std::{error::Error, path::PathBuf, str::FromStr, usize};
-use time::Duration;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{named_params, Connection, OptionalExtension};
+use std::error::Error;
+use std::path::Path;
 
 /// Inserts a slice of [AudioFile](super::audio::AudioFile)s into the DB.
 ///
@@ -34,6 +38,7 @@ pub fn insert_audios(
         insert_next_batch_of_audios(&transaction, &mut audio_iter)?;
         transaction.commit()?;
     }
+    conn.execute_batch(include_str!("audio_files/analyze_audios.sql"))?;
     Ok(())
 }
 
@@ -62,27 +67,70 @@ pub fn get_audio_by_hash(conn: &mut Connection, hash: &Hash) -> AudioFile {
     .unwrap()
 }
 
+/// Retvieve the audio stored at the given path, None if no audio is stored there.
+/// The path is resolved first, so relative paths and links find the audio too.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `audio_path` - Where the audio file is on disk.
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::audio_files::get_audio_by_path;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let audio = get_audio_by_path(&mut conn, Path::new("/home/me/music/track.mp3"));
+pub fn get_audio_by_path(
+    conn: &mut Connection,
+    audio_path: &Path,
+) -> Result<Option<AudioFile>, Box<dyn Error>> {
+    let audio_path = audio_path.canonicalize()?;
+    let user_media_folders = query_user_media_folders(conn)?;
+    let (folder, relative_audio_path) =
+        split_at_user_media_folder(&user_media_folders, &audio_path);
+    Ok(conn
+        .query_row(
+            include_str!("audio_files/get_audio_by_path.sql"),
+            named_params! {
+                ":folder_id": folder.map(|f| f.folder_id),
+                ":audio_path": path_to_bytes(&relative_audio_path),
+            },
+            audio_select_result_to_audiofile,
+        )
+        .optional()?)
+}
+
 /// Retvieve audios with albums like the given string.
 ///
 /// # Arguments
 ///
 /// * `conn` - The open database connection to insert into.
 /// * `album_name` - The title to retrieve.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
 ///
 /// Examples
 /// ```no_run
 /// use rusqlite::Connection;
 /// use hathor_audios::database::audio_files::get_audios_by_album_name;
+/// use hathor_audios::database::listing::ListOptions;
 ///
 /// let mut conn = Connection::open_in_memory().unwrap();
-/// let audios = get_audios_by_album_name(&mut conn, "Ablum name");
-pub fn get_audios_by_album_name(conn: &mut Connection, album_name: &str) -> Vec<AudioFile> {
-    query_map_to_audiofiles(
+/// let page = get_audios_by_album_name(&mut conn, "Ablum name", &ListOptions::default());
+pub fn get_audios_by_album_name(
+    conn: &mut Connection,
+    album_name: &str,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    query_page(
         conn,
         include_str!("audio_files/get_audios_by_album_name.sql"),
-        named_params! {":album_name": album_name.to_string() },
+        vec![SqlValue::Text(album_name.to_string())],
+        options,
+        &[],
     )
-    .unwrap()
 }
 
 /// Retvieve audios with artist names like the given string.
@@ -91,21 +139,28 @@ pub fn get_audios_by_album_name(conn: &mut Connection, album_name: &str) -> Vec<
 ///
 /// * `conn` - The open database connection to insert into.
 /// * `artist_name` - The title to retrieve.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
 ///
 /// Examples
 /// ```no_run
 /// use rusqlite::Connection;
 /// use hathor_audios::database::audio_files::get_audios_by_artist_name;
+/// use hathor_audios::database::listing::ListOptions;
 ///
 /// let mut conn = Connection::open_in_memory().unwrap();
-/// let audios = get_audios_by_artist_name(&mut conn, "Artist name");
-pub fn get_audios_by_artist_name(conn: &mut Connection, audio_title: &str) -> Vec<AudioFile> {
-    query_map_to_audiofiles(
+/// let page = get_audios_by_artist_name(&mut conn, "Artist name", &ListOptions::default());
+pub fn get_audios_by_artist_name(
+    conn: &mut Connection,
+    audio_title: &str,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    query_page(
         conn,
         include_str!("audio_files/get_audios_by_artist_name.sql"),
-        named_params! {":artist_name": audio_title.to_string() },
+        vec![SqlValue::Text(audio_title.to_string())],
+        options,
+        &[],
     )
-    .unwrap()
 }
 
 /// Retvieve audios with titles like the given string.
@@ -114,48 +169,75 @@ pub fn get_audios_by_artist_name(conn: &mut Connection, audio_title: &str) -> Ve
 ///
 /// * `conn` - The open database connection to insert into.
 /// * `audio_title` - The title to retrieve.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
 ///
 /// Examples
 /// ```no_run
 /// use rusqlite::Connection;
 /// use hathor_audios::database::audio_files::get_audios_by_title;
+/// use hathor_audios::database::listing::ListOptions;
 ///
 /// let mut conn = Connection::open_in_memory().unwrap();
-/// let audios = get_audios_by_title(&mut conn, "Audio name");
-pub fn get_audios_by_title(conn: &mut Connection, audio_title: &str) -> Vec<AudioFile> {
-    query_map_to_audiofiles(
+/// let page = get_audios_by_title(&mut conn, "Audio name", &ListOptions::default());
+pub fn get_audios_by_title(
+    conn: &mut Connection,
+    audio_title: &str,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    query_page(
         conn,
         include_str!("audio_files/get_audios_by_title.sql"),
-        named_params! {":audio_title": audio_title.to_string() },
+        vec![SqlValue::Text(audio_title.to_string())],
+        options,
+        &[],
     )
-    .unwrap()
 }
 
-fn audio_select_result_to_audiofile(row: &Row) -> Result<AudioFile, rusqlite::Error> {
-    let mut img_path: Option<PathBuf> = None;
-    let img_path_str = &row.get::<usize, String>(8);
-    if let Ok(img_path_str) = img_path_str {
-        if let Ok(p) = PathBuf::from_str(img_path_str) {
-            img_path = Some(p);
-        }
-    }
-    let audio_path: PathBuf;
-    if let Ok(p) = PathBuf::from_str(&row.get::<usize, String>(7)?) {
-        audio_path = p;
-    } else {
-        return Err(rusqlite::Error::ExecuteReturnedResults);
+/// Moves the rows of every table keyed by an audio's hash, see [rehash_audio].
+const REHASH_AUDIO_STATEMENTS: [&str; 7] = [
+    include_str!("audio_files/rehash_audios.sql"),
+    include_str!("audio_files/rehash_audio_files.sql"),
+    include_str!("audio_files/rehash_playlist_items.sql"),
+    include_str!("audio_files/rehash_plays.sql"),
+    include_str!("audio_files/rehash_audio_ratings.sql"),
+    include_str!("audio_files/rehash_audio_favourites.sql"),
+    include_str!("audio_files/rehash_audio_tags.sql"),
+];
+
+/// How many paths the audio with the given hash is stored at.
+pub(crate) fn count_audio_locations(conn: &Connection, hash: &Hash) -> Result<u32, Box<dyn Error>> {
+    Ok(conn.query_row(
+        include_str!("audio_files/count_audio_locations.sql"),
+        named_params! {":file_hash": hash.to_string() },
+        |row| row.get("location_count"),
+    )?)
+}
+
+/// Moves an audio, its locations and everything the user set on it over to a new hash.
+/// Used after Hathor changes an audio's file itself, as the hash covers the whole file.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `old_hash` - Hash the audio is stored under.
+/// * `new_hash` - Hash of the file's new contents.
+pub(crate) fn rehash_audio(
+    conn: &mut Connection,
+    old_hash: &Hash,
+    new_hash: &Hash,
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    for statement in REHASH_AUDIO_STATEMENTS {
+        transaction.execute(
+            statement,
+            named_params! {
+                ":old_hash": old_hash.to_string(),
+                ":new_hash": new_hash.to_string(),
+            },
+        )?;
     }
-    Ok(AudioFile {
-        file_hash: Hash::from_str(&row.get::<usize, String>(0)?).unwrap(),
-        audio_title: row.get(1)?,
-        album_name: row.get(2)?,
-        artist_name: row.get(3)?,
-        track_num: row.get(4)?,
-        release_year: row.get(5)?,
-        audio_length: Duration::seconds(row.get::<usize, i64>(6)?),
-        audio_path,
-        img_path,
-    })
+    transaction.commit()?;
+    Ok(())
 }
 
 fn insert_next_batch_of_audios(
@@ -168,6 +250,7 @@ fn insert_next_batch_of_audios(
     let mut statement_audio_files = transaction
         .prepare_cached(include_str!(r"audio_files/insert_audio_file.sql"))
         .unwrap();
+    let user_media_folders = query_user_media_folders(transaction)?;
     for _ in 0..=INSERT_BATCH_SIZE {
         if let Some(audio) = audios_iter.next() {
             let params = named_params! {
@@ -175,19 +258,40 @@ fn insert_next_batch_of_audios(
                 ":audio_title": audio.audio_title,
                 ":album_name": audio.album_name,
                 ":artist_name": audio.artist_name,
+                ":album_artist_name": audio.album_artist_name,
+                ":genre": audio.genre,
+                ":composer": audio.composer,
+                ":lyrics": audio.lyrics,
                 ":track_num": audio.track_num,
+                ":disc_num": audio.disc_num,
                 ":release_year": audio.release_year,
-                ":audio_length_s": audio.audio_length.whole_nanoseconds() as i64,
+                ":audio_length_s": audio.audio_length.whole_seconds(),
+                ":start_offset_ns": audio.start_offset.whole_nanoseconds() as i64,
+                ":end_offset_ns": audio.end_offset.map(|end| end.whole_nanoseconds() as i64),
             };
             statement_audios.execute(params)?;
+            let audio_path = audio.audio_path.canonicalize()?;
+            let img_path = audio
+                .img_path
+                .as_ref()
+                .map(|p| p.canonicalize())
+                .transpose()?;
+            // Paths under a media root are stored relative to it, so the root can be relocated.
+            // A cover may be under another root than its audio.
+            let (folder, relative_audio_path) =
+                split_at_user_media_folder(&user_media_folders, &audio_path);
+            let (img_folder, relative_img_path) = img_path
+                .as_ref()
+                .map(|img_path| split_at_user_media_folder(&user_media_folders, img_path))
+                .unzip();
             let params = named_params! {
                 ":file_hash": audio.file_hash.to_string(),
-                ":audio_path": audio.audio_path.canonicalize()?.into_os_string().into_string().unwrap(),
-                ":img_path": audio.img_path.as_ref().unwrap()
-                    .canonicalize()?
-                        .into_os_string()
-                        .into_string().unwrap()
-                ,
+                ":folder_id": folder.map(|f| f.folder_id),
+                ":audio_path": path_to_bytes(&relative_audio_path),
+                ":audio_path_display": path_to_display(&audio_path),
+                ":img_folder_id": img_folder.flatten().map(|f| f.folder_id),
+                ":img_path": relative_img_path.as_deref().map(path_to_bytes),
+                ":img_path_display": img_path.as_deref().map(path_to_display),
             };
             statement_audio_files.execute(params)?;
         } else {
@@ -201,12 +305,18 @@ fn insert_next_batch_of_audios(
 mod test_audios_operations {
     use crate::audio::AudioFile;
     use crate::database::audio_files::{
-        get_audio_by_hash, get_audios_by_album_name, get_audios_by_artist_name,
+        get_audio_by_hash, get_audio_by_path, get_audios_by_album_name, get_audios_by_artist_name,
         get_audios_by_title, insert_audios,
     };
-    use crate::fixtures::{playlist_db_in_memory, TestInMemoryDBContext};
+    use crate::database::listing::ListOptions;
+    use crate::database::user_media_folders::add_user_media_folder;
+    use crate::fixtures::{
+        insert_temp_audio, playlist_db_in_memory, temp_audios_context, TestInMemoryDBContext,
+    };
+    use blake3::Hash;
     use rstest::rstest;
     use rusqlite::named_params;
+    use std::{fs, slice};
 
     /// Create a fake test database, insert a batch of audios, and check first inserted.
     #[rstest]
@@ -278,12 +388,36 @@ mod test_audios_operations {
         assert_eq!(audiofile_from_db, playlist_db_in_memory.audios[0]);
     }
 
+    /// Audios are found by path whether they are stored absolute or under a media root.
+    #[rstest]
+    fn test_get_audio_by_path(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let audio = playlist_db_in_memory.audios[2].clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        assert_eq!(
+            get_audio_by_path(conn, &audio.audio_path).unwrap(),
+            Some(audio.clone())
+        );
+        add_user_media_folder(conn, &playlist_db_in_memory.temp_audio_dir).unwrap();
+        assert_eq!(
+            get_audio_by_path(conn, &audio.audio_path).unwrap(),
+            Some(audio.clone())
+        );
+        let cover = audio.img_path.unwrap();
+        assert_eq!(get_audio_by_path(conn, &cover).unwrap(), None);
+        assert!(get_audio_by_path(conn, &cover.with_extension("missing")).is_err());
+    }
+
     /// Create a fake test database, insert a batch of audios,
     /// and check multiple can be retrieved by an album name match.
     #[rstest]
     fn test_get_audios_by_album_name_multiple(mut playlist_db_in_memory: TestInMemoryDBContext) {
-        let audiofiles_from_db =
-            get_audios_by_album_name(&mut playlist_db_in_memory.connection, "album");
+        let audiofiles_from_db = get_audios_by_album_name(
+            &mut playlist_db_in_memory.connection,
+            "album",
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(audiofiles_from_db, playlist_db_in_memory.audios);
     }
 
@@ -291,8 +425,13 @@ mod test_audios_operations {
     /// and check multiple can be retrieved by an artist name match.
     #[rstest]
     fn test_get_audios_by_artist_name_multiple(mut playlist_db_in_memory: TestInMemoryDBContext) {
-        let audiofiles_from_db =
-            get_audios_by_artist_name(&mut playlist_db_in_memory.connection, "artist");
+        let audiofiles_from_db = get_audios_by_artist_name(
+            &mut playlist_db_in_memory.connection,
+            "artist",
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(audiofiles_from_db, playlist_db_in_memory.audios);
     }
 
@@ -300,8 +439,102 @@ mod test_audios_operations {
     /// and check multiple can be retrieved by an title match.
     #[rstest]
     fn test_get_audios_by_title_multiple(mut playlist_db_in_memory: TestInMemoryDBContext) {
-        let audiofiles_from_db =
-            get_audios_by_title(&mut playlist_db_in_memory.connection, "title");
+        let audiofiles_from_db = get_audios_by_title(
+            &mut playlist_db_in_memory.connection,
+            "title",
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(audiofiles_from_db, playlist_db_in_memory.audios);
     }
+
+    /// Insert an audio without a cover and a path over 256 bytes long,
+    /// and check it round-trips unchanged.
+    #[rstest]
+    fn test_insert_audio_long_path_no_cover(mut temp_audios_context: TestInMemoryDBContext) {
+        let mut audio_path = temp_audios_context.temp_audio_dir.canonicalize().unwrap();
+        for n in 0..6 {
+            audio_path.push(format!("{}_{}", "long_directory_name".repeat(2), n));
+        }
+        fs::create_dir_all(&audio_path).unwrap();
+        audio_path.push("test.mp3");
+        fs::File::create(&audio_path).unwrap();
+        assert!(audio_path.as_os_str().len() > 256);
+
+        let audio = AudioFile {
+            file_hash: Hash::from_hex(format!("{:064}", 7)).unwrap(),
+            audio_path,
+            img_path: None,
+            ..AudioFile::default()
+        };
+        insert_audios(&mut temp_audios_context.connection, slice::from_ref(&audio)).unwrap();
+        let audiofile_from_db =
+            get_audio_by_hash(&mut temp_audios_context.connection, &audio.file_hash);
+        assert_eq!(audiofile_from_db, audio);
+    }
+
+    /// Insert an audio whose file name is not valid UTF-8,
+    /// and check the exact path bytes round-trip.
+    #[cfg(unix)]
+    #[rstest]
+    fn test_insert_audio_non_utf8_path(mut temp_audios_context: TestInMemoryDBContext) {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut audio_path = temp_audios_context.temp_audio_dir.canonicalize().unwrap();
+        audio_path.push(OsStr::from_bytes(b"caf\xe9_\xff.mp3"));
+        fs::File::create(&audio_path).unwrap();
+        let mut img_path = temp_audios_context.temp_audio_dir.canonicalize().unwrap();
+        img_path.push(OsStr::from_bytes(b"cov\xe9r.png"));
+        fs::File::create(&img_path).unwrap();
+
+        let audio = AudioFile {
+            file_hash: Hash::from_hex(format!("{:064}", 8)).unwrap(),
+            audio_path,
+            img_path: Some(img_path),
+            ..AudioFile::default()
+        };
+        insert_audios(&mut temp_audios_context.connection, slice::from_ref(&audio)).unwrap();
+        let audiofile_from_db =
+            get_audio_by_hash(&mut temp_audios_context.connection, &audio.file_hash);
+        assert_eq!(audiofile_from_db, audio);
+    }
+
+    /// Create a fake test database, insert an audio with non-ASCII names,
+    /// and check the title, album and artist lookups ignore case and diacritics.
+    #[rstest]
+    fn test_get_audios_by_name_ignores_case_and_diacritics(
+        mut temp_audios_context: TestInMemoryDBContext,
+    ) {
+        let audio = insert_temp_audio(
+            &mut temp_audios_context,
+            AudioFile {
+                audio_title: String::from("Straße"),
+                album_name: String::from("Ágætis byrjun"),
+                artist_name: String::from("Björk"),
+                ..AudioFile::default()
+            },
+        );
+        let conn = &mut temp_audios_context.connection;
+        let titles_like = |conn: &mut _, title| {
+            get_audios_by_title(conn, title, &ListOptions::default())
+                .unwrap()
+                .items
+        };
+        let albums_like = |conn: &mut _, album| {
+            get_audios_by_album_name(conn, album, &ListOptions::default())
+                .unwrap()
+                .items
+        };
+        let artists_like = |conn: &mut _, artist| {
+            get_audios_by_artist_name(conn, artist, &ListOptions::default())
+                .unwrap()
+                .items
+        };
+        assert_eq!(titles_like(conn, "STRASSE"), vec![audio.clone()]);
+        assert_eq!(albums_like(conn, "agætis"), vec![audio.clone()]);
+        assert_eq!(artists_like(conn, "BJORK"), vec![audio]);
+        assert!(artists_like(conn, "bjorn").is_empty());
+    }
 }
@@ -0,0 +1,5 @@
+-- Without statistics on the audios indexes the query planner would rather sort a whole
+-- listing than walk an index to its first page, see listing::query_page.
+-- A sample is enough to choose an index, and keeps this quick on large libraries.
+PRAGMA analysis_limit = 1000;
+ANALYZE audios;
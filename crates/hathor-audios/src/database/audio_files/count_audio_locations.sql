@@ -0,0 +1,3 @@
+SELECT COUNT(*) AS location_count
+FROM audio_files
+WHERE audio_files.file_hash = :file_hash;
@@ -0,0 +1,8 @@
+SELECT audio_listings.*
+FROM audio_listings
+    INNER JOIN audio_files
+        ON audio_listings.audio_file_id = audio_files.rowid
+WHERE
+    IFNULL(audio_files.folder_id, 0) = IFNULL(:folder_id, 0)
+    AND audio_files.audio_path = :audio_path
+LIMIT 1;
@@ -1,6 +1,14 @@
 CREATE TABLE IF NOT EXISTS audio_files (
-    file_hash CHAR(64)
-    , audio_path VARCHAR(256) -- Windows path limit.
-    , img_path VARCHAR(256) -- Windows path limit.
-    , PRIMARY KEY (file_hash, audio_path)
-) WITHOUT ROWID;
+    file_hash CHAR(64) NOT NULL
+    -- Media root audio_path is relative to, NULL when it is absolute.
+    , folder_id INTEGER REFERENCES user_media_folders (folder_id)
+    , audio_path BLOB NOT NULL -- Raw OS path bytes, no length limit.
+    , audio_path_display TEXT -- Lossy UTF-8 form of audio_path, display only.
+    -- Media root img_path is relative to, which may differ from the audio's.
+    , img_folder_id INTEGER REFERENCES user_media_folders (folder_id)
+    , img_path BLOB -- Raw OS path bytes, no length limit.
+    , img_path_display TEXT -- Lossy UTF-8 form of img_path, display only.
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS audio_files_location
+ON audio_files (file_hash, IFNULL(folder_id, 0), audio_path);
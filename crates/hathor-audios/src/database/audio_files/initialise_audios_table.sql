@@ -3,7 +3,77 @@ CREATE TABLE IF NOT EXISTS audios (
     , audio_title VARCHAR(256)
     , album_name VARCHAR(256)
     , artist_name VARCHAR(256)
+    , album_artist_name VARCHAR(256)
+    , genre VARCHAR(256)
+    , composer VARCHAR(256)
+    , lyrics TEXT
     , track_num INT(8)
     , release_year INT(16)
     , audio_length_seconds INT(64)
+    , disc_num INT(8)
+    , date_added INT(64) -- Unix seconds, when the audio was first inserted.
+    -- Nanoseconds into the file, CUE sheet tracks share one file.
+    , start_offset_ns INT(64) NOT NULL DEFAULT 0
+    , end_offset_ns INT(64) -- NULL to play to the end of the file.
 ) WITHOUT ROWID;
+
+-- Listings page by walking these from a cursor, see listing::query_page.
+-- Each ends in the artist, album, disc and track that break ties in listings,
+-- so pages are read in order from the index alone.
+-- Text columns sort ignoring case, so their indexes must too.
+CREATE INDEX IF NOT EXISTS audios_by_artist
+ON audios (artist_name COLLATE NOCASE, album_name COLLATE NOCASE, disc_num, track_num);
+
+CREATE INDEX IF NOT EXISTS audios_by_title
+ON audios (
+    audio_title COLLATE NOCASE
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
+
+CREATE INDEX IF NOT EXISTS audios_by_album
+ON audios (
+    album_name COLLATE NOCASE
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
+
+CREATE INDEX IF NOT EXISTS audios_by_album_artist
+ON audios (
+    album_artist_name COLLATE NOCASE
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
+
+CREATE INDEX IF NOT EXISTS audios_by_year
+ON audios (
+    release_year
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
+
+CREATE INDEX IF NOT EXISTS audios_by_date_added
+ON audios (
+    date_added
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
+
+CREATE INDEX IF NOT EXISTS audios_by_length
+ON audios (
+    audio_length_seconds
+    , artist_name COLLATE NOCASE
+    , album_name COLLATE NOCASE
+    , disc_num
+    , track_num
+);
@@ -1,9 +1,34 @@
-INSERT OR IGNORE INTO audios VALUES (
+-- Columns are named as tables migrated from older versions have them in another order.
+INSERT OR IGNORE INTO audios (
+    file_hash
+    , audio_title
+    , album_name
+    , artist_name
+    , album_artist_name
+    , genre
+    , composer
+    , lyrics
+    , track_num
+    , release_year
+    , audio_length_seconds
+    , disc_num
+    , date_added
+    , start_offset_ns
+    , end_offset_ns
+) VALUES (
     :file_hash
     , :audio_title
     , :album_name
     , :artist_name
+    , :album_artist_name
+    , :genre
+    , :composer
+    , :lyrics
     , :track_num
     , :release_year
     , :audio_length_s
+    , :disc_num
+    , UNIXEPOCH()
+    , :start_offset_ns
+    , :end_offset_ns
 );
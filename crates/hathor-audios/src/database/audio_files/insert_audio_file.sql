@@ -1,5 +1,17 @@
-INSERT OR IGNORE INTO audio_files VALUES (
+INSERT OR IGNORE INTO audio_files (
+    file_hash
+    , folder_id
+    , audio_path
+    , audio_path_display
+    , img_folder_id
+    , img_path
+    , img_path_display
+) VALUES (
     :file_hash
+    , :folder_id
     , :audio_path
+    , :audio_path_display
+    , :img_folder_id
     , :img_path
+    , :img_path_display
 );
@@ -0,0 +1,2 @@
+UPDATE audio_favourites SET file_hash = :new_hash
+WHERE file_hash = :old_hash;
@@ -0,0 +1,2 @@
+UPDATE playlist_items SET file_hash = :new_hash
+WHERE file_hash = :old_hash;
@@ -0,0 +1,2 @@
+UPDATE plays SET file_hash = :new_hash
+WHERE file_hash = :old_hash;
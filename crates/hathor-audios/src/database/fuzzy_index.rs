@@ -0,0 +1,361 @@
+use crate::audio::AudioFile;
+use crate::database::listing::ListOptions;
+use crate::database::query::{get_audios_by_query, Query};
+use crate::database::unicode_folding::fold_text;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// Title, artist and album weights, in that order.
+const FIELD_WEIGHTS: [f32; 3] = [1.0, 0.9, 0.8];
+/// Word similarity below this counts as no match for that word.
+const MIN_WORD_SIMILARITY: f32 = 0.7;
+/// Added when a whole field equals the query.
+const EXACT_FIELD_BOOST: f32 = 1.0;
+/// Added when a whole field starts with the query.
+const PREFIX_FIELD_BOOST: f32 = 0.5;
+
+type Trigram = [char; 3];
+
+/// A typo tolerant, in-memory search index over title, artist and album.
+/// Build it once, search it as the user types, and [refresh](FuzzyIndex::refresh)
+/// it after the library changes.
+#[derive(Default)]
+pub struct FuzzyIndex {
+    entries: Vec<IndexedAudio>,
+    trigrams: HashMap<Trigram, Vec<usize>>,
+}
+
+/// An audio found by a [FuzzyIndex] search. Higher scores are closer matches.
+#[derive(PartialEq, Debug, Clone)]
+pub struct FuzzyMatch {
+    pub audio: AudioFile,
+    pub score: f32,
+}
+
+struct IndexedAudio {
+    audio: AudioFile,
+    /// Folded title, artist and album.
+    fields: [String; 3],
+}
+
+impl FuzzyIndex {
+    /// Builds an index over the given audios. Audios with the same hash are indexed once.
+    pub fn new(audios: impl IntoIterator<Item = AudioFile>) -> Self {
+        let mut index = FuzzyIndex::default();
+        let mut seen = HashSet::new();
+        for audio in audios {
+            if seen.insert(audio.file_hash) {
+                index.add(audio);
+            }
+        }
+        index
+    }
+
+    /// Builds an index over every audio in the DB.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rusqlite::Connection;
+    /// use hathor_audios::database::fuzzy_index::FuzzyIndex;
+    ///
+    /// let mut conn = Connection::open_in_memory().unwrap();
+    /// let index = FuzzyIndex::from_db(&mut conn).unwrap();
+    /// let matches = index.search("radiohed", 20);
+    pub fn from_db(conn: &mut Connection) -> Result<Self, Box<dyn Error>> {
+        let page = get_audios_by_query(conn, &Query::default(), &ListOptions::default())?;
+        Ok(FuzzyIndex::new(page.items))
+    }
+
+    /// Rebuilds the index from the current contents of the DB.
+    pub fn refresh(&mut self, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+        *self = FuzzyIndex::from_db(conn)?;
+        Ok(())
+    }
+
+    /// Number of audios in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the audios closest to the query, best first.
+    ///
+    /// Every query word is matched against the words of the title, artist and album,
+    /// allowing typos and prefixes. Whole fields equal to, or starting with, the query
+    /// are boosted above partial matches.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<FuzzyMatch> {
+        let query = fold_text(query);
+        let query_words = words(&query);
+        if query_words.is_empty() {
+            return Vec::new();
+        }
+        let query_phrase = query_words.join(" ");
+
+        let mut matches = self
+            .candidates(&query_words)
+            .into_iter()
+            .filter_map(|i| {
+                let entry = &self.entries[i];
+                entry
+                    .score(&query_words, &query_phrase)
+                    .map(|score| (entry, score))
+            })
+            .collect::<Vec<(&IndexedAudio, f32)>>();
+        matches.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .total_cmp(a_score)
+                .then_with(|| a.fields.cmp(&b.fields))
+        });
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(entry, score)| FuzzyMatch {
+                audio: entry.audio.clone(),
+                score,
+            })
+            .collect()
+    }
+
+    fn add(&mut self, audio: AudioFile) {
+        let fields = [
+            words(&fold_text(&audio.audio_title)).join(" "),
+            words(&fold_text(&audio.artist_name)).join(" "),
+            words(&fold_text(&audio.album_name)).join(" "),
+        ];
+        let i = self.entries.len();
+        let entry_trigrams = fields
+            .iter()
+            .flat_map(|field| field.split(' ').flat_map(word_trigrams))
+            .collect::<HashSet<Trigram>>();
+        for trigram in entry_trigrams {
+            self.trigrams.entry(trigram).or_default().push(i);
+        }
+        self.entries.push(IndexedAudio { audio, fields });
+    }
+
+    /// Entries sharing enough trigrams with the query to be worth scoring.
+    fn candidates(&self, query_words: &[&str]) -> Vec<usize> {
+        let query_trigrams = query_words
+            .iter()
+            .flat_map(|word| word_trigrams(word))
+            .collect::<HashSet<Trigram>>();
+        let mut shared_counts: HashMap<usize, usize> = HashMap::new();
+        for trigram in &query_trigrams {
+            for i in self.trigrams.get(trigram).into_iter().flatten() {
+                *shared_counts.entry(*i).or_default() += 1;
+            }
+        }
+        let required = query_trigrams.len().div_ceil(4);
+        shared_counts
+            .into_iter()
+            .filter(|(_, count)| *count >= required)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl IndexedAudio {
+    /// Scores the entry against the query, None if it doesn't match.
+    fn score(&self, query_words: &[&str], query_phrase: &str) -> Option<f32> {
+        let field_words = self
+            .fields
+            .iter()
+            .map(|field| field.split(' ').collect())
+            .collect::<Vec<Vec<&str>>>();
+        let mut total = 0.0;
+        for query_word in query_words {
+            let best = field_words
+                .iter()
+                .zip(FIELD_WEIGHTS)
+                .flat_map(|(words, weight)| {
+                    words
+                        .iter()
+                        .map(move |word| word_similarity(query_word, word) * weight)
+                })
+                .fold(0.0, f32::max);
+            if best < MIN_WORD_SIMILARITY {
+                return None;
+            }
+            total += best;
+        }
+        let mut score = total / query_words.len() as f32;
+        let boost = self
+            .fields
+            .iter()
+            .map(|field| {
+                if field == query_phrase {
+                    EXACT_FIELD_BOOST
+                } else if field.starts_with(query_phrase) {
+                    PREFIX_FIELD_BOOST
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f32::max);
+        score += boost;
+        Some(score)
+    }
+}
+
+/// Splits folded text into words of letters and digits.
+fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Trigrams of a word padded with spaces, so short words and word starts still produce some.
+fn word_trigrams(word: &str) -> Vec<Trigram> {
+    let padded = format!("  {} ", word).chars().collect::<Vec<char>>();
+    padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// 1.0 for equal words, 0.9 when the query word starts the word, otherwise one minus the
+/// edit distance relative to the longer word. Typos in the typed part of a word are allowed
+/// by also comparing against the word cut to the query's length.
+fn word_similarity(query_word: &str, word: &str) -> f32 {
+    if query_word == word {
+        return 1.0;
+    }
+    if word.starts_with(query_word) {
+        return 0.9;
+    }
+    let query_chars = query_word.chars().collect::<Vec<char>>();
+    let word_chars = word.chars().collect::<Vec<char>>();
+    let similarity = |a: &[char], b: &[char]| {
+        1.0 - levenshtein(a, b) as f32 / a.len().max(b.len()).max(1) as f32
+    };
+    let whole = similarity(&query_chars, &word_chars);
+    let prefix = if query_chars.len() >= 4 && word_chars.len() > query_chars.len() {
+        // Slightly below a whole word match, as with exact prefixes.
+        similarity(&query_chars, &word_chars[..query_chars.len()]) * 0.9
+    } else {
+        0.0
+    };
+    whole.max(prefix)
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod fuzzy_index_tests {
+    use super::{levenshtein, word_similarity, FuzzyIndex};
+    use crate::audio::AudioFile;
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        let tracks = [
+            ("Airbag", "Radiohead", "OK Computer"),
+            ("Paranoid Android", "Radiohead", "OK Computer"),
+            ("Time", "Pink Floyd", "The Dark Side of the Moon"),
+            ("Money", "Pink Floyd", "The Dark Side of the Moon"),
+            ("Radio", "Darkside", "Psychic"),
+            ("Moon", "Björk", "Biophilia"),
+        ];
+        for (n, (title, artist, album)) in tracks.into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    artist_name: String::from(artist),
+                    album_name: String::from(album),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        temp_audios_context
+    }
+
+    fn search_titles(index: &FuzzyIndex, query: &str) -> Vec<String> {
+        index
+            .search(query, 10)
+            .into_iter()
+            .map(|m| m.audio.audio_title)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("radiohed", vec!["Airbag", "Paranoid Android"])]
+    #[case("dark side moon", vec!["Money", "Time"])]
+    #[case("paranoid andriod", vec!["Paranoid Android"])]
+    #[case("pink floid mony", vec!["Money"])]
+    #[case("bjork", vec!["Moon"])]
+    #[case("zzzz", vec![])]
+    #[case("", vec![])]
+    fn test_fuzzy_search(
+        mut library: TestInMemoryDBContext,
+        #[case] query: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        let index = FuzzyIndex::from_db(&mut library.connection).unwrap();
+        assert_eq!(search_titles(&index, query), expected);
+    }
+
+    /// An exact title outranks typo and prefix matches of other fields.
+    #[rstest]
+    fn test_fuzzy_search_boosts_exact_matches(mut library: TestInMemoryDBContext) {
+        let index = FuzzyIndex::from_db(&mut library.connection).unwrap();
+        let matches = index.search("radio", 10);
+        assert_eq!(matches[0].audio.audio_title, "Radio");
+        assert!(matches.windows(2).all(|m| m[0].score >= m[1].score));
+        assert!(matches[0].score > matches[1].score);
+    }
+
+    #[rstest]
+    fn test_fuzzy_index_refresh(mut library: TestInMemoryDBContext) {
+        let mut index = FuzzyIndex::from_db(&mut library.connection).unwrap();
+        assert_eq!(index.len(), 6);
+        insert_temp_audio(
+            &mut library,
+            AudioFile {
+                file_hash: Hash::from_hex(format!("{:064}", 6)).unwrap(),
+                audio_title: String::from("Roygbiv"),
+                artist_name: String::from("Boards of Canada"),
+                ..AudioFile::default()
+            },
+        );
+        assert!(index.search("roygbiv", 10).is_empty());
+        index.refresh(&mut library.connection).unwrap();
+        assert_eq!(index.len(), 7);
+        assert_eq!(search_titles(&index, "roygbif"), vec!["Roygbiv"]);
+    }
+
+    #[rstest]
+    #[case("kitten", "sitting", 3)]
+    #[case("", "abc", 3)]
+    #[case("radiohed", "radiohead", 1)]
+    fn test_levenshtein(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        let a = a.chars().collect::<Vec<char>>();
+        let b = b.chars().collect::<Vec<char>>();
+        assert_eq!(levenshtein(&a, &b), expected);
+    }
+
+    #[rstest]
+    #[case("moon", "moon", 1.0)]
+    #[case("moo", "moon", 0.9)]
+    #[case("radiohed", "radiohead", 1.0 - 1.0 / 9.0)]
+    fn test_word_similarity(#[case] query_word: &str, #[case] word: &str, #[case] expected: f32) {
+        assert!((word_similarity(query_word, word) - expected).abs() < 1e-6);
+    }
+}
@@ -1,16 +1,31 @@
+use crate::database::migrations::migrate;
+use crate::database::unicode_folding::register_fold_text_function;
 use rusqlite::Connection;
 
-pub(crate) fn init_db(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) fn init_db(conn: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    // Needed by the search triggers, so register it before anything touches the tables.
+    register_fold_text_function(conn)?;
+    migrate(conn)?;
     conn.execute(include_str!("playlists/initialise_playlists_table.sql"), ())?;
-    conn.execute(include_str!("audio_files/initialise_audios_table.sql"), ())?;
-    conn.execute(
-        include_str!("audio_files/initialise_audio_files_table.sql"),
-        (),
-    )?;
+    conn.execute_batch(include_str!(
+        "playlists/initialise_playlist_items_table.sql"
+    ))?;
+    conn.execute_batch(include_str!(
+        "smart_playlists/initialise_smart_playlists_tables.sql"
+    ))?;
+    conn.execute_batch(include_str!("audio_files/initialise_audios_table.sql"))?;
+    conn.execute_batch(include_str!("audio_files/initialise_audio_files_table.sql"))?;
     conn.execute(
         include_str!("user_media_folders/initialise_user_media_folders_table.sql"),
         (),
     )?;
+    conn.execute_batch(include_str!("search/initialise_audios_search_table.sql"))?;
+    conn.execute_batch(include_str!("plays/initialise_plays_table.sql"))?;
+    conn.execute_batch(include_str!("plays/initialise_play_stats_view.sql"))?;
+    conn.execute_batch(include_str!("user_data/initialise_user_data_tables.sql"))?;
+    conn.execute_batch(include_str!("listing/initialise_audio_listings_view.sql"))?;
+    conn.execute_batch(include_str!("albums/initialise_album_listings_view.sql"))?;
+    conn.execute_batch(include_str!("artists/initialise_artist_listings_view.sql"))?;
     Ok(())
 }
 
@@ -21,7 +36,7 @@ mod database_connect {
 
     #[test]
     fn test_table_creation_doesnt_fail_on_connect() {
-        let conn = Connection::open_in_memory().unwrap();
-        init_db(&conn).expect("Database setup error");
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_db(&mut conn).expect("Database setup error");
     }
 }
@@ -0,0 +1,532 @@
+use crate::audio::AudioFile;
+use crate::database::audio_select_result_to_audiofile;
+use crate::database::query::{Field, FieldKind, SortTerm};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, Row};
+use std::error::Error;
+
+/// Columns ordering every listing after its sort keys, so pages never skip or repeat audios.
+/// `audio_file_id` is unique per row, the rest keep ties in album order.
+const TIEBREAK_COLUMNS: &[(&str, bool)] = &[
+    ("artist_name", true),
+    ("album_name", true),
+    ("disc_num", false),
+    ("track_num", false),
+    ("file_hash", false),
+    ("audio_file_id", false),
+];
+
+/// How to order and page a listing, of audios or of albums, artists, playlists or their items.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::query::{Field, SortTerm};
+///
+/// let options = ListOptions {
+///     sort: vec![SortTerm { field: Field::Year, descending: true }],
+///     limit: Some(100),
+///     ..ListOptions::default()
+/// };
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Sort keys, most significant first. Ties are left in the listing's own order,
+    /// for audios by artist, album, disc and track.
+    pub sort: Vec<SortTerm>,
+    /// Most items to return, None for all of them.
+    pub limit: Option<u32>,
+    /// Continue from the end of a previous page, see [Page::next].
+    pub after: Option<Cursor>,
+}
+
+/// One page of a listing, of audios unless the listing says otherwise.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Page<T = AudioFile> {
+    pub items: Vec<T>,
+    /// Set as [ListOptions::after] to get the following page, None on the last page.
+    pub next: Option<Cursor>,
+}
+
+/// A position in a listing, just after the last item of a page.
+/// It holds the sort values of that item rather than an offset,
+/// so pages stay consistent while items are added or removed.
+/// Only valid for listings with the same sort keys.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cursor {
+    keys: Vec<OrderKey>,
+    values: Vec<SqlValue>,
+}
+
+/// A column of a listing's select in the listing's order.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct OrderKey {
+    column: &'static str,
+    text: bool,
+    descending: bool,
+}
+
+impl From<SortTerm> for OrderKey {
+    fn from(term: SortTerm) -> Self {
+        OrderKey {
+            column: term.field.sort_column(),
+            text: term.field.kind() == FieldKind::Text,
+            descending: term.descending,
+        }
+    }
+}
+
+impl OrderKey {
+    /// Orders by a column that isn't a [Field], e.g. a playlist position.
+    pub(crate) const fn ascending(column: &'static str) -> Self {
+        OrderKey {
+            column,
+            text: false,
+            descending: false,
+        }
+    }
+
+    /// Orders by a text column that isn't a [Field], ignoring case, e.g. a playlist name.
+    pub(crate) const fn ascending_text(column: &'static str) -> Self {
+        OrderKey {
+            column,
+            text: true,
+            descending: false,
+        }
+    }
+
+    fn expression(&self) -> String {
+        if self.text {
+            format!("{} COLLATE NOCASE", self.column)
+        } else {
+            self.column.to_string()
+        }
+    }
+}
+
+/// Runs a select over `audio_listings` and returns one page of it, ordered by the sort keys
+/// in `options`, then the listing's own `default_order`, then [TIEBREAK_COLUMNS].
+///
+/// Only the sort keys are read while paging, so SQLite can walk an index of the `audios`
+/// table from the cursor and stop at the page's end. The plays and tags of each audio are
+/// only looked up for the rows of the page, unless the listing sorts or filters on them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `sql` - Select returning `audio_listings` rows, without ORDER BY or LIMIT.
+/// * `params` - Positional parameters of `sql`, its placeholders must be `?1` to `?N`.
+/// * `options` - Sort, limit and cursor requested by the caller.
+/// * `default_order` - Order of the listing itself, e.g. playlist positions.
+pub(crate) fn query_page(
+    conn: &Connection,
+    sql: &str,
+    params: Vec<SqlValue>,
+    options: &ListOptions,
+    default_order: &[OrderKey],
+) -> Result<Page, Box<dyn Error>> {
+    query_audio_page(
+        conn,
+        sql,
+        params,
+        options,
+        default_order,
+        &[],
+        |audio, _| audio.ok_or(rusqlite::Error::QueryReturnedNoRows),
+    )
+}
+
+/// [query_page] for listings whose items hold more than the audio, e.g. playlist items.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `sql` - Select returning `audio_listings` rows, without ORDER BY or LIMIT.
+///   Rows whose `audio_file_id` is NULL, e.g. from a LEFT JOIN, have no audio.
+/// * `params` - Positional parameters of `sql`, its placeholders must be `?1` to `?N`.
+/// * `options` - Sort, limit and cursor requested by the caller.
+/// * `default_order` - Order of the listing itself, e.g. playlist positions.
+/// * `columns` - Other columns of `sql` that `read_item` reads.
+/// * `read_item` - Builds an item from its audio and its row of `sql`.
+pub(crate) fn query_audio_page<T>(
+    conn: &Connection,
+    sql: &str,
+    params: Vec<SqlValue>,
+    options: &ListOptions,
+    default_order: &[OrderKey],
+    columns: &[&str],
+    read_item: impl Fn(Option<AudioFile>, &Row) -> Result<T, rusqlite::Error>,
+) -> Result<Page<T>, Box<dyn Error>> {
+    let keys = order_keys(&options.sort, default_order);
+    let columns = keys
+        .iter()
+        .map(|key| key.column)
+        .chain(columns.iter().copied())
+        .chain(["audio_file_id"])
+        .collect::<Vec<&str>>();
+    let mut get_audio_listing =
+        conn.prepare_cached(include_str!("listing/get_audio_listing.sql"))?;
+    query_keyset_page(
+        conn,
+        sql,
+        params,
+        options,
+        keys,
+        &columns.join(", "),
+        |row| {
+            let audio = match row.get::<&str, Option<i64>>("audio_file_id")? {
+                Some(audio_file_id) => Some(
+                    get_audio_listing
+                        .query_row([audio_file_id], audio_select_result_to_audiofile)?,
+                ),
+                None => None,
+            };
+            read_item(audio, row)
+        },
+    )
+}
+
+/// Runs a select and returns one page of it, for listings of things other than audios.
+/// Only the fields in `sortable` may be sorted on, as the listing has columns for them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `sql` - Select without ORDER BY or LIMIT.
+/// * `params` - Positional parameters of `sql`, its placeholders must be `?1` to `?N`.
+/// * `options` - Sort, limit and cursor requested by the caller.
+/// * `sortable` - Fields whose [sort column](Field::sort_column) `sql` returns.
+/// * `default_order` - Order of the listing, it must end in a unique column.
+/// * `read_item` - Builds an item from a row of `sql`.
+pub(crate) fn query_rows_page<T>(
+    conn: &Connection,
+    sql: &str,
+    params: Vec<SqlValue>,
+    options: &ListOptions,
+    sortable: &[Field],
+    default_order: &[OrderKey],
+    read_item: fn(&Row) -> Result<T, rusqlite::Error>,
+) -> Result<Page<T>, Box<dyn Error>> {
+    if let Some(term) = options
+        .sort
+        .iter()
+        .find(|term| !sortable.contains(&term.field))
+    {
+        return Err(format!("this listing can't be sorted by {:?}", term.field).into());
+    }
+    let keys = options
+        .sort
+        .iter()
+        .map(|term| OrderKey::from(*term))
+        .chain(default_order.iter().cloned())
+        .collect();
+    query_keyset_page(conn, sql, params, options, keys, "*", read_item)
+}
+
+/// Pages through `sql` in the order of `keys`, reading each row with `read_item`.
+/// The columns selected must include every key.
+fn query_keyset_page<T>(
+    conn: &Connection,
+    sql: &str,
+    mut params: Vec<SqlValue>,
+    options: &ListOptions,
+    keys: Vec<OrderKey>,
+    columns: &str,
+    mut read_item: impl FnMut(&Row) -> Result<T, rusqlite::Error>,
+) -> Result<Page<T>, Box<dyn Error>> {
+    let mut sql = format!(
+        "SELECT {} FROM ({}) AS listing",
+        columns,
+        sql.trim_end().trim_end_matches(';')
+    );
+    if let Some(cursor) = &options.after {
+        if cursor.keys != keys {
+            return Err("cursor is from a listing with a different sort order".into());
+        }
+        sql += " WHERE ";
+        sql += &after_cursor_sql(&keys, &cursor.values, params.len() + 1);
+        params.extend(cursor.values.iter().cloned());
+    }
+    sql += " ORDER BY ";
+    sql += &order_by_sql(&keys);
+    if let Some(limit) = options.limit {
+        // One extra row tells whether there is another page.
+        params.push(SqlValue::Integer(i64::from(limit) + 1));
+        sql += &format!(" LIMIT ?{}", params.len());
+    }
+
+    let mut statement = conn.prepare(&sql)?;
+    let mut rows = statement.query(params_from_iter(params))?;
+    let limit = options.limit.map(|limit| limit as usize);
+    let mut items = Vec::new();
+    let mut last_values = None;
+    while let Some(row) = rows.next()? {
+        if Some(items.len()) == limit {
+            return Ok(Page {
+                items,
+                next: last_values.map(|values| Cursor { keys, values }),
+            });
+        }
+        items.push(read_item(row)?);
+        if Some(items.len()) == limit {
+            last_values = Some(
+                keys.iter()
+                    .map(|key| row.get::<&str, SqlValue>(key.column))
+                    .collect::<Result<Vec<SqlValue>, rusqlite::Error>>()?,
+            );
+        }
+    }
+    Ok(Page { items, next: None })
+}
+
+/// The full order of a listing: `sort`, then `default_order`, then [TIEBREAK_COLUMNS].
+pub(crate) fn order_keys(sort: &[SortTerm], default_order: &[OrderKey]) -> Vec<OrderKey> {
+    sort.iter()
+        .map(|term| OrderKey::from(*term))
+        .chain(default_order.iter().cloned())
+        .chain(TIEBREAK_COLUMNS.iter().map(|(column, text)| OrderKey {
+            column,
+            text: *text,
+            descending: false,
+        }))
+        .collect()
+}
+
+/// The terms of an ORDER BY clause, without the keywords.
+/// NULLs sort below every value, which [after_cursor_sql] relies on.
+pub(crate) fn order_by_sql(keys: &[OrderKey]) -> String {
+    keys.iter()
+        .map(|key| {
+            let direction = if key.descending {
+                "DESC NULLS LAST"
+            } else {
+                "ASC NULLS FIRST"
+            };
+            format!("{} {}", key.expression(), direction)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Rows ordered after the cursor: `(a > ?1) OR (a IS ?1 AND b > ?2) OR ...`,
+/// comparing with `<` on descending keys.
+/// Columns may be NULL, e.g. the date added of audios from older versions,
+/// so keys are matched with `IS` and a NULL counts as below every value.
+/// Row values can't be used as keys may mix directions.
+///
+/// SQLite can't seek an index with that chain, and would rather union index searches
+/// for each alternative and sort them, so the chain hides its columns from the planner
+/// behind a unary `+`. A range on the first key, from the cursor's `values`, is added
+/// instead for SQLite to seek to.
+fn after_cursor_sql(keys: &[OrderKey], values: &[SqlValue], first_param: usize) -> String {
+    let alternatives = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let mut tests = keys[..i]
+                .iter()
+                .enumerate()
+                .map(|(j, equal_key)| {
+                    format!("+{} IS ?{}", equal_key.expression(), first_param + j)
+                })
+                .collect::<Vec<String>>();
+            let (operator, null_after, null_before) = if key.descending {
+                ("<", "", "NOT ")
+            } else {
+                (">", "NOT ", "")
+            };
+            tests.push(format!(
+                "(+{0} {1} ?{2} OR (+{0} IS {3}NULL AND ?{2} IS {4}NULL))",
+                key.expression(),
+                operator,
+                first_param + i,
+                null_after,
+                null_before
+            ));
+            format!("({})", tests.join(" AND "))
+        })
+        .collect::<Vec<String>>();
+    let chain = format!("({})", alternatives.join(" OR "));
+    let seek = match (keys.first(), values.first()) {
+        (Some(key), Some(SqlValue::Null)) if key.descending => {
+            format!("{} IS NULL", key.expression())
+        }
+        // Ascending, NULLs come first so any row may follow one.
+        (Some(_), Some(SqlValue::Null)) => return chain,
+        // Descending, NULLs come last so they follow every value.
+        (Some(key), Some(_)) if key.descending => {
+            format!(
+                "({0} <= ?{1} OR {0} IS NULL)",
+                key.expression(),
+                first_param
+            )
+        }
+        (Some(key), Some(_)) => format!("{} >= ?{}", key.expression(), first_param),
+        _ => return chain,
+    };
+    format!("{} AND {}", seek, chain)
+}
+
+#[cfg(test)]
+mod test_listing_operations {
+    use super::ListOptions;
+    use crate::audio::AudioFile;
+    use crate::database::audio_files::get_audios_by_artist_name;
+    use crate::database::query::{Field, SortTerm};
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+    use time::Duration;
+
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        let tracks = [
+            ("Plainsong", "Disintegration", 1, 1, 1989, 312),
+            ("Pictures of You", "Disintegration", 1, 2, 1989, 448),
+            ("Lovesong", "Disintegration", 1, 5, 1989, 209),
+            ("Fascination Street", "Disintegration", 2, 1, 1989, 317),
+            ("A Forest", "Seventeen Seconds", 1, 5, 1980, 355),
+            ("Play for Today", "Seventeen Seconds", 1, 2, 1980, 220),
+            (
+                "Just Like Heaven",
+                "Kiss Me Kiss Me Kiss Me",
+                1,
+                7,
+                1987,
+                212,
+            ),
+        ];
+        for (n, (title, album, disc, track, year, seconds)) in tracks.into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    artist_name: String::from("The Cure"),
+                    album_name: String::from(album),
+                    disc_num: disc,
+                    track_num: track,
+                    release_year: year,
+                    audio_length: Duration::seconds(seconds),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        temp_audios_context
+    }
+
+    /// Pages through the whole listing, returning the titles of each page.
+    fn page_titles(library: &mut TestInMemoryDBContext, sort: Vec<SortTerm>) -> Vec<Vec<String>> {
+        let mut options = ListOptions {
+            sort,
+            limit: Some(3),
+            after: None,
+        };
+        let mut pages = Vec::new();
+        loop {
+            let page =
+                get_audios_by_artist_name(&mut library.connection, "cure", &options).unwrap();
+            pages.push(page.items.into_iter().map(|a| a.audio_title).collect());
+            match page.next {
+                Some(next) => options.after = Some(next),
+                None => return pages,
+            }
+        }
+    }
+
+    #[rstest]
+    fn test_default_order_is_album_disc_track(mut library: TestInMemoryDBContext) {
+        assert_eq!(
+            page_titles(&mut library, Vec::new()),
+            vec![
+                vec!["Plainsong", "Pictures of You", "Lovesong"],
+                vec!["Fascination Street", "Just Like Heaven", "Play for Today"],
+                vec!["A Forest"],
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_pages_with_mixed_sort_directions(mut library: TestInMemoryDBContext) {
+        let sort = vec![
+            SortTerm {
+                field: Field::Year,
+                descending: false,
+            },
+            SortTerm {
+                field: Field::Length,
+                descending: true,
+            },
+        ];
+        assert_eq!(
+            page_titles(&mut library, sort),
+            vec![
+                vec!["A Forest", "Play for Today", "Just Like Heaven"],
+                vec!["Pictures of You", "Fascination Street", "Plainsong"],
+                vec!["Lovesong"],
+            ]
+        );
+    }
+
+    /// NULL sort values, e.g. the date added of audios from older versions,
+    /// come first ascending and last descending, and pages step over them.
+    #[rstest]
+    #[case(false, vec![
+        vec!["Plainsong", "Pictures of You", "Fascination Street"],
+        vec!["Play for Today", "Lovesong", "A Forest"],
+        vec!["Just Like Heaven"],
+    ])]
+    #[case(true, vec![
+        vec!["Just Like Heaven", "Lovesong", "A Forest"],
+        vec!["Plainsong", "Pictures of You", "Fascination Street"],
+        vec!["Play for Today"],
+    ])]
+    fn test_pages_across_null_values(
+        mut library: TestInMemoryDBContext,
+        #[case] descending: bool,
+        #[case] expected_pages: Vec<Vec<&str>>,
+    ) {
+        library
+            .connection
+            .execute(
+                r"UPDATE audios
+                SET date_added = CASE WHEN track_num > 2 THEN track_num END",
+                (),
+            )
+            .unwrap();
+        let sort = vec![SortTerm {
+            field: Field::Added,
+            descending,
+        }];
+        assert_eq!(page_titles(&mut library, sort), expected_pages);
+    }
+
+    /// A full last page still ends the listing, rather than leading to an empty page.
+    #[rstest]
+    fn test_exact_final_page_has_no_next(mut library: TestInMemoryDBContext) {
+        let options = ListOptions {
+            limit: Some(7),
+            ..ListOptions::default()
+        };
+        let page = get_audios_by_artist_name(&mut library.connection, "cure", &options).unwrap();
+        assert_eq!(page.items.len(), 7);
+        assert_eq!(page.next, None);
+    }
+
+    #[rstest]
+    fn test_cursor_from_another_sort_is_rejected(mut library: TestInMemoryDBContext) {
+        let mut options = ListOptions {
+            limit: Some(2),
+            ..ListOptions::default()
+        };
+        options.after = get_audios_by_artist_name(&mut library.connection, "cure", &options)
+            .unwrap()
+            .next;
+        options.sort = vec![SortTerm {
+            field: Field::Title,
+            descending: false,
+        }];
+        let result = get_audios_by_artist_name(&mut library.connection, "cure", &options);
+        assert!(result.is_err());
+    }
+}
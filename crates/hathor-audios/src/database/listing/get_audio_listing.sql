@@ -0,0 +1,3 @@
+SELECT audio_listings.*
+FROM audio_listings
+WHERE audio_listings.audio_file_id = ?1;
@@ -0,0 +1,68 @@
+-- Recreated on every connect, so the view always matches the tables it reads.
+DROP VIEW IF EXISTS audio_listings;
+
+-- One row per stored location of an audio, with every column listings sort on.
+CREATE VIEW audio_listings AS
+SELECT
+    audios.file_hash
+    , audios.audio_title
+    , audios.album_name
+    , audios.artist_name
+    , audios.album_artist_name
+    , audios.genre
+    , audios.composer
+    , audios.lyrics
+    , audios.track_num
+    , audios.disc_num
+    , audios.release_year
+    , audios.audio_length_seconds
+    , audios.date_added
+    , audios.start_offset_ns
+    , audios.end_offset_ns
+    -- Unplayed audios count as 0 plays and skips, last played at 0.
+    -- Plays are looked up per audio rather than joined from their grouped view,
+    -- so SQLite only reads them for the rows a query returns, through their index.
+    , (
+        SELECT IFNULL(SUM(plays.completed), 0)
+        FROM plays
+        WHERE plays.file_hash = audios.file_hash
+    ) AS play_count
+    , (
+        SELECT IFNULL(SUM(NOT plays.completed), 0)
+        FROM plays
+        WHERE plays.file_hash = audios.file_hash
+    ) AS skip_count
+    , (
+        SELECT IFNULL(MAX(plays.started_at), 0)
+        FROM plays
+        WHERE plays.file_hash = audios.file_hash
+    ) AS last_played
+    -- Unrated audios sort below 0 stars.
+    , IFNULL(audio_ratings.half_stars, -1) AS rating
+    , audio_favourites.file_hash IS NOT NULL AS favourite
+    -- The audio's tags joined in name order, for sorting on, looked up per audio like plays.
+    , (
+        SELECT IFNULL(GROUP_CONCAT(ordered_tags.tag, ', '), '')
+        FROM (
+            SELECT audio_tags.tag
+            FROM audio_tags
+            WHERE audio_tags.file_hash = audios.file_hash
+            ORDER BY audio_tags.tag COLLATE NOCASE
+        ) AS ordered_tags
+    ) AS user_tags
+    , audio_files.rowid AS audio_file_id
+    , audio_files.audio_path
+    , audio_files.img_path
+    , audio_folders.folder_path
+    , img_folders.folder_path AS img_folder_path
+FROM audios
+    INNER JOIN audio_files
+        ON audios.file_hash = audio_files.file_hash
+    LEFT JOIN user_media_folders AS audio_folders
+        ON audio_files.folder_id = audio_folders.folder_id
+    LEFT JOIN user_media_folders AS img_folders
+        ON audio_files.img_folder_id = img_folders.folder_id
+    LEFT JOIN audio_ratings
+        ON audios.file_hash = audio_ratings.file_hash
+    LEFT JOIN audio_favourites
+        ON audios.file_hash = audio_favourites.file_hash;
@@ -0,0 +1,333 @@
+//! Upgrades databases created by older versions of Hathor.
+//!
+//! A database's `user_version` counts the migrations applied to it.
+//! New databases are created with the latest tables, so they skip every migration.
+//! Migrations read and write through their own SQL, which stays as the tables were
+//! when the migration was added, rather than through the SQL of the current tables.
+
+use crate::database::os_paths::{path_to_bytes, path_to_display};
+use crate::database::user_media_folders::{query_user_media_folders, rebase_audio_files};
+use rusqlite::{named_params, Connection, OptionalExtension, Transaction};
+use std::error::Error;
+use std::path::Path;
+
+type Migration = fn(&Transaction) -> Result<(), Box<dyn Error>>;
+
+/// Every migration in the order they were added. Only ever append to this.
+const MIGRATIONS: &[Migration] = &[
+    migrate_paths_to_bytes,
+    migrate_paths_to_media_roots,
+    migrate_tag_columns,
+    migrate_lengths_to_seconds,
+    migrate_listing_columns,
+    migrate_playlist_items,
+    migrate_playlists_to_entities,
+    migrate_cue_offsets,
+];
+
+/// Applies the migrations the database hasn't had yet, each in its own transaction.
+/// Must run before the tables are created, as older tables may share their names.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to upgrade.
+pub(crate) fn migrate(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let version = conn.pragma_query_value(None, "user_version", |row| row.get::<usize, i64>(0))?;
+    let version = usize::try_from(version)?;
+    if version > MIGRATIONS.len() {
+        return Err(format!(
+            "database version {} is newer than this version of Hathor supports",
+            version
+        )
+        .into());
+    }
+    // Databases from before versioning are at version 0 too, but already have tables.
+    if version == 0 && !table_exists(conn, "audios")? {
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() as i64)?;
+        return Ok(());
+    }
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = conn.transaction()?;
+        migration(&transaction)?;
+        transaction.pragma_update(None, "user_version", applied as i64 + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table_name: &str) -> rusqlite::Result<bool> {
+    Ok(conn
+        .query_row(
+            include_str!("migrations/get_table.sql"),
+            named_params! {":table_name": table_name},
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Paths stored as text become raw OS bytes, with a lossy copy kept for display.
+fn migrate_paths_to_bytes(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_paths_to_bytes.sql"))?;
+    let folder_paths = transaction
+        .prepare(include_str!("migrations/get_legacy_user_media_folders.sql"))?
+        .query_map((), |row| row.get::<usize, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let audio_files = transaction
+        .prepare(include_str!("migrations/get_legacy_audio_files.sql"))?
+        .query_map((), |row| {
+            Ok((
+                row.get::<usize, String>(0)?,
+                row.get::<usize, String>(1)?,
+                row.get::<usize, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut statement = transaction.prepare(include_str!(
+        "migrations/insert_path_bytes_user_media_folder.sql"
+    ))?;
+    for folder_path in &folder_paths {
+        let folder_path = Path::new(folder_path);
+        statement.execute(named_params! {
+            ":folder_path": path_to_bytes(folder_path),
+            ":folder_path_display": path_to_display(folder_path),
+        })?;
+    }
+    let mut statement =
+        transaction.prepare(include_str!("migrations/insert_path_bytes_audio_file.sql"))?;
+    for (file_hash, audio_path, img_path) in audio_files {
+        let audio_path = Path::new(&audio_path);
+        let img_path = img_path.as_deref().map(Path::new);
+        statement.execute(named_params! {
+            ":file_hash": file_hash,
+            ":audio_path": path_to_bytes(audio_path),
+            ":audio_path_display": path_to_display(audio_path),
+            ":img_path": img_path.map(path_to_bytes),
+            ":img_path_display": img_path.map(path_to_display),
+        })?;
+    }
+    transaction.execute_batch(include_str!("migrations/drop_legacy_path_tables.sql"))?;
+    Ok(())
+}
+
+/// Audio files under a media root are re-stored relative to it.
+fn migrate_paths_to_media_roots(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_paths_to_media_roots.sql"))?;
+    for folder in query_user_media_folders(transaction)? {
+        rebase_audio_files(transaction, folder.folder_id, &folder.folder_path)?;
+    }
+    Ok(())
+}
+
+/// Adds the tags searched by full-text search to audios.
+fn migrate_tag_columns(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_tag_columns.sql"))?;
+    Ok(())
+}
+
+/// Lengths were stored in nanoseconds, despite the column's name.
+fn migrate_lengths_to_seconds(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_lengths_to_seconds.sql"))?;
+    Ok(())
+}
+
+/// Adds the disc number and date added that listings sort on to audios.
+fn migrate_listing_columns(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_listing_columns.sql"))?;
+    Ok(())
+}
+
+/// Playlist entries become ordered items, which may repeat an audio.
+fn migrate_playlist_items(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_playlist_items.sql"))?;
+    Ok(())
+}
+
+/// Playlists become rows of their own, which their items refer to.
+fn migrate_playlists_to_entities(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_playlists_to_entities.sql"))?;
+    Ok(())
+}
+
+/// Adds the offsets of CUE sheet tracks into their file to audios.
+fn migrate_cue_offsets(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(include_str!("migrations/migrate_cue_offsets.sql"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_migrations {
+    use super::MIGRATIONS;
+    use crate::audio::AudioFile;
+    use crate::database::audio_files::get_audio_by_hash;
+    use crate::database::initialise_db::init_db;
+    use crate::database::listing::ListOptions;
+    use crate::database::os_paths::path_from_bytes;
+    use crate::database::playlists::{get_playlist_items, list_playlists};
+    use crate::database::search::search;
+    use blake3::Hash;
+    use rstest::rstest;
+    use rusqlite::Connection;
+    use std::path::PathBuf;
+    use time::Duration;
+
+    /// Tables as the first release of Hathor created them, before versioning.
+    const UNVERSIONED_TABLES: &str = r"
+        CREATE TABLE playlists (
+            playlist_name VARCHAR(256)
+            , file_hash CHAR(64)
+            , PRIMARY KEY (playlist_name, file_hash)
+        ) WITHOUT ROWID;
+        CREATE TABLE audios (
+            file_hash CHAR(64) PRIMARY KEY
+            , audio_title VARCHAR(256)
+            , album_name VARCHAR(256)
+            , artist_name VARCHAR(256)
+            , track_num INT(8)
+            , release_year INT(16)
+            , audio_length_seconds INT(64)
+        ) WITHOUT ROWID;
+        CREATE TABLE audio_files (
+            file_hash CHAR(64)
+            , audio_path VARCHAR(256)
+            , img_path VARCHAR(256)
+            , PRIMARY KEY (file_hash, audio_path)
+        ) WITHOUT ROWID;
+        CREATE TABLE user_media_folders (
+            folder_path VARCHAR(256) PRIMARY KEY
+        ) WITHOUT ROWID;
+    ";
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[rstest]
+    fn test_new_database_starts_at_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_db(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+        init_db(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+    }
+
+    #[rstest]
+    fn test_newer_database_fails() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() as i64 + 1)
+            .unwrap();
+        assert!(init_db(&mut conn).is_err());
+    }
+
+    #[rstest]
+    fn test_migrate_unversioned_audios() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(UNVERSIONED_TABLES).unwrap();
+        conn.execute_batch(&format!(
+            r"INSERT INTO audios VALUES
+                ('{0:064}', 'Roads', 'Dummy', 'Portishead', 10, 1994, 305000000000)
+                , ('{1:064}', 'Teardrop', 'Mezzanine', 'Massive Attack', 3, 1998, 330500000000);
+            INSERT INTO audio_files VALUES
+                ('{0:064}', '/music/Dummy/10 Roads.mp3', '/music/Dummy/cover.png')
+                , ('{1:064}', '/downloads/Teardrop.mp3', NULL);
+            INSERT INTO user_media_folders VALUES ('/music');",
+            0, 1
+        ))
+        .unwrap();
+        init_db(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+
+        assert_eq!(
+            get_audio_by_hash(&mut conn, &Hash::from_hex(format!("{:064}", 0)).unwrap()),
+            AudioFile {
+                audio_title: String::from("Roads"),
+                album_name: String::from("Dummy"),
+                artist_name: String::from("Portishead"),
+                track_num: 10,
+                release_year: 1994,
+                audio_length: Duration::seconds(305),
+                audio_path: PathBuf::from("/music/Dummy/10 Roads.mp3"),
+                img_path: Some(PathBuf::from("/music/Dummy/cover.png")),
+                ..AudioFile::default()
+            }
+        );
+        let locations = conn
+            .prepare(
+                r"SELECT folder_id, img_folder_id, audio_path_display
+                FROM audio_files ORDER BY file_hash",
+            )
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<(Option<i64>, Option<i64>, String)>, _>>()
+            .unwrap();
+        assert_eq!(
+            locations,
+            [
+                (Some(1), Some(1), String::from("/music/Dummy/10 Roads.mp3")),
+                (None, None, String::from("/downloads/Teardrop.mp3"))
+            ]
+        );
+        let folders = conn
+            .prepare(r"SELECT folder_path, folder_path_display FROM user_media_folders")
+            .unwrap()
+            .query_map((), |row| Ok((path_from_bytes(row.get(0)?), row.get(1)?)))
+            .unwrap()
+            .collect::<Result<Vec<(PathBuf, String)>, _>>()
+            .unwrap();
+        assert_eq!(folders, [(PathBuf::from("/music"), String::from("/music"))]);
+        let found = search(&mut conn, "teardrop", &ListOptions::default()).unwrap();
+        assert_eq!(found.items.len(), 1);
+        assert_eq!(found.items[0].audio_length, Duration::seconds(330));
+    }
+
+    #[rstest]
+    fn test_migrate_unversioned_playlists() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(UNVERSIONED_TABLES).unwrap();
+        conn.execute_batch(&format!(
+            r"INSERT INTO audios VALUES
+                ('{0:064}', 'Zero', '', '', 1, 2000, 60000000000)
+                , ('{1:064}', 'One', '', '', 2, 2000, 90000000000);
+            INSERT INTO audio_files VALUES
+                ('{0:064}', '/music/0.mp3', NULL)
+                , ('{1:064}', '/music/1.mp3', NULL);
+            INSERT INTO playlists VALUES
+                ('Mix', '{1:064}')
+                , ('Mix', '{0:064}')
+                , ('Solo', '{1:064}');",
+            0, 1
+        ))
+        .unwrap();
+        init_db(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+
+        let playlists = list_playlists(&mut conn, &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|p| (p.playlist_name, p.track_count, p.audio_length))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            playlists,
+            [
+                (String::from("Mix"), Some(2), Some(Duration::seconds(150))),
+                (String::from("Solo"), Some(1), Some(Duration::seconds(90)))
+            ]
+        );
+        let titles = |conn: &mut Connection, playlist_name| {
+            get_playlist_items(conn, playlist_name, &ListOptions::default())
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|item| (item.position, item.audio.unwrap().audio_title))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            titles(&mut conn, "Mix"),
+            [(0, String::from("Zero")), (1, String::from("One"))]
+        );
+        assert_eq!(titles(&mut conn, "Solo"), [(0, String::from("One"))]);
+    }
+}
@@ -0,0 +1,3 @@
+DROP TABLE legacy_audio_files;
+
+DROP TABLE legacy_user_media_folders;
@@ -0,0 +1,5 @@
+SELECT
+    legacy_audio_files.file_hash
+    , legacy_audio_files.audio_path
+    , legacy_audio_files.img_path
+FROM legacy_audio_files;
@@ -0,0 +1,3 @@
+SELECT legacy_user_media_folders.folder_path
+FROM legacy_user_media_folders
+ORDER BY legacy_user_media_folders.folder_path;
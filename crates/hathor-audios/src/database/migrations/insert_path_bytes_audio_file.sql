@@ -0,0 +1,13 @@
+INSERT OR IGNORE INTO audio_files (
+    file_hash
+    , audio_path
+    , audio_path_display
+    , img_path
+    , img_path_display
+) VALUES (
+    :file_hash
+    , :audio_path
+    , :audio_path_display
+    , :img_path
+    , :img_path_display
+);
@@ -0,0 +1,4 @@
+-- Existing audios play their whole file.
+ALTER TABLE audios ADD COLUMN start_offset_ns INT(64) NOT NULL DEFAULT 0;
+
+ALTER TABLE audios ADD COLUMN end_offset_ns INT(64);
@@ -0,0 +1,2 @@
+UPDATE audios
+SET audio_length_seconds = audios.audio_length_seconds / 1000000000;
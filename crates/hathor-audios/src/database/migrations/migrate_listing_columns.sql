@@ -0,0 +1,8 @@
+ALTER TABLE audios ADD COLUMN disc_num INT(8);
+
+-- Left NULL for existing audios, when they were first inserted wasn't stored.
+ALTER TABLE audios ADD COLUMN date_added INT(64);
+
+-- Existing audios get the disc number of audios without one.
+UPDATE audios
+SET disc_num = 1;
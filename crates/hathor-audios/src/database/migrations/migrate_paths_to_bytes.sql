@@ -0,0 +1,17 @@
+-- Paths were stored as text, which can't hold every OS path.
+ALTER TABLE user_media_folders RENAME TO legacy_user_media_folders;
+ALTER TABLE audio_files RENAME TO legacy_audio_files;
+
+CREATE TABLE user_media_folders (
+    folder_path BLOB PRIMARY KEY -- Raw OS path bytes, no length limit.
+    , folder_path_display TEXT -- Lossy UTF-8 form of folder_path, display only.
+) WITHOUT ROWID;
+
+CREATE TABLE audio_files (
+    file_hash CHAR(64)
+    , audio_path BLOB -- Raw OS path bytes, no length limit.
+    , audio_path_display TEXT -- Lossy UTF-8 form of audio_path, display only.
+    , img_path BLOB -- Raw OS path bytes, no length limit.
+    , img_path_display TEXT -- Lossy UTF-8 form of img_path, display only.
+    , PRIMARY KEY (file_hash, audio_path)
+) WITHOUT ROWID;
@@ -0,0 +1,54 @@
+-- Paths were only ever absolute, those under a media root become relative to it.
+-- The rows are copied with absolute paths here, and rebased onto their roots after.
+ALTER TABLE user_media_folders RENAME TO legacy_user_media_folders;
+ALTER TABLE audio_files RENAME TO legacy_audio_files;
+
+CREATE TABLE user_media_folders (
+    folder_id INTEGER PRIMARY KEY
+    , folder_path BLOB UNIQUE -- Raw OS path bytes, no length limit.
+    , folder_path_display TEXT -- Lossy UTF-8 form of folder_path, display only.
+);
+
+CREATE TABLE audio_files (
+    file_hash CHAR(64) NOT NULL
+    -- Media root audio_path is relative to, NULL when it is absolute.
+    , folder_id INTEGER REFERENCES user_media_folders (folder_id)
+    , audio_path BLOB NOT NULL -- Raw OS path bytes, no length limit.
+    , audio_path_display TEXT -- Lossy UTF-8 form of audio_path, display only.
+    -- Media root img_path is relative to, which may differ from the audio's.
+    , img_folder_id INTEGER REFERENCES user_media_folders (folder_id)
+    , img_path BLOB -- Raw OS path bytes, no length limit.
+    , img_path_display TEXT -- Lossy UTF-8 form of img_path, display only.
+);
+
+CREATE UNIQUE INDEX audio_files_location
+ON audio_files (file_hash, IFNULL(folder_id, 0), audio_path);
+
+INSERT INTO user_media_folders (
+    folder_path
+    , folder_path_display
+)
+SELECT
+    legacy_user_media_folders.folder_path
+    , legacy_user_media_folders.folder_path_display
+FROM legacy_user_media_folders
+ORDER BY legacy_user_media_folders.folder_path;
+
+INSERT INTO audio_files (
+    file_hash
+    , audio_path
+    , audio_path_display
+    , img_path
+    , img_path_display
+)
+SELECT
+    legacy_audio_files.file_hash
+    , legacy_audio_files.audio_path
+    , legacy_audio_files.audio_path_display
+    , legacy_audio_files.img_path
+    , legacy_audio_files.img_path_display
+FROM legacy_audio_files;
+
+DROP TABLE legacy_audio_files;
+
+DROP TABLE legacy_user_media_folders;
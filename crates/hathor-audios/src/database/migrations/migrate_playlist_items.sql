@@ -0,0 +1,29 @@
+-- Playlists used to be one row per playlist and audio, with no order or duplicates.
+CREATE TABLE playlist_items (
+    item_id INTEGER PRIMARY KEY
+    , playlist_name VARCHAR(256) NOT NULL
+    , file_hash CHAR(64) NOT NULL
+    -- 0 based and contiguous within a playlist.
+    -- Not unique, so a range of items can be shifted with one UPDATE.
+    , position INTEGER NOT NULL
+);
+
+CREATE INDEX playlist_items_position
+ON playlist_items (playlist_name, position);
+
+-- The old rows had no order, so items keep the order the old table listed them in.
+INSERT INTO playlist_items (
+    playlist_name
+    , file_hash
+    , position
+)
+SELECT
+    playlists.playlist_name
+    , playlists.file_hash
+    , ROW_NUMBER() OVER (
+        PARTITION BY playlists.playlist_name
+        ORDER BY playlists.file_hash
+    ) - 1
+FROM playlists;
+
+DROP TABLE playlists;
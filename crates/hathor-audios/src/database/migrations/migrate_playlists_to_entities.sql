@@ -0,0 +1,56 @@
+-- Playlists used to exist only as the names on their items.
+ALTER TABLE playlist_items RENAME TO legacy_playlist_items;
+
+CREATE TABLE playlists (
+    playlist_id INTEGER PRIMARY KEY
+    , playlist_name VARCHAR(256) NOT NULL UNIQUE
+    , description TEXT NOT NULL DEFAULT ''
+    , created_at INT(64) NOT NULL -- Unix seconds.
+    , modified_at INT(64) NOT NULL -- Unix seconds, updated when the items change too.
+    , img_path BLOB -- Raw OS path bytes of the cover, no length limit.
+    , img_path_display TEXT -- Lossy UTF-8 form of img_path, display only.
+);
+
+CREATE TABLE playlist_items (
+    item_id INTEGER PRIMARY KEY
+    , playlist_id INTEGER NOT NULL REFERENCES playlists (playlist_id)
+    , file_hash CHAR(64) NOT NULL
+    -- 0 based and contiguous within a playlist.
+    -- Not unique, so a range of items can be shifted with one UPDATE.
+    , position INTEGER NOT NULL
+);
+
+-- When the old playlists were made wasn't stored, so they count as made now.
+INSERT INTO playlists (
+    playlist_name
+    , created_at
+    , modified_at
+)
+SELECT DISTINCT
+    legacy_playlist_items.playlist_name
+    , UNIXEPOCH()
+    , UNIXEPOCH()
+FROM legacy_playlist_items
+ORDER BY legacy_playlist_items.playlist_name;
+
+-- Items keep their IDs and positions.
+INSERT INTO playlist_items (
+    item_id
+    , playlist_id
+    , file_hash
+    , position
+)
+SELECT
+    legacy_playlist_items.item_id
+    , playlists.playlist_id
+    , legacy_playlist_items.file_hash
+    , legacy_playlist_items.position
+FROM legacy_playlist_items
+    INNER JOIN playlists
+        ON legacy_playlist_items.playlist_name = playlists.playlist_name;
+
+-- Dropping the old table frees the name of its index.
+DROP TABLE legacy_playlist_items;
+
+CREATE INDEX playlist_items_position
+ON playlist_items (playlist_id, position);
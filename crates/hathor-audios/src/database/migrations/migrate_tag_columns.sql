@@ -0,0 +1,16 @@
+-- Tags read since the first release.
+ALTER TABLE audios ADD COLUMN album_artist_name VARCHAR(256);
+
+ALTER TABLE audios ADD COLUMN genre VARCHAR(256);
+
+ALTER TABLE audios ADD COLUMN composer VARCHAR(256);
+
+ALTER TABLE audios ADD COLUMN lyrics TEXT;
+
+-- Existing audios get the values of untagged audios.
+UPDATE audios
+SET
+    album_artist_name = ''
+    , genre = ''
+    , composer = ''
+    , lyrics = '';
@@ -0,0 +1,74 @@
+//! Lossless conversion between paths and the raw bytes stored in the DB.
+//!
+//! Paths are not guaranteed to be valid UTF-8 (e.g. Linux file names are arbitrary bytes),
+//! so they are stored as BLOBs of their native OS representation rather than as text.
+//! On Windows this is the UTF-16 code units of the path, stored little-endian.
+
+use std::path::{Path, PathBuf};
+
+/// Returns the raw OS bytes of the given path.
+#[cfg(unix)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Rebuilds a path from raw OS bytes created by [path_to_bytes].
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+/// Returns the raw OS bytes of the given path.
+#[cfg(windows)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str()
+        .encode_wide()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Rebuilds a path from raw OS bytes created by [path_to_bytes].
+#[cfg(windows)]
+pub(crate) fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+    let wide: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    PathBuf::from(std::ffi::OsString::from_wide(&wide))
+}
+
+/// Human readable form of a path, for display columns only.
+/// Invalid UTF-8 is replaced, so this must never be read back as a path.
+pub(crate) fn path_to_display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod os_paths_tests {
+    use super::{path_from_bytes, path_to_bytes};
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    #[rstest]
+    #[case(PathBuf::from("/music/album/track.mp3"))]
+    #[case(PathBuf::from("/music/Sigur Rós/Ágætis byrjun/01 ✕.flac"))]
+    #[case(PathBuf::from("relative/path.ogg"))]
+    fn test_path_bytes_round_trip(#[case] path: PathBuf) {
+        assert_eq!(path_from_bytes(path_to_bytes(&path)), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_bytes_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let path = PathBuf::from(OsStr::from_bytes(b"/music/caf\xe9/\xff\xfe.mp3"));
+        let bytes = path_to_bytes(&path);
+        assert_eq!(bytes, b"/music/caf\xe9/\xff\xfe.mp3");
+        assert_eq!(path_from_bytes(bytes), path);
+    }
+}
@@ -1,10 +1,278 @@
 use crate::audio::{self, AudioFile};
-use crate::database::{query_map_to_audiofiles, INSERT_BATCH_SIZE};
+use crate::database::listing::{
+    query_audio_page, query_page, query_rows_page, ListOptions, OrderKey, Page,
+};
+use crate::database::os_paths::{path_from_bytes, path_to_bytes, path_to_display};
+use crate::database::smart_playlists::{
+    delete_smart_playlist, get_audios_from_smart_playlist, is_smart_playlist, smart_playlist_totals,
+};
+use crate::database::INSERT_BATCH_SIZE;
 use rusqlite::named_params;
-use rusqlite::Connection;
+use rusqlite::types::{Type, Value as SqlValue};
+use rusqlite::{Connection, OptionalExtension, Row, Transaction};
+use std::collections::HashSet;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use time::{Duration, OffsetDateTime};
 
-/// Inserts a slice of [AudioFile](super::audio::AudioFile)s into a playlist in the DB.
+/// A playlist and totals over its audios.
+/// Names are unique, so playlists are looked up by name.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Playlist {
+    pub playlist_id: i64,
+    pub playlist_name: String,
+    pub description: String,
+    pub created_at: OffsetDateTime,
+    /// Last change to the playlist or its items.
+    pub modified_at: OffsetDateTime,
+    pub img_path: Option<PathBuf>,
+    /// Audios chosen by rules rather than items,
+    /// see [smart_playlists](super::smart_playlists).
+    pub smart: bool,
+    /// None for smart playlists from [list_playlists], which doesn't evaluate their rules.
+    pub track_count: Option<u32>,
+    /// None whenever `track_count` is.
+    pub audio_length: Option<Duration>,
+}
+
+/// Items in playlist order, see [PlaylistItem::position].
+const PLAYLIST_ORDER: [OrderKey; 2] = [
+    OrderKey::ascending("position"),
+    OrderKey::ascending("item_id"),
+];
+
+/// An entry of a playlist. The same audio may appear in several items.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PlaylistItem {
+    /// Identifies the item, unlike the audio's hash it is unique within the playlist.
+    pub item_id: i64,
+    /// 0 based index of the item in its playlist.
+    pub position: usize,
+    /// None if the audio has left the library, e.g. its file was deleted.
+    /// The item keeps its place, and finds the audio again if it is scanned back in.
+    pub audio: Option<AudioFile>,
+}
+
+/// Creates an empty playlist.
+/// Fails if a playlist with the name already exists.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `description` - Free text shown with the playlist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::create_playlist;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let playlist = create_playlist(&mut conn, "Road trip", "Songs for the M6");
+pub fn create_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+    description: &str,
+) -> Result<Playlist, Box<dyn Error>> {
+    insert_playlist(conn, playlist_name, description)?;
+    get_playlist(conn, playlist_name)?
+        .ok_or_else(|| format!("playlist {} was not created", playlist_name).into())
+}
+
+/// Retvieve the named playlist, None if it doesn't exist.
+/// A smart playlist is evaluated to fill in its totals.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - The playlist to retrieve (exact match only).
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::playlists::get_playlist;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let playlist = get_playlist(&mut conn, "Playlist name");
+pub fn get_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+) -> Result<Option<Playlist>, Box<dyn Error>> {
+    let playlist = conn
+        .query_row(
+            include_str!("playlists/get_playlist.sql"),
+            named_params! {":playlist_name": playlist_name},
+            playlist_select_result_to_playlist,
+        )
+        .optional()?;
+    playlist
+        .map(|playlist| with_smart_totals(conn, playlist))
+        .transpose()
+}
+
+/// Retvieve a page of the playlists ordered by name, including empty ones.
+/// Smart playlists are listed without totals, as each would need a full evaluation;
+/// use [get_playlist] to total one of them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `options` - Page size and cursor, see [ListOptions]. Playlists can't be sorted.
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::playlists::list_playlists;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let page = list_playlists(&mut conn, &ListOptions::default());
+pub fn list_playlists(
+    conn: &mut Connection,
+    options: &ListOptions,
+) -> Result<Page<Playlist>, Box<dyn Error>> {
+    query_rows_page(
+        conn,
+        include_str!("playlists/list_playlists.sql"),
+        Vec::new(),
+        options,
+        &[],
+        &[
+            OrderKey::ascending_text("playlist_name"),
+            OrderKey::ascending("playlist_id"),
+        ],
+        playlist_select_result_to_playlist,
+    )
+}
+
+/// Renames a playlist, its items are kept.
+/// Fails if the playlist doesn't exist or the new name is taken.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Current title of the playlist.
+/// * `new_playlist_name` - Title the playlist will have.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::rename_playlist;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// rename_playlist(&mut conn, "Road trip", "Road trip 2024");
+pub fn rename_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+    new_playlist_name: &str,
+) -> Result<(), Box<dyn Error>> {
+    let playlist_id = get_existing_playlist_id(conn, playlist_name)?;
+    conn.execute(
+        include_str!("playlists/rename_playlist.sql"),
+        named_params! {":playlist_id": playlist_id, ":new_playlist_name": new_playlist_name},
+    )?;
+    Ok(())
+}
+
+/// Replaces the description of a playlist.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `description` - Free text shown with the playlist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::set_playlist_description;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// set_playlist_description(&mut conn, "Road trip", "Songs for the A1");
+pub fn set_playlist_description(
+    conn: &mut Connection,
+    playlist_name: &str,
+    description: &str,
+) -> Result<(), Box<dyn Error>> {
+    let playlist_id = get_existing_playlist_id(conn, playlist_name)?;
+    conn.execute(
+        include_str!("playlists/set_playlist_description.sql"),
+        named_params! {":playlist_id": playlist_id, ":description": description},
+    )?;
+    Ok(())
+}
+
+/// Sets or clears the cover image of a playlist.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `img_path` - Path to the cover, None to remove it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::set_playlist_cover;
+/// use std::path::Path;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// set_playlist_cover(&mut conn, "Road trip", Some(Path::new("/home/me/road.png")));
+pub fn set_playlist_cover(
+    conn: &mut Connection,
+    playlist_name: &str,
+    img_path: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let playlist_id = get_existing_playlist_id(conn, playlist_name)?;
+    conn.execute(
+        include_str!("playlists/set_playlist_cover.sql"),
+        named_params! {
+            ":playlist_id": playlist_id,
+            ":img_path": img_path.map(path_to_bytes),
+            ":img_path_display": img_path.map(path_to_display),
+        },
+    )?;
+    Ok(())
+}
+
+/// Deletes a playlist and all of its items. The audios themselves are kept.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::delete_playlist;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// delete_playlist(&mut conn, "Road trip");
+pub fn delete_playlist(conn: &mut Connection, playlist_name: &str) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_existing_playlist_id(&transaction, playlist_name)?;
+    transaction.execute(
+        include_str!("playlists/delete_playlist_items.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    delete_smart_playlist(&transaction, playlist_id)?;
+    transaction.execute(
+        include_str!("playlists/delete_playlist.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Appends a slice of [AudioFile](super::audio::AudioFile)s to a playlist in the DB.
+/// Audios already in the playlist are added again.
+/// The playlist is created if it doesn't exist.
 ///
 /// # Arguments
 ///
@@ -38,27 +306,430 @@ pub fn insert_audios_into_playlist(
     Ok(())
 }
 
-/// Retvieve audios from the named playlist.
+/// Retvieve audios from the named playlist, in playlist order unless `options` sorts them.
+/// Items whose audio has left the library are skipped, see [get_playlist_items] for them.
+/// Smart playlists are evaluated now, in the order of their rules.
 ///
 /// # Arguments
 ///
 /// * `conn` - The open database connection to insert into.
 /// * `playlist_name` - The playlist to retrieve (exact match only).
+/// * `options` - Sort, page size and cursor, see [ListOptions].
 ///
 /// Examples
 /// ```no_run
 /// use rusqlite::Connection;
+/// use hathor_audios::database::listing::ListOptions;
 /// use hathor_audios::database::playlists::get_audios_from_playlist;
 ///
 /// let mut conn = Connection::open_in_memory().unwrap();
-/// let audios = get_audios_from_playlist(&mut conn, "Playlist name");
-pub fn get_audios_from_playlist(conn: &mut Connection, playlist_name: &str) -> Vec<AudioFile> {
-    query_map_to_audiofiles(
+/// let page = get_audios_from_playlist(&mut conn, "Playlist name", &ListOptions::default());
+pub fn get_audios_from_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    if let Some(playlist_id) = get_playlist_id(conn, playlist_name)? {
+        if is_smart_playlist(conn, playlist_id)? {
+            return get_audios_from_smart_playlist(conn, playlist_id, options);
+        }
+    }
+    query_page(
         conn,
         include_str!("playlists/get_audios_from_playlist.sql"),
-        named_params! {":playlist_name": playlist_name.to_string() },
+        vec![SqlValue::Text(playlist_name.to_string())],
+        options,
+        &PLAYLIST_ORDER,
+    )
+}
+
+/// Retvieve a page of the items of the named playlist, with their IDs and positions,
+/// in playlist order unless `options` sorts them.
+/// Returns an empty page if the playlist doesn't exist.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - The playlist to retrieve (exact match only).
+/// * `options` - Sort, page size and cursor, see [ListOptions].
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::playlists::get_playlist_items;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let page = get_playlist_items(&mut conn, "Playlist name", &ListOptions::default());
+pub fn get_playlist_items(
+    conn: &mut Connection,
+    playlist_name: &str,
+    options: &ListOptions,
+) -> Result<Page<PlaylistItem>, Box<dyn Error>> {
+    let Some(playlist_id) = get_playlist_id(conn, playlist_name)? else {
+        return Ok(Page {
+            items: Vec::new(),
+            next: None,
+        });
+    };
+    query_audio_page(
+        conn,
+        include_str!("playlists/get_playlist_items.sql"),
+        vec![SqlValue::Integer(playlist_id)],
+        options,
+        &PLAYLIST_ORDER,
+        &["item_id", "position"],
+        |audio, row| {
+            Ok(PlaylistItem {
+                item_id: row.get("item_id")?,
+                position: row.get("position")?,
+                audio,
+            })
+        },
+    )
+}
+
+/// Inserts audios into a playlist before the item at `index`, or at the end if `index` is
+/// the playlist's length. The playlist is created if it doesn't exist.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `index` - Position the first audio will have.
+/// * `audios` - Collection of [AudioFile](super::audio::AudioFile)s to insert, in order.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::audio::AudioFile;
+/// use hathor_audios::database::playlists::insert_audios_into_playlist_at;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// insert_audios_into_playlist_at(&mut conn, "my_playlist", 0, &[AudioFile::default()]);
+pub fn insert_audios_into_playlist_at(
+    conn: &mut Connection,
+    playlist_name: &str,
+    index: usize,
+    audios: &[AudioFile],
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_or_create_playlist_id(&transaction, playlist_name)?;
+    let length = count_playlist_items(&transaction, playlist_id)?;
+    if index > length {
+        return Err(index_error(playlist_name, index, length + 1));
+    }
+    shift_playlist_items(&transaction, playlist_id, index, None, audios.len() as i64)?;
+    let mut statement =
+        transaction.prepare_cached(include_str!("playlists/insert_playlist_item.sql"))?;
+    for (i, audio) in audios.iter().enumerate() {
+        statement.execute(named_params! {
+            ":playlist_id": playlist_id,
+            ":file_hash": audio.file_hash.to_string(),
+            ":position": index + i,
+        })?;
+    }
+    drop(statement);
+    touch_playlist(&transaction, playlist_id)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Moves the item at `from_index` so it ends up at `to_index`, shifting the items in between.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `from_index` - Current position of the item.
+/// * `to_index` - Position the item will have.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::move_playlist_item;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// move_playlist_item(&mut conn, "my_playlist", 4, 0);
+pub fn move_playlist_item(
+    conn: &mut Connection,
+    playlist_name: &str,
+    from_index: usize,
+    to_index: usize,
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_item_playlist_id(&transaction, playlist_name)?;
+    let length = count_playlist_items(&transaction, playlist_id)?;
+    if to_index >= length {
+        return Err(index_error(playlist_name, to_index, length));
+    }
+    let item_id = get_playlist_item_id_at(&transaction, playlist_id, playlist_name, from_index)?;
+    if from_index < to_index {
+        shift_playlist_items(
+            &transaction,
+            playlist_id,
+            from_index + 1,
+            Some(to_index),
+            -1,
+        )?;
+    } else if to_index < from_index {
+        shift_playlist_items(&transaction, playlist_id, to_index, Some(from_index - 1), 1)?;
+    }
+    transaction.execute(
+        include_str!("playlists/set_playlist_item_position.sql"),
+        named_params! {":item_id": item_id, ":position": to_index},
+    )?;
+    touch_playlist(&transaction, playlist_id)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Removes the item at `index`, later items move up to fill the gap.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `index` - Position of the item to remove.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::playlists::remove_playlist_item;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// remove_playlist_item(&mut conn, "my_playlist", 2);
+pub fn remove_playlist_item(
+    conn: &mut Connection,
+    playlist_name: &str,
+    index: usize,
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_item_playlist_id(&transaction, playlist_name)?;
+    let item_id = get_playlist_item_id_at(&transaction, playlist_id, playlist_name, index)?;
+    transaction.execute(
+        include_str!("playlists/delete_playlist_item.sql"),
+        named_params! {":item_id": item_id},
+    )?;
+    shift_playlist_items(&transaction, playlist_id, index + 1, None, -1)?;
+    touch_playlist(&transaction, playlist_id)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Puts every item of a playlist in a new order.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `item_ids` - IDs of all of the playlist's items, in their new order.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::playlists::{get_playlist_items, reorder_playlist};
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let items = get_playlist_items(&mut conn, "my_playlist", &ListOptions::default())
+///     .unwrap()
+///     .items;
+/// let reversed = items.iter().rev().map(|item| item.item_id).collect::<Vec<i64>>();
+/// reorder_playlist(&mut conn, "my_playlist", &reversed);
+pub fn reorder_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+    item_ids: &[i64],
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_item_playlist_id(&transaction, playlist_name)?;
+    let current_item_ids = transaction
+        .prepare(include_str!("playlists/get_playlist_item_ids.sql"))?
+        .query_map(named_params! {":playlist_id": playlist_id}, |row| {
+            row.get::<usize, i64>(0)
+        })?
+        .collect::<Result<HashSet<i64>, rusqlite::Error>>()?;
+    if item_ids.len() != current_item_ids.len()
+        || item_ids.iter().collect::<HashSet<&i64>>().len() != item_ids.len()
+        || !item_ids.iter().all(|id| current_item_ids.contains(id))
+    {
+        return Err(format!(
+            "new order of playlist {} must list each of its {} items once",
+            playlist_name,
+            current_item_ids.len()
+        )
+        .into());
+    }
+    let mut statement =
+        transaction.prepare_cached(include_str!("playlists/set_playlist_item_position.sql"))?;
+    for (position, item_id) in item_ids.iter().enumerate() {
+        statement.execute(named_params! {":item_id": item_id, ":position": position})?;
+    }
+    drop(statement);
+    touch_playlist(&transaction, playlist_id)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+fn get_playlist_id(conn: &Connection, playlist_name: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        include_str!("playlists/get_playlist_id.sql"),
+        named_params! {":playlist_name": playlist_name},
+        |row| row.get(0),
     )
-    .unwrap()
+    .optional()
+}
+
+/// Adds a playlist row, returning its ID.
+pub(crate) fn insert_playlist(
+    conn: &Connection,
+    playlist_name: &str,
+    description: &str,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        include_str!("playlists/insert_playlist.sql"),
+        named_params! {":playlist_name": playlist_name, ":description": description},
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn get_existing_playlist_id(conn: &Connection, playlist_name: &str) -> Result<i64, Box<dyn Error>> {
+    get_playlist_id(conn, playlist_name)?
+        .ok_or_else(|| format!("no playlist named {}", playlist_name).into())
+}
+
+fn get_or_create_playlist_id(
+    transaction: &Transaction,
+    playlist_name: &str,
+) -> Result<i64, Box<dyn Error>> {
+    match get_playlist_id(transaction, playlist_name)? {
+        Some(playlist_id) => check_playlist_has_items(transaction, playlist_name, playlist_id),
+        None => Ok(insert_playlist(transaction, playlist_name, "")?),
+    }
+}
+
+/// Like [get_existing_playlist_id], but also fails for smart playlists, which have no items.
+fn get_item_playlist_id(conn: &Connection, playlist_name: &str) -> Result<i64, Box<dyn Error>> {
+    let playlist_id = get_existing_playlist_id(conn, playlist_name)?;
+    check_playlist_has_items(conn, playlist_name, playlist_id)
+}
+
+fn check_playlist_has_items(
+    conn: &Connection,
+    playlist_name: &str,
+    playlist_id: i64,
+) -> Result<i64, Box<dyn Error>> {
+    if is_smart_playlist(conn, playlist_id)? {
+        return Err(format!(
+            "{} is a smart playlist, its items can't be edited",
+            playlist_name
+        )
+        .into());
+    }
+    Ok(playlist_id)
+}
+
+/// Marks the playlist as modified now.
+fn touch_playlist(transaction: &Transaction, playlist_id: i64) -> rusqlite::Result<usize> {
+    transaction.execute(
+        include_str!("playlists/touch_playlist.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )
+}
+
+fn count_playlist_items(transaction: &Transaction, playlist_id: i64) -> rusqlite::Result<usize> {
+    transaction.query_row(
+        include_str!("playlists/count_playlist_items.sql"),
+        named_params! {":playlist_id": playlist_id},
+        |row| row.get(0),
+    )
+}
+
+fn get_playlist_item_id_at(
+    transaction: &Transaction,
+    playlist_id: i64,
+    playlist_name: &str,
+    index: usize,
+) -> Result<i64, Box<dyn Error>> {
+    transaction
+        .query_row(
+            include_str!("playlists/get_playlist_item_id_at.sql"),
+            named_params! {":playlist_id": playlist_id, ":position": index},
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| {
+            let length = count_playlist_items(transaction, playlist_id).unwrap_or(0);
+            index_error(playlist_name, index, length)
+        })
+}
+
+/// Adds `shift` to the positions of items from `first_index` to `last_index` inclusive,
+/// or to the end of the playlist if `last_index` is None.
+fn shift_playlist_items(
+    transaction: &Transaction,
+    playlist_id: i64,
+    first_index: usize,
+    last_index: Option<usize>,
+    shift: i64,
+) -> rusqlite::Result<usize> {
+    transaction.execute(
+        include_str!("playlists/shift_playlist_items.sql"),
+        named_params! {
+            ":playlist_id": playlist_id,
+            ":shift": shift,
+            ":first_position": first_index,
+            ":last_position": last_index.map_or(i64::MAX, |i| i as i64),
+        },
+    )
+}
+
+fn index_error(playlist_name: &str, index: usize, length: usize) -> Box<dyn Error> {
+    format!(
+        "index {} is out of range for playlist {} of length {}",
+        index, playlist_name, length
+    )
+    .into()
+}
+
+/// Smart playlists have no items to total in SQL, so their totals come from evaluating them.
+fn with_smart_totals(conn: &Connection, playlist: Playlist) -> Result<Playlist, Box<dyn Error>> {
+    if !playlist.smart {
+        return Ok(playlist);
+    }
+    let (track_count, audio_length) = smart_playlist_totals(conn, playlist.playlist_id)?;
+    Ok(Playlist {
+        track_count: Some(track_count),
+        audio_length: Some(audio_length),
+        ..playlist
+    })
+}
+
+fn playlist_select_result_to_playlist(row: &Row) -> Result<Playlist, rusqlite::Error> {
+    let timestamp = |column: &str| {
+        OffsetDateTime::from_unix_timestamp(row.get(column)?)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Integer, Box::new(e)))
+    };
+    Ok(Playlist {
+        playlist_id: row.get("playlist_id")?,
+        playlist_name: row.get("playlist_name")?,
+        description: row.get("description")?,
+        created_at: timestamp("created_at")?,
+        modified_at: timestamp("modified_at")?,
+        img_path: row
+            .get::<&str, Option<Vec<u8>>>("img_path")?
+            .map(path_from_bytes),
+        smart: row.get("smart")?,
+        track_count: row.get("track_count")?,
+        audio_length: row
+            .get::<&str, Option<i64>>("audio_length_seconds")?
+            .map(Duration::seconds),
+    })
 }
 
 fn insert_next_batch_of_audios_into_playlist(
@@ -67,13 +738,17 @@ fn insert_next_batch_of_audios_into_playlist(
     audios_iter: &mut std::iter::Peekable<std::slice::Iter<'_, audio::AudioFile>>,
 ) -> Result<(), Box<dyn Error>> {
     let mut statement = transaction
-        .prepare_cached(include_str!(r"playlists/add_audio_to_playlist.sql"))
+        .prepare_cached(include_str!(r"playlists/insert_playlist_item.sql"))
         .unwrap();
-    for _ in 0..=INSERT_BATCH_SIZE {
+    let playlist_id = get_or_create_playlist_id(transaction, playlist_name)?;
+    touch_playlist(transaction, playlist_id)?;
+    let length = count_playlist_items(transaction, playlist_id)?;
+    for position in length..=length + INSERT_BATCH_SIZE as usize {
         if let Some(audio) = audios_iter.next() {
             let params = named_params! {
-                ":playlist_name": playlist_name,
+                ":playlist_id": playlist_id,
                 ":file_hash": audio.file_hash.to_string(),
+                ":position": position,
             };
             statement.execute(params)?;
         } else {
@@ -87,14 +762,49 @@ fn insert_next_batch_of_audios_into_playlist(
 mod test_playlists_operations {
     use crate::audio::AudioFile;
     use crate::database::audio_files::get_audios_by_title;
-    use crate::database::playlists::get_audios_from_playlist;
-    use crate::fixtures::{playlist_db_in_memory, TestInMemoryDBContext};
+    use crate::database::listing::ListOptions;
+    use crate::database::playlists::{
+        create_playlist, delete_playlist, get_audios_from_playlist, get_playlist,
+        get_playlist_items, insert_audios_into_playlist, insert_audios_into_playlist_at,
+        list_playlists, move_playlist_item, remove_playlist_item, rename_playlist,
+        reorder_playlist, set_playlist_cover, set_playlist_description,
+    };
+    use crate::fixtures::{
+        insert_temp_audio, playlist_db_in_memory, temp_audios_context, TestInMemoryDBContext,
+    };
+    use blake3::Hash;
     use rstest::rstest;
+    use std::path::Path;
+    use time::Duration;
+
+    /// Titles of the playlist's audios, in playlist order.
+    fn playlist_titles(context: &mut TestInMemoryDBContext, playlist_name: &str) -> Vec<String> {
+        get_playlist_items(
+            &mut context.connection,
+            playlist_name,
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            assert_eq!(item.position, i);
+            item.audio.unwrap().audio_title
+        })
+        .collect()
+    }
 
     /// Create a fake test database, insert a batch of audios into two playlists, and check it inserted.
     #[rstest]
     fn test_insert_audios_into_playlist(mut playlist_db_in_memory: TestInMemoryDBContext) {
-        let actual_audios = get_audios_by_title(&mut playlist_db_in_memory.connection, "test");
+        let actual_audios = get_audios_by_title(
+            &mut playlist_db_in_memory.connection,
+            "test",
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(actual_audios, playlist_db_in_memory.audios);
     }
 
@@ -102,8 +812,333 @@ mod test_playlists_operations {
     /// and check that they can be retrieved + reconstructed via the playlist name.
     #[rstest]
     fn test_get_audios_from_playlist(mut playlist_db_in_memory: TestInMemoryDBContext) {
-        let playlist_1_audios: Vec<AudioFile> =
-            get_audios_from_playlist(&mut playlist_db_in_memory.connection, "test_playlist_1");
+        let playlist_1_audios: Vec<AudioFile> = get_audios_from_playlist(
+            &mut playlist_db_in_memory.connection,
+            "test_playlist_1",
+            &ListOptions::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(playlist_1_audios, playlist_db_in_memory.audios[0..2]);
     }
+
+    /// Re-adding audios appends them again rather than failing.
+    #[rstest]
+    fn test_insert_duplicate_audios_into_playlist(
+        mut playlist_db_in_memory: TestInMemoryDBContext,
+    ) {
+        let audios = playlist_db_in_memory.audios.clone();
+        insert_audios_into_playlist(
+            &mut playlist_db_in_memory.connection,
+            "test_playlist_1",
+            &audios[0..2],
+        )
+        .unwrap();
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_1"),
+            [
+                "test title 0",
+                "test title 1",
+                "test title 0",
+                "test title 1"
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_insert_audios_into_playlist_at(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let audios = playlist_db_in_memory.audios.clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        insert_audios_into_playlist_at(conn, "test_playlist_1", 1, &audios[1..3]).unwrap();
+        insert_audios_into_playlist_at(conn, "test_playlist_1", 4, &audios[0..1]).unwrap();
+        assert!(insert_audios_into_playlist_at(conn, "test_playlist_1", 6, &audios).is_err());
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_1"),
+            [
+                "test title 0",
+                "test title 1",
+                "test title 2",
+                "test title 1",
+                "test title 0"
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(0, 2, ["test title 1", "test title 2", "test title 0"])]
+    #[case(2, 0, ["test title 2", "test title 0", "test title 1"])]
+    #[case(1, 1, ["test title 0", "test title 1", "test title 2"])]
+    fn test_move_playlist_item(
+        mut playlist_db_in_memory: TestInMemoryDBContext,
+        #[case] from_index: usize,
+        #[case] to_index: usize,
+        #[case] expected_titles: [&str; 3],
+    ) {
+        let audios = playlist_db_in_memory.audios.clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        insert_audios_into_playlist(conn, "test_playlist_3", &audios).unwrap();
+        move_playlist_item(conn, "test_playlist_3", from_index, to_index).unwrap();
+        assert!(move_playlist_item(conn, "test_playlist_3", 0, 3).is_err());
+        assert!(move_playlist_item(conn, "test_playlist_3", 3, 0).is_err());
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_3"),
+            expected_titles
+        );
+    }
+
+    #[rstest]
+    fn test_remove_playlist_item(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let audios = playlist_db_in_memory.audios.clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        insert_audios_into_playlist(conn, "test_playlist_3", &audios).unwrap();
+        remove_playlist_item(conn, "test_playlist_3", 1).unwrap();
+        assert!(remove_playlist_item(conn, "test_playlist_3", 2).is_err());
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_3"),
+            ["test title 0", "test title 2"]
+        );
+    }
+
+    #[rstest]
+    fn test_reorder_playlist(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let audios = playlist_db_in_memory.audios.clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        insert_audios_into_playlist(
+            conn,
+            "test_playlist_3",
+            &[&audios[..], &audios[0..1]].concat(),
+        )
+        .unwrap();
+        let item_ids = get_playlist_items(conn, "test_playlist_3", &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| item.item_id)
+            .collect::<Vec<i64>>();
+        let reordered = [item_ids[3], item_ids[1], item_ids[0], item_ids[2]];
+        let error = reorder_playlist(conn, "test_playlist_3", &reordered[0..3]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "new order of playlist test_playlist_3 must list each of its 4 items once"
+        );
+        assert!(reorder_playlist(conn, "test_playlist_3", &[reordered[0]; 4]).is_err());
+        reorder_playlist(conn, "test_playlist_3", &reordered).unwrap();
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_3"),
+            [
+                "test title 0",
+                "test title 1",
+                "test title 0",
+                "test title 2"
+            ]
+        );
+        let page = get_audios_from_playlist(
+            &mut playlist_db_in_memory.connection,
+            "test_playlist_3",
+            &ListOptions {
+                limit: Some(3),
+                ..ListOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            page.items,
+            [&audios[0], &audios[1], &audios[0]].map(Clone::clone)
+        );
+        assert!(page.next.is_some());
+    }
+
+    /// Pages hold consecutive items, including repeats of an audio.
+    #[rstest]
+    fn test_get_playlist_items_pages(mut temp_audios_context: TestInMemoryDBContext) {
+        for n in 0..3 {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let audios = temp_audios_context.audios.clone();
+        let conn = &mut temp_audios_context.connection;
+        insert_audios_into_playlist(conn, "Mix", &[&audios[..], &audios[0..1]].concat()).unwrap();
+
+        let mut options = ListOptions {
+            limit: Some(3),
+            ..ListOptions::default()
+        };
+        let page = get_playlist_items(conn, "Mix", &options).unwrap();
+        let positions = page.items.iter().map(|item| item.position);
+        assert_eq!(positions.collect::<Vec<usize>>(), [0, 1, 2]);
+        options.after = page.next;
+        let page = get_playlist_items(conn, "Mix", &options).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].audio.as_ref(), Some(&audios[0]));
+        assert_eq!(page.next, None);
+    }
+
+    /// Items of an audio that left the library keep their place, without the audio.
+    #[rstest]
+    fn test_get_playlist_items_of_missing_audio(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let audios = playlist_db_in_memory.audios.clone();
+        let conn = &mut playlist_db_in_memory.connection;
+        insert_audios_into_playlist(conn, "Mix", &audios).unwrap();
+        conn.execute(
+            r"DELETE FROM audios WHERE file_hash = ?1",
+            [audios[1].file_hash.to_string()],
+        )
+        .unwrap();
+
+        let mut options = ListOptions {
+            limit: Some(1),
+            ..ListOptions::default()
+        };
+        let mut items = Vec::new();
+        loop {
+            let page = get_playlist_items(conn, "Mix", &options).unwrap();
+            items.extend(page.items);
+            if page.next.is_none() {
+                break;
+            }
+            options.after = page.next;
+        }
+        let items = items
+            .into_iter()
+            .map(|item| (item.position, item.audio))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            [
+                (0, Some(audios[0].clone())),
+                (1, None),
+                (2, Some(audios[2].clone()))
+            ]
+        );
+        let playable = get_audios_from_playlist(conn, "Mix", &ListOptions::default()).unwrap();
+        assert_eq!(playable.items, [audios[0].clone(), audios[2].clone()]);
+    }
+
+    #[rstest]
+    fn test_list_playlists_with_totals(mut temp_audios_context: TestInMemoryDBContext) {
+        for n in 0..3 {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_length: Duration::seconds(60 + n),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let audios = temp_audios_context.audios.clone();
+        let conn = &mut temp_audios_context.connection;
+        create_playlist(conn, "empty", "Nothing yet").unwrap();
+        insert_audios_into_playlist(conn, "Mix", &[&audios[..], &audios[0..1]].concat()).unwrap();
+
+        let playlists = list_playlists(conn, &ListOptions::default()).unwrap().items;
+        let totals = playlists
+            .iter()
+            .map(|p| {
+                (
+                    p.playlist_name.as_str(),
+                    p.track_count,
+                    p.audio_length.map(Duration::whole_seconds),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            totals,
+            [("empty", Some(0), Some(0)), ("Mix", Some(4), Some(243))]
+        );
+        assert_eq!(playlists[0].description, "Nothing yet");
+        assert!(playlists[1].created_at <= playlists[1].modified_at);
+    }
+
+    #[rstest]
+    fn test_list_playlists_pages(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let conn = &mut playlist_db_in_memory.connection;
+        create_playlist(conn, "A first", "").unwrap();
+        let mut options = ListOptions {
+            limit: Some(2),
+            ..ListOptions::default()
+        };
+        let mut names = Vec::new();
+        loop {
+            let page = list_playlists(conn, &options).unwrap();
+            names.extend(page.items.into_iter().map(|p| p.playlist_name));
+            match page.next {
+                Some(next) => options.after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(names, ["A first", "test_playlist_1", "test_playlist_2"]);
+    }
+
+    #[rstest]
+    fn test_create_existing_playlist_fails(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let conn = &mut playlist_db_in_memory.connection;
+        assert!(create_playlist(conn, "test_playlist_1", "").is_err());
+        assert_eq!(
+            list_playlists(conn, &ListOptions::default())
+                .unwrap()
+                .items
+                .len(),
+            2
+        );
+    }
+
+    #[rstest]
+    fn test_rename_playlist(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let conn = &mut playlist_db_in_memory.connection;
+        assert!(rename_playlist(conn, "test_playlist_1", "test_playlist_2").is_err());
+        assert!(rename_playlist(conn, "missing", "test_playlist_3").is_err());
+        rename_playlist(conn, "test_playlist_1", "test_playlist_3").unwrap();
+        assert_eq!(get_playlist(conn, "test_playlist_1").unwrap(), None);
+        assert_eq!(
+            playlist_titles(&mut playlist_db_in_memory, "test_playlist_3"),
+            ["test title 0", "test title 1"]
+        );
+    }
+
+    #[rstest]
+    fn test_set_playlist_description_and_cover(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let conn = &mut playlist_db_in_memory.connection;
+        let cover = Path::new("/covers/playlist.png");
+        set_playlist_description(conn, "test_playlist_1", "Described").unwrap();
+        set_playlist_cover(conn, "test_playlist_1", Some(cover)).unwrap();
+        let playlist = get_playlist(conn, "test_playlist_1").unwrap().unwrap();
+        assert_eq!(playlist.description, "Described");
+        assert_eq!(playlist.img_path.as_deref(), Some(cover));
+
+        set_playlist_cover(conn, "test_playlist_1", None).unwrap();
+        let playlist = get_playlist(conn, "test_playlist_1").unwrap().unwrap();
+        assert_eq!(playlist.img_path, None);
+        assert!(set_playlist_description(conn, "missing", "").is_err());
+    }
+
+    #[rstest]
+    fn test_delete_playlist(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let conn = &mut playlist_db_in_memory.connection;
+        delete_playlist(conn, "test_playlist_1").unwrap();
+        assert!(delete_playlist(conn, "test_playlist_1").is_err());
+        assert_eq!(get_playlist(conn, "test_playlist_1").unwrap(), None);
+        assert!(
+            get_playlist_items(conn, "test_playlist_1", &ListOptions::default())
+                .unwrap()
+                .items
+                .is_empty()
+        );
+        // Recreating the playlist doesn't bring its old items back.
+        create_playlist(conn, "test_playlist_1", "").unwrap();
+        let playlist = get_playlist(conn, "test_playlist_1").unwrap().unwrap();
+        assert_eq!(playlist.track_count, Some(0));
+        assert_eq!(
+            get_audios_by_title(conn, "test title 0", &ListOptions::default())
+                .unwrap()
+                .items
+                .len(),
+            1
+        );
+    }
 }
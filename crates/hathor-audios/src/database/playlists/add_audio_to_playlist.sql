@@ -1,4 +0,0 @@
-INSERT INTO playlists VALUES (
-    :playlist_name
-    , :file_hash
-);
@@ -0,0 +1,3 @@
+SELECT COUNT(*)
+FROM playlist_items
+WHERE playlist_items.playlist_id = :playlist_id;
@@ -0,0 +1,2 @@
+DELETE FROM playlists
+WHERE playlist_id = :playlist_id;
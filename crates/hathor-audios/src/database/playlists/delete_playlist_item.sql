@@ -0,0 +1,2 @@
+DELETE FROM playlist_items
+WHERE item_id = :item_id;
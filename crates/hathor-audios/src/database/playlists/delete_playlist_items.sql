@@ -0,0 +1,2 @@
+DELETE FROM playlist_items
+WHERE playlist_id = :playlist_id;
@@ -1,22 +1,17 @@
-WITH matching_playlists AS (
-    SELECT playlists.file_hash
-    FROM playlists
-    WHERE playlists.playlist_name = :playlist_name
-)
-
 SELECT
-    audios.file_hash
-    , audios.audio_title
-    , audios.album_name
-    , audios.artist_name
-    , audios.track_num
-    , audios.release_year
-    , audios.audio_length_seconds
-    , audio_files.audio_path
-    , audio_files.img_path
-FROM matching_playlists
-    INNER JOIN audios
-        ON
-            matching_playlists.file_hash = audios.file_hash
-    INNER JOIN audio_files
-        ON matching_playlists.file_hash = audio_files.file_hash;
+    audio_listings.*
+    , playlist_items.item_id
+    , playlist_items.position
+FROM playlists
+    INNER JOIN playlist_items
+        ON playlists.playlist_id = playlist_items.playlist_id
+    INNER JOIN audio_listings
+        ON playlist_items.file_hash = audio_listings.file_hash
+WHERE
+    playlists.playlist_name = ?1
+    -- One row per item, at the first path its audio is stored at.
+    AND audio_listings.audio_file_id = (
+        SELECT MIN(audio_files.rowid)
+        FROM audio_files
+        WHERE audio_files.file_hash = playlist_items.file_hash
+    );
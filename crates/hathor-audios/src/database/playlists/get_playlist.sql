@@ -0,0 +1,23 @@
+SELECT
+    playlists.playlist_id
+    , playlists.playlist_name
+    , playlists.description
+    , playlists.created_at
+    , playlists.modified_at
+    , playlists.img_path
+    , smart_playlists.playlist_id IS NOT NULL AS smart
+    , CASE WHEN smart_playlists.playlist_id IS NULL
+        THEN COUNT(playlist_items.item_id)
+    END AS track_count
+    , CASE WHEN smart_playlists.playlist_id IS NULL
+        THEN IFNULL(SUM(audios.audio_length_seconds), 0)
+    END AS audio_length_seconds
+FROM playlists
+    LEFT JOIN smart_playlists
+        ON playlists.playlist_id = smart_playlists.playlist_id
+    LEFT JOIN playlist_items
+        ON playlists.playlist_id = playlist_items.playlist_id
+    LEFT JOIN audios
+        ON playlist_items.file_hash = audios.file_hash
+WHERE playlists.playlist_name = :playlist_name
+GROUP BY playlists.playlist_id;
@@ -0,0 +1,3 @@
+SELECT playlists.playlist_id
+FROM playlists
+WHERE playlists.playlist_name = :playlist_name;
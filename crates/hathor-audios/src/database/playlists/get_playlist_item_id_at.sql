@@ -0,0 +1,5 @@
+SELECT playlist_items.item_id
+FROM playlist_items
+WHERE
+    playlist_items.playlist_id = :playlist_id
+    AND playlist_items.position = :position;
@@ -0,0 +1,4 @@
+SELECT playlist_items.item_id
+FROM playlist_items
+WHERE playlist_items.playlist_id = :playlist_id
+ORDER BY playlist_items.position;
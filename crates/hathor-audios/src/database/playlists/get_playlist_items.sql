@@ -0,0 +1,14 @@
+SELECT
+    audio_listings.*
+    , playlist_items.item_id
+    , playlist_items.position
+FROM playlist_items
+    -- Items whose audio has left the library are kept, with NULL audio columns.
+    -- One row per item, at the first path its audio is stored at.
+    LEFT JOIN audio_listings
+        ON audio_listings.audio_file_id = (
+            SELECT MIN(audio_files.rowid)
+            FROM audio_files
+            WHERE audio_files.file_hash = playlist_items.file_hash
+        )
+WHERE playlist_items.playlist_id = ?1;
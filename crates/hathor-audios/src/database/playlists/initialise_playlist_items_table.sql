@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS playlist_items (
+    item_id INTEGER PRIMARY KEY
+    , playlist_id INTEGER NOT NULL REFERENCES playlists (playlist_id)
+    , file_hash CHAR(64) NOT NULL
+    -- 0 based and contiguous within a playlist.
+    -- Not unique, so a range of items can be shifted with one UPDATE.
+    , position INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS playlist_items_position
+ON playlist_items (playlist_id, position);
@@ -1,5 +1,9 @@
 CREATE TABLE IF NOT EXISTS playlists (
-    playlist_name VARCHAR(256)
-    , file_hash CHAR(64)
-    , PRIMARY KEY (playlist_name, file_hash)
-) WITHOUT ROWID;
+    playlist_id INTEGER PRIMARY KEY
+    , playlist_name VARCHAR(256) NOT NULL UNIQUE
+    , description TEXT NOT NULL DEFAULT ''
+    , created_at INT(64) NOT NULL -- Unix seconds.
+    , modified_at INT(64) NOT NULL -- Unix seconds, updated when the items change too.
+    , img_path BLOB -- Raw OS path bytes of the cover, no length limit.
+    , img_path_display TEXT -- Lossy UTF-8 form of img_path, display only.
+);
@@ -0,0 +1,2 @@
+INSERT INTO playlists (playlist_name, description, created_at, modified_at)
+VALUES (:playlist_name, :description, UNIXEPOCH(), UNIXEPOCH());
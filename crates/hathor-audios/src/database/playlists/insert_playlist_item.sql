@@ -0,0 +1,2 @@
+INSERT INTO playlist_items (playlist_id, file_hash, position)
+VALUES (:playlist_id, :file_hash, :position);
@@ -0,0 +1,5 @@
+UPDATE playlists
+SET
+    playlist_name = :new_playlist_name
+    , modified_at = UNIXEPOCH()
+WHERE playlist_id = :playlist_id;
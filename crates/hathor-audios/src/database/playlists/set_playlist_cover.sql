@@ -0,0 +1,6 @@
+UPDATE playlists
+SET
+    img_path = :img_path
+    , img_path_display = :img_path_display
+    , modified_at = UNIXEPOCH()
+WHERE playlist_id = :playlist_id;
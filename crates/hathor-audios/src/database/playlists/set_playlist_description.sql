@@ -0,0 +1,5 @@
+UPDATE playlists
+SET
+    description = :description
+    , modified_at = UNIXEPOCH()
+WHERE playlist_id = :playlist_id;
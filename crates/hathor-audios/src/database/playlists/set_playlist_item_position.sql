@@ -0,0 +1,3 @@
+UPDATE playlist_items
+SET position = :position
+WHERE item_id = :item_id;
@@ -0,0 +1,5 @@
+UPDATE playlist_items
+SET position = position + :shift
+WHERE
+    playlist_id = :playlist_id
+    AND position BETWEEN :first_position AND :last_position;
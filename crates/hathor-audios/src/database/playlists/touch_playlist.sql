@@ -0,0 +1,3 @@
+UPDATE playlists
+SET modified_at = UNIXEPOCH()
+WHERE playlist_id = :playlist_id;
@@ -0,0 +1,334 @@
+//! The history of what was played, written by the playback thread.
+//!
+//! Play counts, skip counts and last played times are also columns of library listings,
+//! see [Field::Plays](super::query::Field::Plays) and the fields after it.
+
+use crate::audio::AudioFile;
+use crate::database::audio_select_result_to_audiofile;
+use blake3::Hash;
+use rusqlite::types::Type;
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+use std::error::Error;
+use time::{Duration, OffsetDateTime};
+
+/// One play of an audio.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Play {
+    pub file_hash: Hash,
+    pub started_at: OffsetDateTime,
+    /// How much of the audio was heard, parts seeked over aren't counted.
+    pub listened: Duration,
+    pub outcome: PlayOutcome,
+    pub source: PlaySource,
+}
+
+/// How a play ended.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum PlayOutcome {
+    /// Played to the end of the audio.
+    Completed,
+    /// Another audio was played before the end.
+    Skipped,
+}
+
+/// What an audio was played from.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub enum PlaySource {
+    /// Picked from the library directly, e.g. a search result.
+    #[default]
+    Library,
+    Album(String),
+    Playlist(String),
+    Queue,
+}
+
+/// Totals of the plays of one audio.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct PlayStats {
+    /// Plays that reached the end.
+    pub play_count: u32,
+    /// Plays skipped before the end.
+    pub skip_count: u32,
+    /// Start of the latest play, None if never played.
+    pub last_played: Option<OffsetDateTime>,
+}
+
+/// A play in the history along with the audio played.
+#[derive(PartialEq, Debug, Clone)]
+pub struct HistoryEntry {
+    pub play_id: i64,
+    pub play: Play,
+    pub audio: AudioFile,
+}
+
+/// Adds a play to the history.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `play` - The finished play.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::plays::{record_play, Play, PlayOutcome, PlaySource};
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+/// use time::{Duration, OffsetDateTime};
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let play = Play {
+///     file_hash: Hash::from_hex(format!("{:064}", 0)).unwrap(),
+///     started_at: OffsetDateTime::now_utc(),
+///     listened: Duration::seconds(215),
+///     outcome: PlayOutcome::Completed,
+///     source: PlaySource::Album(String::from("Dummy")),
+/// };
+/// record_play(&mut conn, &play);
+pub fn record_play(conn: &mut Connection, play: &Play) -> Result<(), Box<dyn Error>> {
+    let (source_kind, source_name) = match &play.source {
+        PlaySource::Library => ("library", None),
+        PlaySource::Album(name) => ("album", Some(name)),
+        PlaySource::Playlist(name) => ("playlist", Some(name)),
+        PlaySource::Queue => ("queue", None),
+    };
+    conn.execute(
+        include_str!("plays/insert_play.sql"),
+        named_params! {
+            ":file_hash": play.file_hash.to_string(),
+            ":started_at": play.started_at.unix_timestamp(),
+            ":listened_ms": play.listened.whole_milliseconds() as i64,
+            ":completed": play.outcome == PlayOutcome::Completed,
+            ":source_kind": source_kind,
+            ":source_name": source_name,
+        },
+    )?;
+    Ok(())
+}
+
+/// Retvieve the play count, skip count and last played time of an audio.
+/// Audios never played have all zero counts.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `hash` - Hash of the audio.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::plays::get_play_stats;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// let play_count = get_play_stats(&mut conn, &hash).unwrap().play_count;
+pub fn get_play_stats(conn: &mut Connection, hash: &Hash) -> Result<PlayStats, Box<dyn Error>> {
+    let stats = conn
+        .query_row(
+            include_str!("plays/get_play_stats.sql"),
+            named_params! { ":file_hash": hash.to_string() },
+            |row| {
+                Ok(PlayStats {
+                    play_count: row.get("play_count")?,
+                    skip_count: row.get("skip_count")?,
+                    last_played: Some(timestamp(row, "last_played")?),
+                })
+            },
+        )
+        .optional()?;
+    Ok(stats.unwrap_or_default())
+}
+
+/// Retvieve the latest plays, newest first.
+/// Audios played several times appear once per play.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `limit` - Most plays to return.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::plays::get_recently_played;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// for entry in get_recently_played(&mut conn, 20).unwrap() {
+///     println!("{} at {}", entry.audio.audio_title, entry.play.started_at);
+/// }
+pub fn get_recently_played(
+    conn: &mut Connection,
+    limit: u32,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    let mut statement = conn.prepare(include_str!("plays/get_recently_played.sql"))?;
+    let entries = statement
+        .query_map(
+            named_params! { ":limit": limit },
+            play_select_result_to_history_entry,
+        )?
+        .collect::<Result<Vec<HistoryEntry>, rusqlite::Error>>()?;
+    Ok(entries)
+}
+
+fn play_select_result_to_history_entry(row: &Row) -> Result<HistoryEntry, rusqlite::Error> {
+    let audio = audio_select_result_to_audiofile(row)?;
+    let source_name = row.get::<&str, Option<String>>("source_name")?;
+    let source = match (
+        row.get::<&str, String>("source_kind")?.as_str(),
+        source_name,
+    ) {
+        ("album", Some(name)) => PlaySource::Album(name),
+        ("playlist", Some(name)) => PlaySource::Playlist(name),
+        ("queue", _) => PlaySource::Queue,
+        _ => PlaySource::Library,
+    };
+    let outcome = if row.get("completed")? {
+        PlayOutcome::Completed
+    } else {
+        PlayOutcome::Skipped
+    };
+    Ok(HistoryEntry {
+        play_id: row.get("play_id")?,
+        play: Play {
+            file_hash: audio.file_hash,
+            started_at: timestamp(row, "started_at")?,
+            listened: Duration::milliseconds(row.get("listened_ms")?),
+            outcome,
+            source,
+        },
+        audio,
+    })
+}
+
+fn timestamp(row: &Row, column: &str) -> Result<OffsetDateTime, rusqlite::Error> {
+    OffsetDateTime::from_unix_timestamp(row.get(column)?)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Integer, Box::new(e)))
+}
+
+#[cfg(test)]
+mod test_plays_operations {
+    use super::{
+        get_play_stats, get_recently_played, record_play, Play, PlayOutcome, PlaySource, PlayStats,
+    };
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::database::query::{get_audios_by_query, Query};
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+    use time::{Duration, OffsetDateTime};
+
+    /// Three audios, the first played twice and skipped once, the second skipped once.
+    #[fixture]
+    fn history(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        for (n, title) in ["Roads", "Sour Times", "Glory Box"].into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let hashes = temp_audios_context
+            .audios
+            .iter()
+            .map(|audio| audio.file_hash)
+            .collect::<Vec<Hash>>();
+        let plays = [
+            (hashes[0], 100, PlayOutcome::Completed, PlaySource::Library),
+            (hashes[1], 200, PlayOutcome::Skipped, PlaySource::Queue),
+            (
+                hashes[0],
+                300,
+                PlayOutcome::Skipped,
+                PlaySource::Album(String::from("Dummy")),
+            ),
+            (
+                hashes[0],
+                400,
+                PlayOutcome::Completed,
+                PlaySource::Playlist(String::from("Bristol")),
+            ),
+        ];
+        for (file_hash, started_at, outcome, source) in plays {
+            let play = Play {
+                file_hash,
+                started_at: OffsetDateTime::from_unix_timestamp(started_at).unwrap(),
+                listened: Duration::milliseconds(started_at * 10 + 1),
+                outcome,
+                source,
+            };
+            record_play(&mut temp_audios_context.connection, &play).unwrap();
+        }
+        temp_audios_context
+    }
+
+    #[rstest]
+    #[case(0, 2, 1, Some(400))]
+    #[case(1, 0, 1, Some(200))]
+    #[case(2, 0, 0, None)]
+    fn test_get_play_stats(
+        mut history: TestInMemoryDBContext,
+        #[case] audio: usize,
+        #[case] play_count: u32,
+        #[case] skip_count: u32,
+        #[case] last_played: Option<i64>,
+    ) {
+        let hash = history.audios[audio].file_hash;
+        assert_eq!(
+            get_play_stats(&mut history.connection, &hash).unwrap(),
+            PlayStats {
+                play_count,
+                skip_count,
+                last_played: last_played
+                    .map(|seconds| OffsetDateTime::from_unix_timestamp(seconds).unwrap()),
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_get_recently_played(mut history: TestInMemoryDBContext) {
+        let entries = get_recently_played(&mut history.connection, 3).unwrap();
+        let titles = entries
+            .iter()
+            .map(|entry| entry.audio.audio_title.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, ["Roads", "Roads", "Sour Times"]);
+        let latest = &entries[0].play;
+        assert_eq!(latest.source, PlaySource::Playlist(String::from("Bristol")));
+        assert_eq!(latest.outcome, PlayOutcome::Completed);
+        assert_eq!(latest.listened, Duration::milliseconds(4001));
+        assert_eq!(latest.file_hash, history.audios[0].file_hash);
+        assert_eq!(
+            entries[1].play.source,
+            PlaySource::Album(String::from("Dummy"))
+        );
+        assert_eq!(entries[2].play.source, PlaySource::Queue);
+    }
+
+    #[rstest]
+    #[case("sort:-plays,title", vec!["Roads", "Glory Box", "Sour Times"])]
+    #[case("sort:-skips,title", vec!["Roads", "Sour Times", "Glory Box"])]
+    #[case("sort:played", vec!["Glory Box", "Sour Times", "Roads"])]
+    #[case("skips:1 sort:-played", vec!["Roads", "Sour Times"])]
+    fn test_query_by_play_stats(
+        mut history: TestInMemoryDBContext,
+        #[case] query: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        let query = Query::parse(query).unwrap();
+        let audios = get_audios_by_query(&mut history.connection, &query, &ListOptions::default())
+            .unwrap()
+            .items;
+        let titles = audios
+            .iter()
+            .map(|audio| audio.audio_title.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, expected);
+    }
+}
@@ -0,0 +1,6 @@
+SELECT
+    play_stats.play_count
+    , play_stats.skip_count
+    , play_stats.last_played
+FROM play_stats
+WHERE play_stats.file_hash = :file_hash;
@@ -0,0 +1,15 @@
+SELECT
+    audio_listings.*
+    , plays.play_id
+    , plays.started_at
+    , plays.listened_ms
+    , plays.completed
+    , plays.source_kind
+    , plays.source_name
+FROM plays
+    INNER JOIN audio_listings
+        ON plays.file_hash = audio_listings.file_hash
+-- One row per play, even if its audio is stored at several paths.
+GROUP BY plays.play_id
+ORDER BY plays.started_at DESC, plays.play_id DESC
+LIMIT :limit;
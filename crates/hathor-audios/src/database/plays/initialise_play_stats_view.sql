@@ -0,0 +1,13 @@
+-- Recreated on every connect, so the view always matches the tables it reads.
+DROP VIEW IF EXISTS play_stats;
+
+-- Totals of the plays of each audio that has any.
+-- Only plays that reached the end count as plays, the rest as skips.
+CREATE VIEW play_stats AS
+SELECT
+    plays.file_hash
+    , SUM(plays.completed) AS play_count
+    , SUM(NOT plays.completed) AS skip_count
+    , MAX(plays.started_at) AS last_played
+FROM plays
+GROUP BY plays.file_hash;
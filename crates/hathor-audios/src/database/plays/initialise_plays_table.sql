@@ -0,0 +1,16 @@
+-- One row per time an audio was played, written by the playback thread.
+CREATE TABLE IF NOT EXISTS plays (
+    play_id INTEGER PRIMARY KEY
+    , file_hash CHAR(64) NOT NULL
+    , started_at INT(64) NOT NULL -- Unix seconds.
+    , listened_ms INT(64) NOT NULL -- Audio actually heard, seeked over parts excluded.
+    , completed BOOLEAN NOT NULL -- False when skipped before the end.
+    , source_kind VARCHAR(16) NOT NULL -- library, album, playlist or queue.
+    , source_name VARCHAR(256) -- The album or playlist played from.
+);
+
+CREATE INDEX IF NOT EXISTS plays_by_audio
+ON plays (file_hash, started_at);
+
+CREATE INDEX IF NOT EXISTS plays_by_time
+ON plays (started_at);
@@ -0,0 +1,15 @@
+INSERT INTO plays (
+    file_hash
+    , started_at
+    , listened_ms
+    , completed
+    , source_kind
+    , source_name
+) VALUES (
+    :file_hash
+    , :started_at
+    , :listened_ms
+    , :completed
+    , :source_kind
+    , :source_name
+);
@@ -0,0 +1,469 @@
+mod parser;
+
+pub use parser::QueryParseError;
+
+use crate::database::listing::{query_page, ListOptions, Page};
+use crate::database::unicode_folding::fold_text;
+use crate::database::user_data::Rating;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use std::error::Error;
+use std::str::FromStr;
+
+/// A parsed library query, e.g.
+/// `artist:"Boards of Canada" year:1995..2002 -title:remix length:>5m sort:year`.
+///
+/// Syntax:
+/// * `field:value` matches text fields containing the value, `field:=value` matches them exactly.
+/// * Number and length fields take `field:value`, `field:>value` (also `>=`, `<`, `<=`, `=`)
+///   or inclusive ranges `field:low..high`, where either end may be left open.
+/// * Lengths are written as `90`, `90s`, `5m`, `1h2m3s` or `3:30`.
+/// * Ratings are written in stars from 0 to 5, in steps of a half, e.g. `rating:>=3.5`.
+///   Unrated audios match no rating, so `-rating:<3` finds them with the well rated.
+/// * Words without a field match the title, album or artist.
+/// * `"quoted values"` may contain spaces, `-` before a term negates it.
+/// * Terms are all required, `OR` between terms makes either side enough.
+/// * `sort:field` orders the results, `sort:-field` reverses the order.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Query {
+    pub filter: Option<Expr>,
+    pub sort: Vec<SortTerm>,
+}
+
+/// A boolean filter over audios.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Expr {
+    Condition(Condition),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+/// A single test of one field, e.g. `year:>=1995`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Condition {
+    pub field: Field,
+    pub comparison: Comparison,
+}
+
+/// How a field is compared against a value.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Comparison {
+    /// Case and diacritic insensitive substring match, text fields only.
+    Contains(String),
+    Equals(Value),
+    GreaterThan(Value),
+    AtLeast(Value),
+    LessThan(Value),
+    AtMost(Value),
+    /// Inclusive range.
+    Between(Value, Value),
+}
+
+/// A value compared against a field. Lengths are whole seconds.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Rating(Rating),
+}
+
+/// A field of an audio that can be filtered or sorted on.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Field {
+    /// Title, album or artist, used for words without a field.
+    Any,
+    Title,
+    Album,
+    Artist,
+    AlbumArtist,
+    Genre,
+    Composer,
+    Lyrics,
+    Year,
+    Track,
+    Disc,
+    Length,
+    /// When the audio was first added, in Unix seconds.
+    Added,
+    /// Plays that reached the end.
+    Plays,
+    /// Plays skipped before the end.
+    Skips,
+    /// When the audio was last played, in Unix seconds, 0 if never.
+    LastPlayed,
+    /// The user's rating in stars, unrated audios sort below 0 stars.
+    /// Comparisons only match rated audios, so negating one keeps the unrated.
+    Rating,
+    /// 1 for the user's favourites, 0 for the rest.
+    Favourite,
+    /// The user's tags, filters match audios with any tag that matches.
+    Tag,
+}
+
+/// The type of values a [Field] holds.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum FieldKind {
+    Text,
+    Integer,
+    /// Seconds, written with units in queries.
+    Duration,
+    /// A [Rating], written in stars in queries.
+    Stars,
+}
+
+/// One key of the result order.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct SortTerm {
+    pub field: Field,
+    pub descending: bool,
+}
+
+const NAMED_FIELDS: &[Field] = &[
+    Field::Title,
+    Field::Album,
+    Field::Artist,
+    Field::AlbumArtist,
+    Field::Genre,
+    Field::Composer,
+    Field::Lyrics,
+    Field::Year,
+    Field::Track,
+    Field::Disc,
+    Field::Length,
+    Field::Added,
+    Field::Plays,
+    Field::Skips,
+    Field::LastPlayed,
+    Field::Rating,
+    Field::Favourite,
+    Field::Tag,
+];
+
+impl Field {
+    /// Looks a field up by the name used in queries.
+    pub fn from_name(name: &str) -> Option<Field> {
+        NAMED_FIELDS
+            .iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// The name used for this field in queries.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Any => "any",
+            Field::Title => "title",
+            Field::Album => "album",
+            Field::Artist => "artist",
+            Field::AlbumArtist => "albumartist",
+            Field::Genre => "genre",
+            Field::Composer => "composer",
+            Field::Lyrics => "lyrics",
+            Field::Year => "year",
+            Field::Track => "track",
+            Field::Disc => "disc",
+            Field::Length => "length",
+            Field::Added => "added",
+            Field::Plays => "plays",
+            Field::Skips => "skips",
+            Field::LastPlayed => "played",
+            Field::Rating => "rating",
+            Field::Favourite => "favourite",
+            Field::Tag => "tag",
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Field::Year
+            | Field::Track
+            | Field::Disc
+            | Field::Added
+            | Field::Plays
+            | Field::Skips
+            | Field::LastPlayed
+            | Field::Favourite => FieldKind::Integer,
+            Field::Length => FieldKind::Duration,
+            Field::Rating => FieldKind::Stars,
+            _ => FieldKind::Text,
+        }
+    }
+
+    /// Column of `audio_listings` this field is sorted on.
+    pub(crate) fn sort_column(&self) -> &'static str {
+        self.columns()[0]
+    }
+
+    /// Columns of `audio_listings` this field is read from.
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Field::Any => &["audio_title", "album_name", "artist_name"],
+            Field::Title => &["audio_title"],
+            Field::Album => &["album_name"],
+            Field::Artist => &["artist_name"],
+            Field::AlbumArtist => &["album_artist_name"],
+            Field::Genre => &["genre"],
+            Field::Composer => &["composer"],
+            Field::Lyrics => &["lyrics"],
+            Field::Year => &["release_year"],
+            Field::Track => &["track_num"],
+            Field::Disc => &["disc_num"],
+            Field::Length => &["audio_length_seconds"],
+            Field::Added => &["date_added"],
+            Field::Plays => &["play_count"],
+            Field::Skips => &["skip_count"],
+            Field::LastPlayed => &["last_played"],
+            Field::Rating => &["rating"],
+            Field::Favourite => &["favourite"],
+            // Sorted on the joined tags, filtered on each tag, see Condition::to_sql.
+            Field::Tag => &["user_tags"],
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parser::parse(s)
+    }
+}
+
+impl Query {
+    /// Parses a query, see [Query] for the syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hathor_audios::database::query::Query;
+    ///
+    /// let query = Query::parse(r#"artist:"Boards of Canada" year:1995..2002 sort:year"#).unwrap();
+    /// assert!(Query::parse("year:nineteen").is_err());
+    pub fn parse(input: &str) -> Result<Query, QueryParseError> {
+        parser::parse(input)
+    }
+
+    /// Compiles the query's filter into a select over `audio_listings`, and the parameters it binds.
+    /// The sort is left to [query_page](super::listing::query_page).
+    pub(crate) fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        let mut params = Vec::new();
+        let mut sql = String::from("SELECT audio_listings.* FROM audio_listings");
+        if let Some(filter) = &self.filter {
+            sql += " WHERE ";
+            sql += &filter.to_sql(&mut params);
+        }
+        (sql, params)
+    }
+}
+
+impl Expr {
+    /// Compiles the expression into a SQL boolean, pushing its parameters onto `params`.
+    pub(crate) fn to_sql(&self, params: &mut Vec<SqlValue>) -> String {
+        match self {
+            Expr::Condition(condition) => condition.to_sql(params),
+            Expr::Not(expr) => format!("NOT ({})", expr.to_sql(params)),
+            Expr::And(exprs) => join_sql(exprs, " AND ", params),
+            Expr::Or(exprs) => join_sql(exprs, " OR ", params),
+        }
+    }
+}
+
+impl Condition {
+    fn to_sql(&self, params: &mut Vec<SqlValue>) -> String {
+        // Placeholders are numbered so fields over several columns bind their value once.
+        let n = params.len() + 1;
+        let operator = match &self.comparison {
+            Comparison::Contains(text) => {
+                params.push(SqlValue::Text(format!(
+                    "%{}%",
+                    escape_like(&fold_text(text))
+                )));
+                format!("LIKE ?{} ESCAPE '\\'", n)
+            }
+            Comparison::Equals(Value::Text(text)) => {
+                params.push(SqlValue::Text(escape_like(&fold_text(text))));
+                format!("LIKE ?{} ESCAPE '\\'", n)
+            }
+            Comparison::Equals(value) => {
+                params.push(value.into());
+                format!("= ?{}", n)
+            }
+            Comparison::GreaterThan(value) => {
+                params.push(value.into());
+                format!("> ?{}", n)
+            }
+            Comparison::AtLeast(value) => {
+                params.push(value.into());
+                format!(">= ?{}", n)
+            }
+            Comparison::LessThan(value) => {
+                params.push(value.into());
+                format!("< ?{}", n)
+            }
+            Comparison::AtMost(value) => {
+                params.push(value.into());
+                format!("<= ?{}", n)
+            }
+            Comparison::Between(low, high) => {
+                params.push(low.into());
+                params.push(high.into());
+                format!("BETWEEN ?{} AND ?{}", n, n + 1)
+            }
+        };
+        if self.field == Field::Tag {
+            return format!(
+                "EXISTS (SELECT 1 FROM audio_tags WHERE audio_tags.file_hash = \
+                 audio_listings.file_hash AND FOLD_TEXT(audio_tags.tag) {})",
+                operator
+            );
+        }
+        if self.field == Field::Rating {
+            // Unrated audios are stored as -1, below every rating.
+            return format!("(rating >= 0 AND rating {})", operator);
+        }
+        let tests = self
+            .field
+            .columns()
+            .iter()
+            .map(|column| match self.field.kind() {
+                FieldKind::Text => format!("FOLD_TEXT({}) {}", column, operator),
+                _ => format!("{} {}", column, operator),
+            })
+            .collect::<Vec<String>>();
+        format!("({})", tests.join(" OR "))
+    }
+}
+
+impl From<&Value> for SqlValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Text(text) => SqlValue::Text(text.clone()),
+            Value::Integer(n) => SqlValue::Integer(*n),
+            // Ratings are stored as half stars.
+            Value::Rating(rating) => SqlValue::Integer(rating.half_stars().into()),
+        }
+    }
+}
+
+/// Retrieve a page of audios matching a [Query].
+/// The query's own sort keys come first, then those in `options`.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `query` - The parsed query to run.
+/// * `options` - Sort, page size and cursor, see [ListOptions].
+///
+/// # Examples
+///
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::query::{get_audios_by_query, Query};
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let query = Query::parse("genre:ambient length:>5m -title:remix sort:-year").unwrap();
+/// let page = get_audios_by_query(&mut conn, &query, &ListOptions::default());
+pub fn get_audios_by_query(
+    conn: &mut Connection,
+    query: &Query,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    let (sql, params) = query.to_sql();
+    let options = ListOptions {
+        sort: query.sort.iter().chain(&options.sort).copied().collect(),
+        ..options.clone()
+    };
+    query_page(conn, &sql, params, &options, &[])
+}
+
+fn join_sql(exprs: &[Expr], separator: &str, params: &mut Vec<SqlValue>) -> String {
+    let parts = exprs
+        .iter()
+        .map(|e| e.to_sql(params))
+        .collect::<Vec<String>>();
+    format!("({})", parts.join(separator))
+}
+
+/// Escapes LIKE wildcards so user text is matched literally.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod test_query_operations {
+    use super::{get_audios_by_query, Query};
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+    use time::Duration;
+
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        let tracks = [
+            ("Roygbiv", "Boards of Canada", "ambient", 1998, 151),
+            ("Dayvan Cowboy", "Boards of Canada", "ambient", 2005, 300),
+            ("Aquarius (Remix)", "Boards of Canada", "ambient", 1998, 360),
+            ("Olson", "Boards of Canada", "ambient", 1998, 91),
+            ("Windowlicker", "Aphex Twin", "electronic", 1999, 367),
+            ("50%_Off", "Aphex Twin", "electronic", 2001, 30),
+            ("Jóga", "Björk", "art pop", 1997, 305),
+        ];
+        for (n, (title, artist, genre, year, seconds)) in tracks.into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    artist_name: String::from(artist),
+                    genre: String::from(genre),
+                    release_year: year,
+                    audio_length: Duration::seconds(seconds),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        temp_audios_context
+    }
+
+    #[rstest]
+    #[case(r#"artist:"boards of canada" year:1995..2002 genre:ambient -title:remix length:>2m"#, vec!["Roygbiv"])]
+    #[case("artist:aphex OR year:2005 sort:-year", vec!["Dayvan Cowboy", "50%_Off", "Windowlicker"])]
+    #[case("year:..1998 length:<=2:31 sort:length", vec!["Olson", "Roygbiv"])]
+    #[case("title:=olson", vec!["Olson"])]
+    #[case("artist:BJORK title:=joga", vec!["Jóga"])]
+    #[case("title:=ols", vec![])]
+    #[case("50%", vec!["50%_Off"])]
+    #[case("o_s", vec![])]
+    #[case("sort:-title genre:electronic", vec!["Windowlicker", "50%_Off"])]
+    fn test_get_audios_by_query(
+        mut library: TestInMemoryDBContext,
+        #[case] query: &str,
+        #[case] expected_titles: Vec<&str>,
+    ) {
+        let query = Query::parse(query).unwrap();
+        let titles = get_audios_by_query(&mut library.connection, &query, &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|a| a.audio_title)
+            .collect::<Vec<String>>();
+        assert_eq!(titles, expected_titles);
+    }
+
+    #[rstest]
+    fn test_get_audios_by_empty_query_returns_all(mut library: TestInMemoryDBContext) {
+        let page = get_audios_by_query(
+            &mut library.connection,
+            &Query::default(),
+            &ListOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(page.items.len(), library.audios.len());
+    }
+}
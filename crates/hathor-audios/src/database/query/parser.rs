@@ -0,0 +1,587 @@
+use super::{Comparison, Condition, Expr, Field, FieldKind, Query, SortTerm, Value};
+use crate::database::user_data::Rating;
+use std::error::Error;
+use std::fmt;
+
+/// A query that could not be parsed.
+/// `position` is the byte offset into the query where the problem starts.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QueryParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl Error for QueryParseError {}
+
+enum Term {
+    Filter(Expr),
+    Sort(Vec<SortTerm>),
+    Or,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+pub(super) fn parse(input: &str) -> Result<Query, QueryParseError> {
+    let mut parser = Parser { input, position: 0 };
+    // Terms are ANDed together within a group, and groups are split by OR.
+    let mut groups: Vec<Vec<Expr>> = vec![Vec::new()];
+    let mut sort = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        let term_position = parser.position;
+        match parser.next_term()? {
+            None => break,
+            Some(Term::Filter(expr)) => groups.last_mut().unwrap().push(expr),
+            Some(Term::Sort(terms)) => sort.extend(terms),
+            Some(Term::Or) => {
+                if groups.last().unwrap().is_empty() {
+                    return Err(error(term_position, "expected a term before `OR`"));
+                }
+                groups.push(Vec::new());
+            }
+        }
+    }
+    if groups.len() > 1 && groups.last().unwrap().is_empty() {
+        return Err(error(input.len(), "expected a term after `OR`"));
+    }
+    let mut groups = groups
+        .into_iter()
+        .filter(|g| !g.is_empty())
+        .map(|mut g| {
+            if g.len() == 1 {
+                g.remove(0)
+            } else {
+                Expr::And(g)
+            }
+        })
+        .collect::<Vec<Expr>>();
+    let filter = match groups.len() {
+        0 => None,
+        1 => groups.pop(),
+        _ => Some(Expr::Or(groups)),
+    };
+    Ok(Query { filter, sort })
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.input[start..self.position]
+    }
+
+    /// Reads a `"quoted value"`, starting at the opening quote.
+    fn quoted(&mut self) -> Result<String, QueryParseError> {
+        let start = self.position;
+        self.bump();
+        let text = self.take_while(|c| c != '"');
+        if self.peek() != Some('"') {
+            return Err(error(start, "unterminated quote"));
+        }
+        self.bump();
+        Ok(text.to_string())
+    }
+
+    fn next_term(&mut self) -> Result<Option<Term>, QueryParseError> {
+        let Some(first) = self.peek() else {
+            return Ok(None);
+        };
+        let term_start = self.position;
+        let negated = first == '-';
+        if negated {
+            self.bump();
+            if self.peek().is_none_or(char::is_whitespace) {
+                return Err(error(term_start, "expected a term after `-`"));
+            }
+        }
+
+        if self.peek() == Some('"') {
+            let text = self.quoted()?;
+            self.expect_term_end()?;
+            return Ok(Some(filter(negated, any_contains(text))));
+        }
+
+        let word_start = self.position;
+        let word = self.take_while(|c| !c.is_whitespace() && c != ':' && c != '"');
+        if self.peek() == Some('"') {
+            return Err(error(self.position, "unexpected quote inside a word"));
+        }
+        if self.peek() != Some(':') {
+            if word == "OR" && !negated {
+                return Ok(Some(Term::Or));
+            }
+            return Ok(Some(filter(negated, any_contains(word.to_string()))));
+        }
+        self.bump();
+
+        if word.eq_ignore_ascii_case("sort") {
+            if negated {
+                return Err(error(term_start, "`sort` can't be negated"));
+            }
+            return Ok(Some(Term::Sort(self.sort_terms()?)));
+        }
+        let field = Field::from_name(word).ok_or_else(|| {
+            error(
+                word_start,
+                &format!(
+                    "unknown field `{}`, quote the term to search for it as text",
+                    word
+                ),
+            )
+        })?;
+
+        let operator_start = self.position;
+        let operator = self.take_while(|c| matches!(c, '<' | '>' | '='));
+        let value_start = self.position;
+        let (value, quoted) = if self.peek() == Some('"') {
+            (self.quoted()?, true)
+        } else {
+            (self.take_while(|c| !c.is_whitespace()).to_string(), false)
+        };
+        self.expect_term_end()?;
+        if value.is_empty() {
+            return Err(error(
+                value_start,
+                &format!("expected a value for `{}`", field.name()),
+            ));
+        }
+        let comparison = parse_comparison(
+            field,
+            Operator {
+                text: operator,
+                position: operator_start,
+            },
+            &value,
+            quoted,
+            value_start,
+        )?;
+        Ok(Some(filter(
+            negated,
+            Expr::Condition(Condition { field, comparison }),
+        )))
+    }
+
+    /// Reads `field` or `-field`, comma separated.
+    fn sort_terms(&mut self) -> Result<Vec<SortTerm>, QueryParseError> {
+        let start = self.position;
+        let value = self.take_while(|c| !c.is_whitespace());
+        if value.is_empty() {
+            return Err(error(start, "expected a field to sort by"));
+        }
+        let mut offset = start;
+        let mut terms = Vec::new();
+        for name in value.split(',') {
+            let descending = name.starts_with('-');
+            let field_name = name.trim_start_matches('-');
+            let field = Field::from_name(field_name).ok_or_else(|| {
+                error(
+                    offset,
+                    &format!("unknown field `{}` to sort by", field_name),
+                )
+            })?;
+            terms.push(SortTerm { field, descending });
+            offset += name.len() + 1;
+        }
+        Ok(terms)
+    }
+
+    fn expect_term_end(&self) -> Result<(), QueryParseError> {
+        match self.peek() {
+            Some(c) if !c.is_whitespace() => Err(error(
+                self.position,
+                "expected a space after the quoted value",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Operator<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+fn parse_comparison(
+    field: Field,
+    operator: Operator,
+    value: &str,
+    quoted: bool,
+    value_position: usize,
+) -> Result<Comparison, QueryParseError> {
+    if field.kind() == FieldKind::Text {
+        return match operator.text {
+            "" => Ok(Comparison::Contains(value.to_string())),
+            "=" => Ok(Comparison::Equals(Value::Text(value.to_string()))),
+            _ => Err(error(
+                operator.position,
+                &format!(
+                    "`{}` can't be used on text field `{}`, only `=`",
+                    operator.text,
+                    field.name()
+                ),
+            )),
+        };
+    }
+
+    if let (true, false, Some((low, high))) =
+        (operator.text.is_empty(), quoted, value.split_once(".."))
+    {
+        let high_position = value_position + low.len() + 2;
+        let low_value = optional_number(field, low, value_position)?;
+        let high_value = optional_number(field, high, high_position)?;
+        return match (low_value, high_value) {
+            (Some(low_value), Some(high_value)) if is_after(&low_value, &high_value) => Err(error(
+                value_position,
+                &format!("range start {} is after its end {}", low, high),
+            )),
+            (Some(low), Some(high)) => Ok(Comparison::Between(low, high)),
+            (Some(low), None) => Ok(Comparison::AtLeast(low)),
+            (None, Some(high)) => Ok(Comparison::AtMost(high)),
+            (None, None) => Err(error(value_position, "range needs a start or an end")),
+        };
+    }
+
+    let number = parse_number(field, value, value_position)?;
+    match operator.text {
+        "" | "=" => Ok(Comparison::Equals(number)),
+        ">" => Ok(Comparison::GreaterThan(number)),
+        ">=" => Ok(Comparison::AtLeast(number)),
+        "<" => Ok(Comparison::LessThan(number)),
+        "<=" => Ok(Comparison::AtMost(number)),
+        _ => Err(error(
+            operator.position,
+            &format!("unknown operator `{}`", operator.text),
+        )),
+    }
+}
+
+fn optional_number(
+    field: Field,
+    value: &str,
+    position: usize,
+) -> Result<Option<Value>, QueryParseError> {
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        parse_number(field, value, position).map(Some)
+    }
+}
+
+fn is_after(low: &Value, high: &Value) -> bool {
+    match (low, high) {
+        (Value::Integer(low), Value::Integer(high)) => low > high,
+        (Value::Rating(low), Value::Rating(high)) => low > high,
+        _ => false,
+    }
+}
+
+fn parse_number(field: Field, value: &str, position: usize) -> Result<Value, QueryParseError> {
+    let parsed = match field.kind() {
+        FieldKind::Duration => parse_duration_seconds(value).map(Value::Integer),
+        FieldKind::Stars => parse_stars(value).map(Value::Rating),
+        _ => value.parse::<i64>().ok().map(Value::Integer),
+    };
+    parsed.ok_or_else(|| {
+        let expected = match field.kind() {
+            FieldKind::Duration => "a length like `90s`, `5m`, `1h2m` or `3:30`",
+            FieldKind::Stars => "stars from 0 to 5 like `3` or `3.5`",
+            _ => "a whole number",
+        };
+        error(
+            position,
+            &format!(
+                "invalid value `{}` for `{}`, expected {}",
+                value,
+                field.name(),
+                expected
+            ),
+        )
+    })
+}
+
+/// Parses a whole or half number of stars from 0 to 5, e.g. `3` or `3.5`.
+fn parse_stars(value: &str) -> Option<Rating> {
+    let stars = value.parse::<f64>().ok()?;
+    if (stars * 2.0).fract() != 0.0 {
+        return None;
+    }
+    Rating::from_stars(stars)
+}
+
+/// Parses `90`, `90s`, `5m`, `1h2m3s`, `3:30` or `1:02:03` into seconds.
+/// None if the value is malformed or too long to count in seconds.
+fn parse_duration_seconds(value: &str) -> Option<i64> {
+    if value.contains(':') {
+        return value.split(':').try_fold(0_i64, |total, part| {
+            (!part.is_empty())
+                .then(|| part.parse::<i64>().ok())
+                .flatten()
+                .and_then(|n| total.checked_mul(60)?.checked_add(n))
+        });
+    }
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Some(seconds);
+    }
+    let mut total: i64 = 0;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = digits
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(unit)?
+            .checked_add(total)?;
+        digits.clear();
+    }
+    digits.is_empty().then_some(total)
+}
+
+fn filter(negated: bool, expr: Expr) -> Term {
+    if negated {
+        Term::Filter(Expr::Not(Box::new(expr)))
+    } else {
+        Term::Filter(expr)
+    }
+}
+
+fn any_contains(text: String) -> Expr {
+    Expr::Condition(Condition {
+        field: Field::Any,
+        comparison: Comparison::Contains(text),
+    })
+}
+
+fn error(position: usize, message: &str) -> QueryParseError {
+    QueryParseError {
+        position,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod query_parser_tests {
+    use super::{parse, parse_duration_seconds, QueryParseError};
+    use crate::database::query::{Comparison, Condition, Expr, Field, Query, SortTerm, Value};
+    use crate::database::user_data::Rating;
+    use rstest::rstest;
+
+    fn condition(field: Field, comparison: Comparison) -> Expr {
+        Expr::Condition(Condition { field, comparison })
+    }
+
+    #[test]
+    fn test_parse_full_query() {
+        let query = parse(
+            r#"artist:"Boards of Canada" year:1995..2002 genre:ambient -title:remix length:>5m sort:year"#,
+        )
+        .unwrap();
+        assert_eq!(
+            query,
+            Query {
+                filter: Some(Expr::And(vec![
+                    condition(
+                        Field::Artist,
+                        Comparison::Contains(String::from("Boards of Canada"))
+                    ),
+                    condition(
+                        Field::Year,
+                        Comparison::Between(Value::Integer(1995), Value::Integer(2002))
+                    ),
+                    condition(Field::Genre, Comparison::Contains(String::from("ambient"))),
+                    Expr::Not(Box::new(condition(
+                        Field::Title,
+                        Comparison::Contains(String::from("remix"))
+                    ))),
+                    condition(Field::Length, Comparison::GreaterThan(Value::Integer(300))),
+                ])),
+                sort: vec![SortTerm {
+                    field: Field::Year,
+                    descending: false,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_or_groups() {
+        let query = parse("boards year:1998 OR -aphex").unwrap();
+        assert_eq!(
+            query.filter,
+            Some(Expr::Or(vec![
+                Expr::And(vec![
+                    condition(Field::Any, Comparison::Contains(String::from("boards"))),
+                    condition(Field::Year, Comparison::Equals(Value::Integer(1998))),
+                ]),
+                Expr::Not(Box::new(condition(
+                    Field::Any,
+                    Comparison::Contains(String::from("aphex"))
+                ))),
+            ]))
+        );
+    }
+
+    #[rstest]
+    #[case("year:1995..", Comparison::AtLeast(Value::Integer(1995)))]
+    #[case("year:..2002", Comparison::AtMost(Value::Integer(2002)))]
+    #[case("track:<=3", Comparison::AtMost(Value::Integer(3)))]
+    #[case("Track:=3", Comparison::Equals(Value::Integer(3)))]
+    #[case(
+        "length:3:30..1h",
+        Comparison::Between(Value::Integer(210), Value::Integer(3600))
+    )]
+    #[case(
+        "title:=\"Roygbiv\"",
+        Comparison::Equals(Value::Text(String::from("Roygbiv")))
+    )]
+    #[case("title:1995..2002", Comparison::Contains(String::from("1995..2002")))]
+    #[case(
+        "rating:>=3.5",
+        Comparison::AtLeast(Value::Rating(Rating::from_half_stars(7).unwrap()))
+    )]
+    #[case(
+        "rating:1..4",
+        Comparison::Between(
+            Value::Rating(Rating::from_half_stars(2).unwrap()),
+            Value::Rating(Rating::from_half_stars(8).unwrap())
+        )
+    )]
+    fn test_parse_comparisons(#[case] query: &str, #[case] expected: Comparison) {
+        let Some(Expr::Condition(Condition { comparison, .. })) = parse(query).unwrap().filter
+        else {
+            panic!("expected a single condition");
+        };
+        assert_eq!(comparison, expected);
+    }
+
+    #[test]
+    fn test_parse_sort_terms() {
+        assert_eq!(
+            parse("sort:-year,title").unwrap().sort,
+            vec![
+                SortTerm {
+                    field: Field::Year,
+                    descending: true,
+                },
+                SortTerm {
+                    field: Field::Title,
+                    descending: false,
+                },
+            ]
+        );
+        assert_eq!(
+            parse("sort:disc,-added,plays").unwrap().sort,
+            vec![
+                SortTerm {
+                    field: Field::Disc,
+                    descending: false,
+                },
+                SortTerm {
+                    field: Field::Added,
+                    descending: true,
+                },
+                SortTerm {
+                    field: Field::Plays,
+                    descending: false,
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("", Query::default())]
+    #[case("   ", Query::default())]
+    fn test_parse_empty(#[case] query: &str, #[case] expected: Query) {
+        assert_eq!(parse(query).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("colour:red", 0, "unknown field `colour`")]
+    #[case("year:nineteen", 5, "invalid value `nineteen` for `year`")]
+    #[case("year:2002..1995", 5, "range start 2002 is after its end 1995")]
+    #[case("year:1995..x", 11, "invalid value `x` for `year`")]
+    #[case("year:..", 5, "range needs a start or an end")]
+    #[case("length:>5x", 8, "invalid value `5x` for `length`")]
+    #[case("rating:3.2", 7, "invalid value `3.2` for `rating`")]
+    #[case("rating:6", 7, "invalid value `6` for `rating`")]
+    #[case("rating:NaN", 7, "invalid value `NaN` for `rating`")]
+    #[case("rating:4..2.5", 7, "range start 4 is after its end 2.5")]
+    #[case(
+        "length:>9999999999999999h",
+        8,
+        "invalid value `9999999999999999h` for `length`"
+    )]
+    #[case("title:>5", 6, "`>` can't be used on text field `title`")]
+    #[case("year:=>5", 5, "unknown operator `=>`")]
+    #[case("artist:\"boards", 7, "unterminated quote")]
+    #[case("artist:", 7, "expected a value for `artist`")]
+    #[case("OR boards", 0, "expected a term before `OR`")]
+    #[case("boards OR", 9, "expected a term after `OR`")]
+    #[case("- boards", 0, "expected a term after `-`")]
+    #[case("sort:colour", 5, "unknown field `colour` to sort by")]
+    #[case("sort:year,-colour", 10, "unknown field `colour` to sort by")]
+    #[case("-sort:year", 0, "`sort` can't be negated")]
+    fn test_parse_errors(
+        #[case] query: &str,
+        #[case] expected_position: usize,
+        #[case] expected_message_start: &str,
+    ) {
+        let QueryParseError { position, message } = parse(query).unwrap_err();
+        assert_eq!(position, expected_position);
+        assert!(
+            message.starts_with(expected_message_start),
+            "unexpected message: {}",
+            message
+        );
+    }
+
+    #[rstest]
+    #[case("90", Some(90))]
+    #[case("90s", Some(90))]
+    #[case("5m", Some(300))]
+    #[case("1h2m3s", Some(3723))]
+    #[case("3:30", Some(210))]
+    #[case("1:02:03", Some(3723))]
+    #[case("5m3", None)]
+    #[case("3::30", None)]
+    #[case("m", None)]
+    #[case("9223372036854775807:0", None)]
+    #[case("9999999999999999h", None)]
+    #[case("9223372036854775807s1s", None)]
+    fn test_parse_duration_seconds(#[case] value: &str, #[case] expected: Option<i64>) {
+        assert_eq!(parse_duration_seconds(value), expected);
+    }
+}
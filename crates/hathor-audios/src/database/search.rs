@@ -0,0 +1,212 @@
+use crate::database::listing::{query_page, ListOptions, OrderKey, Page};
+use crate::database::unicode_folding::fold_text;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Full-text search over title, album, artist, album artist, genre, composer and lyrics.
+/// Every word in the query must match the start of a word in any of those fields,
+/// ignoring case and diacritics.
+/// Results are ranked by relevance (bm25), with title matches weighted highest,
+/// unless `options` sorts them. Ranks shift as the library changes,
+/// so a page may overlap the one before it if audios were added in between.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to search.
+/// * `query` - Free text typed by the user, e.g. "boards can".
+/// * `options` - Sort, page size and cursor, see [ListOptions].
+///
+/// # Examples
+///
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::listing::ListOptions;
+/// use hathor_audios::database::search::search;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let options = ListOptions {
+///     limit: Some(50),
+///     ..ListOptions::default()
+/// };
+/// let page = search(&mut conn, "boards can", &options);
+pub fn search(
+    conn: &mut Connection,
+    query: &str,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    let Some(fts_query) = to_fts_prefix_query(&fold_text(query)) else {
+        return Ok(Page {
+            items: Vec::new(),
+            next: None,
+        });
+    };
+    query_page(
+        conn,
+        include_str!("search/search_audios.sql"),
+        vec![SqlValue::Text(fts_query)],
+        options,
+        &[OrderKey::ascending("search_rank")],
+    )
+}
+
+/// Turns free text into an FTS5 query where every word is a quoted prefix match.
+/// Quoting stops user input being parsed as FTS5 syntax (e.g. `-`, `:`, `AND`).
+/// Returns None if the text has nothing searchable in it.
+fn to_fts_prefix_query(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod test_search_operations {
+    use super::{search, to_fts_prefix_query};
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::fixtures::{
+        insert_temp_audio, playlist_db_in_memory, temp_audios_context, TestInMemoryDBContext,
+    };
+    use blake3::Hash;
+    use rstest::rstest;
+    use rusqlite::named_params;
+
+    fn search_all(context: &mut TestInMemoryDBContext, query: &str) -> Vec<AudioFile> {
+        search(&mut context.connection, query, &ListOptions::default())
+            .unwrap()
+            .items
+    }
+
+    #[rstest]
+    #[case("radio head", Some(r#""radio"* "head"*"#))]
+    #[case(r#"ac"dc -live"#, Some(r#""ac""dc"* "-live"*"#))]
+    #[case("  - ", None)]
+    fn test_to_fts_prefix_query(#[case] query: &str, #[case] expected: Option<&str>) {
+        assert_eq!(to_fts_prefix_query(query).as_deref(), expected);
+    }
+
+    /// Words can match across different fields, and match as prefixes.
+    #[rstest]
+    #[case("artist 1", vec![1])]
+    #[case("tes alb", vec![0, 1, 2])]
+    #[case("title 2 artist", vec![2])]
+    #[case("missing", vec![])]
+    #[case("TÉST ÁRTIST 1", vec![1])]
+    fn test_search_matches(
+        mut playlist_db_in_memory: TestInMemoryDBContext,
+        #[case] query: &str,
+        #[case] expected_audio_indexes: Vec<usize>,
+    ) {
+        let mut audios = search_all(&mut playlist_db_in_memory, query);
+        audios.sort_by(|a, b| a.audio_title.cmp(&b.audio_title));
+        let expected_audios: Vec<AudioFile> = expected_audio_indexes
+            .into_iter()
+            .map(|n| playlist_db_in_memory.audios[n].clone())
+            .collect();
+        assert_eq!(audios, expected_audios);
+    }
+
+    #[rstest]
+    fn test_search_pages(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let all_audios = search_all(&mut playlist_db_in_memory, "test");
+        let mut options = ListOptions {
+            limit: Some(2),
+            ..ListOptions::default()
+        };
+        let page = search(&mut playlist_db_in_memory.connection, "test", &options).unwrap();
+        assert_eq!(all_audios.len(), 3);
+        assert_eq!(page.items, all_audios[0..2]);
+        options.after = page.next;
+        let page = search(&mut playlist_db_in_memory.connection, "test", &options).unwrap();
+        assert_eq!(page.items, all_audios[2..3]);
+        assert_eq!(page.next, None);
+    }
+
+    /// A title match outranks a match in the lyrics.
+    #[rstest]
+    fn test_search_ranks_title_above_lyrics(mut temp_audios_context: TestInMemoryDBContext) {
+        let lyrics_match = insert_temp_audio(
+            &mut temp_audios_context,
+            AudioFile {
+                file_hash: Hash::from_hex(format!("{:064}", 1)).unwrap(),
+                audio_title: String::from("Roygbiv"),
+                lyrics: String::from("music has the right to children"),
+                ..AudioFile::default()
+            },
+        );
+        let title_match = insert_temp_audio(
+            &mut temp_audios_context,
+            AudioFile {
+                file_hash: Hash::from_hex(format!("{:064}", 2)).unwrap(),
+                audio_title: String::from("Music Is Math"),
+                artist_name: String::from("Boards of Canada"),
+                ..AudioFile::default()
+            },
+        );
+        let audios = search_all(&mut temp_audios_context, "music");
+        assert_eq!(audios, vec![title_match, lyrics_match]);
+    }
+
+    /// The triggers keep the search index in sync when audios change.
+    #[rstest]
+    fn test_search_follows_audio_updates(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        playlist_db_in_memory
+            .connection
+            .execute(
+                r"UPDATE audios SET audio_title = 'Dayvan Cowboy' WHERE file_hash = :file_hash",
+                named_params! {":file_hash": playlist_db_in_memory.audios[0].file_hash.to_string()},
+            )
+            .unwrap();
+        let audios = search_all(&mut playlist_db_in_memory, "dayvan");
+        assert_eq!(audios.len(), 1);
+        assert_eq!(audios[0].audio_title, "Dayvan Cowboy");
+        assert!(search_all(&mut playlist_db_in_memory, "title 0").is_empty());
+
+        let new_hash = format!("{:064}", 9);
+        playlist_db_in_memory
+            .connection
+            .execute(
+                r"UPDATE audios SET file_hash = :new_hash WHERE audio_title = 'Dayvan Cowboy'",
+                named_params! {":new_hash": new_hash},
+            )
+            .unwrap();
+        let indexed_hash = playlist_db_in_memory
+            .connection
+            .query_row(
+                r"SELECT file_hash FROM audios_search WHERE audios_search MATCH 'dayvan'",
+                (),
+                |row| row.get::<usize, String>(0),
+            )
+            .unwrap();
+        assert_eq!(indexed_hash, new_hash);
+        assert_eq!(search_row_count(&playlist_db_in_memory), (3, 3));
+
+        playlist_db_in_memory
+            .connection
+            .execute(r"DELETE FROM audios", ())
+            .unwrap();
+        assert!(search_all(&mut playlist_db_in_memory, "dayvan").is_empty());
+        assert_eq!(search_row_count(&playlist_db_in_memory), (0, 0));
+    }
+
+    /// Rows of the search index and of its rowid map.
+    fn search_row_count(context: &TestInMemoryDBContext) -> (i64, i64) {
+        context
+            .connection
+            .query_row(
+                r"SELECT
+                    (SELECT COUNT(*) FROM audios_search)
+                    , (SELECT COUNT(*) FROM audios_search_rows)",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+    }
+}
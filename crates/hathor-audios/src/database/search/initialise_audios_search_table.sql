@@ -0,0 +1,136 @@
+-- Full-text index over the searchable audio metadata, kept in sync with audios by triggers.
+-- Text is stored folded by FOLD_TEXT, so queries must be folded the same way.
+-- FOLD_TEXT is a Rust function Hathor registers on its own connections, so the triggers
+-- fail on any other connection: other tools (e.g. the sqlite3 shell) may read the library,
+-- but writes to audios must go through Hathor.
+CREATE VIRTUAL TABLE IF NOT EXISTS audios_search USING fts5 (
+    file_hash UNINDEXED
+    , audio_title
+    , album_name
+    , artist_name
+    , album_artist_name
+    , genre
+    , composer
+    , lyrics
+    , tokenize = 'unicode61 remove_diacritics 2'
+    , prefix = '2 3'
+);
+
+-- audios has no rowid, so this gives each audio the rowid of its search row.
+-- Search rows are then found by rowid rather than by scanning the unindexed file_hash.
+CREATE TABLE IF NOT EXISTS audios_search_rows (
+    search_rowid INTEGER PRIMARY KEY
+    , file_hash CHAR(64) NOT NULL UNIQUE
+);
+
+CREATE TRIGGER IF NOT EXISTS audios_search_after_insert AFTER INSERT ON audios
+BEGIN
+    INSERT INTO audios_search_rows (file_hash) VALUES (new.file_hash);
+    INSERT INTO audios_search (
+        rowid
+        , file_hash
+        , audio_title
+        , album_name
+        , artist_name
+        , album_artist_name
+        , genre
+        , composer
+        , lyrics
+    ) VALUES (
+        (
+            SELECT audios_search_rows.search_rowid
+            FROM audios_search_rows
+            WHERE audios_search_rows.file_hash = new.file_hash
+        )
+        , new.file_hash
+        , FOLD_TEXT(new.audio_title)
+        , FOLD_TEXT(new.album_name)
+        , FOLD_TEXT(new.artist_name)
+        , FOLD_TEXT(new.album_artist_name)
+        , FOLD_TEXT(new.genre)
+        , FOLD_TEXT(new.composer)
+        , FOLD_TEXT(new.lyrics)
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS audios_search_after_delete AFTER DELETE ON audios
+BEGIN
+    DELETE FROM audios_search
+    WHERE audios_search.rowid = (
+        SELECT audios_search_rows.search_rowid
+        FROM audios_search_rows
+        WHERE audios_search_rows.file_hash = old.file_hash
+    );
+    DELETE FROM audios_search_rows
+    WHERE audios_search_rows.file_hash = old.file_hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audios_search_after_update AFTER UPDATE ON audios
+BEGIN
+    DELETE FROM audios_search
+    WHERE audios_search.rowid = (
+        SELECT audios_search_rows.search_rowid
+        FROM audios_search_rows
+        WHERE audios_search_rows.file_hash = old.file_hash
+    );
+    UPDATE audios_search_rows
+    SET file_hash = new.file_hash
+    WHERE audios_search_rows.file_hash = old.file_hash;
+    INSERT INTO audios_search (
+        rowid
+        , file_hash
+        , audio_title
+        , album_name
+        , artist_name
+        , album_artist_name
+        , genre
+        , composer
+        , lyrics
+    ) VALUES (
+        (
+            SELECT audios_search_rows.search_rowid
+            FROM audios_search_rows
+            WHERE audios_search_rows.file_hash = new.file_hash
+        )
+        , new.file_hash
+        , FOLD_TEXT(new.audio_title)
+        , FOLD_TEXT(new.album_name)
+        , FOLD_TEXT(new.artist_name)
+        , FOLD_TEXT(new.album_artist_name)
+        , FOLD_TEXT(new.genre)
+        , FOLD_TEXT(new.composer)
+        , FOLD_TEXT(new.lyrics)
+    );
+END;
+
+-- Index audios that were stored before the search table existed.
+INSERT INTO audios_search_rows (file_hash)
+SELECT audios.file_hash
+FROM audios
+WHERE NOT EXISTS (SELECT 1 FROM audios_search_rows);
+
+INSERT INTO audios_search (
+    rowid
+    , file_hash
+    , audio_title
+    , album_name
+    , artist_name
+    , album_artist_name
+    , genre
+    , composer
+    , lyrics
+)
+SELECT
+    audios_search_rows.search_rowid
+    , audios.file_hash
+    , FOLD_TEXT(audios.audio_title)
+    , FOLD_TEXT(audios.album_name)
+    , FOLD_TEXT(audios.artist_name)
+    , FOLD_TEXT(audios.album_artist_name)
+    , FOLD_TEXT(audios.genre)
+    , FOLD_TEXT(audios.composer)
+    , FOLD_TEXT(audios.lyrics)
+FROM audios
+    INNER JOIN audios_search_rows
+        ON audios.file_hash = audios_search_rows.file_hash
+WHERE NOT EXISTS (SELECT 1 FROM audios_search);
@@ -0,0 +1,21 @@
+WITH matches AS (
+    SELECT
+        audios_search.file_hash
+        -- Weights follow the column order, file_hash is unindexed.
+        , BM25(audios_search, 0.0, 10.0, 5.0, 5.0, 3.0, 2.0, 2.0, 1.0) AS search_rank
+    FROM audios_search
+    WHERE audios_search MATCH ?1
+)
+
+SELECT
+    audio_listings.*
+    , matches.search_rank
+FROM matches
+    INNER JOIN audio_listings
+        ON matches.file_hash = audio_listings.file_hash
+-- One row per audio, even if it is stored at several paths.
+WHERE audio_listings.audio_file_id = (
+    SELECT MIN(audio_files.rowid)
+    FROM audio_files
+    WHERE audio_files.file_hash = matches.file_hash
+);
@@ -0,0 +1,840 @@
+use crate::database::listing::{order_by_sql, order_keys, query_page, ListOptions, OrderKey, Page};
+use crate::database::playlists::{get_playlist, insert_playlist, Playlist};
+use crate::database::query::{
+    Comparison, Condition, Expr, Field, FieldKind, Query, SortTerm, Value,
+};
+use crate::database::user_data::Rating;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{named_params, params_from_iter, Connection, OptionalExtension, Transaction};
+use std::error::Error;
+use time::{Duration, OffsetDateTime};
+
+/// What a smart playlist holds, evaluated each time the playlist is read,
+/// e.g. the 25 most played audios added in the last 90 days.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::database::query::{Field, SortTerm};
+/// use hathor_audios::database::smart_playlists::{Rule, RuleMatch, RuleTest, SmartPlaylistRules};
+/// use time::Duration;
+///
+/// let rules = SmartPlaylistRules {
+///     rule_match: RuleMatch::All,
+///     rules: vec![Rule {
+///         field: Field::Added,
+///         test: RuleTest::InLast(Duration::days(90)),
+///         negated: false,
+///     }],
+///     sort: vec![SortTerm { field: Field::Plays, descending: true }],
+///     limit: Some(25),
+/// };
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct SmartPlaylistRules {
+    pub rule_match: RuleMatch,
+    /// No rules match every audio.
+    pub rules: Vec<Rule>,
+    /// Order of the playlist, also deciding which audios are kept by `limit`.
+    pub sort: Vec<SortTerm>,
+    /// Most audios in the playlist, None for all that match.
+    pub limit: Option<u32>,
+}
+
+/// Whether an audio must match every rule or just one of them.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum RuleMatch {
+    All,
+    Any,
+}
+
+/// A single condition of a smart playlist, e.g. `length > 5m` or `not genre contains "live"`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Rule {
+    pub field: Field,
+    pub test: RuleTest,
+    /// Keep audios that fail the test instead.
+    pub negated: bool,
+}
+
+/// How a [Rule] tests its field.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum RuleTest {
+    Compare(Comparison),
+    /// Within this long before the playlist is read, for timestamp fields
+    /// such as [Field::Added] or [Field::LastPlayed].
+    InLast(Duration),
+}
+
+impl SmartPlaylistRules {
+    /// The rules as a [Query], with relative times resolved against `now`.
+    pub fn to_query(&self, now: OffsetDateTime) -> Query {
+        let exprs = self
+            .rules
+            .iter()
+            .map(|rule| rule.to_expr(now))
+            .collect::<Vec<Expr>>();
+        let filter = match (exprs.is_empty(), self.rule_match) {
+            (true, _) => None,
+            (false, RuleMatch::All) => Some(Expr::And(exprs)),
+            (false, RuleMatch::Any) => Some(Expr::Or(exprs)),
+        };
+        Query {
+            filter,
+            sort: self.sort.clone(),
+        }
+    }
+}
+
+impl Rule {
+    fn to_expr(&self, now: OffsetDateTime) -> Expr {
+        let comparison = match &self.test {
+            RuleTest::Compare(comparison) => comparison.clone(),
+            RuleTest::InLast(duration) => {
+                Comparison::AtLeast(Value::Integer((now - *duration).unix_timestamp()))
+            }
+        };
+        let condition = Expr::Condition(Condition {
+            field: self.field,
+            comparison,
+        });
+        if self.negated {
+            Expr::Not(Box::new(condition))
+        } else {
+            condition
+        }
+    }
+
+    /// Fails if the test can't apply to the field, as a [Query] wouldn't parse it,
+    /// e.g. `contains` on a number field or a rating compared with a plain integer.
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        let (name, kind) = (self.field.name(), self.field.kind());
+        let comparison = match &self.test {
+            RuleTest::Compare(comparison) => comparison,
+            RuleTest::InLast(_) => {
+                return match self.field {
+                    Field::Added | Field::LastPlayed => Ok(()),
+                    _ => Err(
+                        format!("`in_last` can't be used on `{}`, only timestamps", name).into(),
+                    ),
+                };
+            }
+        };
+        let values = match (kind, comparison) {
+            (FieldKind::Text, Comparison::Contains(_)) => return Ok(()),
+            (FieldKind::Text, Comparison::Equals(value)) => vec![value],
+            (FieldKind::Text, _) => {
+                return Err(format!("text field `{}` can only be compared with `=`", name).into())
+            }
+            (_, Comparison::Contains(_)) => {
+                return Err(format!("`contains` can't be used on number field `{}`", name).into())
+            }
+            (_, Comparison::Between(low, high)) => vec![low, high],
+            (
+                _,
+                Comparison::Equals(value)
+                | Comparison::GreaterThan(value)
+                | Comparison::AtLeast(value)
+                | Comparison::LessThan(value)
+                | Comparison::AtMost(value),
+            ) => vec![value],
+        };
+        let expected = match kind {
+            FieldKind::Text => "text",
+            FieldKind::Integer => "a whole number",
+            FieldKind::Duration => "whole seconds",
+            FieldKind::Stars => "a rating",
+        };
+        match values
+            .into_iter()
+            .find(|value| !value_has_kind(value, kind))
+        {
+            Some(value) => Err(format!(
+                "invalid value {:?} for `{}`, expected {}",
+                value, name, expected
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Creates a smart playlist.
+/// Fails if a playlist with the name already exists.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `description` - Free text shown with the playlist.
+/// * `rules` - Chooses the audios of the playlist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::smart_playlists::{create_smart_playlist, RuleMatch, SmartPlaylistRules};
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let rules = SmartPlaylistRules {
+///     rule_match: RuleMatch::All,
+///     rules: Vec::new(),
+///     sort: Vec::new(),
+///     limit: Some(100),
+/// };
+/// let playlist = create_smart_playlist(&mut conn, "Anything", "", &rules);
+pub fn create_smart_playlist(
+    conn: &mut Connection,
+    playlist_name: &str,
+    description: &str,
+    rules: &SmartPlaylistRules,
+) -> Result<Playlist, Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = insert_playlist(&transaction, playlist_name, description)?;
+    insert_smart_playlist_rules(&transaction, playlist_id, rules)?;
+    transaction.commit()?;
+    get_playlist(conn, playlist_name)?
+        .ok_or_else(|| format!("playlist {} was not created", playlist_name).into())
+}
+
+/// Replaces the rules of a smart playlist.
+/// Fails if the playlist doesn't exist or isn't smart.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `playlist_name` - Title of the playlist.
+/// * `rules` - Chooses the audios of the playlist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::smart_playlists::{set_smart_playlist_rules, RuleMatch, SmartPlaylistRules};
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let rules = SmartPlaylistRules {
+///     rule_match: RuleMatch::Any,
+///     rules: Vec::new(),
+///     sort: Vec::new(),
+///     limit: None,
+/// };
+/// set_smart_playlist_rules(&mut conn, "Anything", &rules);
+pub fn set_smart_playlist_rules(
+    conn: &mut Connection,
+    playlist_name: &str,
+    rules: &SmartPlaylistRules,
+) -> Result<(), Box<dyn Error>> {
+    let transaction = conn.transaction()?;
+    let playlist_id = get_smart_playlist_id(&transaction, playlist_name)?
+        .ok_or_else(|| format!("no smart playlist named {}", playlist_name))?;
+    delete_smart_playlist_rules(&transaction, playlist_id)?;
+    insert_smart_playlist_rules(&transaction, playlist_id, rules)?;
+    transaction.execute(
+        include_str!("playlists/touch_playlist.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Retvieve the rules of a smart playlist, None if the playlist doesn't exist or isn't smart.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - Title of the playlist.
+///
+/// Examples
+/// ```no_run
+/// use rusqlite::Connection;
+/// use hathor_audios::database::smart_playlists::get_smart_playlist_rules;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let rules = get_smart_playlist_rules(&mut conn, "Recently added");
+pub fn get_smart_playlist_rules(
+    conn: &mut Connection,
+    playlist_name: &str,
+) -> Result<Option<SmartPlaylistRules>, Box<dyn Error>> {
+    match get_smart_playlist_id(conn, playlist_name)? {
+        Some(playlist_id) => load_smart_playlist_rules(conn, playlist_id),
+        None => Ok(None),
+    }
+}
+
+/// Removes the rules and sort terms of a smart playlist, leaving it with none.
+pub(crate) fn delete_smart_playlist_rules(
+    transaction: &Transaction,
+    playlist_id: i64,
+) -> rusqlite::Result<()> {
+    transaction.execute(
+        include_str!("smart_playlists/delete_smart_playlist_rules.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    transaction.execute(
+        include_str!("smart_playlists/delete_smart_playlist_sort_terms.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    Ok(())
+}
+
+/// Removes everything that makes a playlist smart, a no-op for other playlists.
+pub(crate) fn delete_smart_playlist(
+    transaction: &Transaction,
+    playlist_id: i64,
+) -> rusqlite::Result<()> {
+    delete_smart_playlist_rules(transaction, playlist_id)?;
+    transaction.execute(
+        include_str!("smart_playlists/delete_smart_playlist.sql"),
+        named_params! {":playlist_id": playlist_id},
+    )?;
+    Ok(())
+}
+
+/// Whether the playlist's audios come from rules rather than items.
+pub(crate) fn is_smart_playlist(conn: &Connection, playlist_id: i64) -> rusqlite::Result<bool> {
+    Ok(conn
+        .query_row(
+            include_str!("smart_playlists/get_smart_playlist.sql"),
+            named_params! {":playlist_id": playlist_id},
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Evaluates a smart playlist's rules, returning a page of the matching audios.
+/// See [get_audios_from_playlist](super::playlists::get_audios_from_playlist).
+pub(crate) fn get_audios_from_smart_playlist(
+    conn: &Connection,
+    playlist_id: i64,
+    options: &ListOptions,
+) -> Result<Page, Box<dyn Error>> {
+    let rules = load_smart_playlist_rules(conn, playlist_id)?
+        .ok_or_else(|| format!("playlist {} is not smart", playlist_id))?;
+    let (sql, params) = smart_playlist_sql(&rules);
+    let rule_order = rules
+        .sort
+        .iter()
+        .map(|term| OrderKey::from(*term))
+        .collect::<Vec<OrderKey>>();
+    query_page(conn, &sql, params, options, &rule_order)
+}
+
+/// Number and total length of the audios a smart playlist currently holds.
+pub(crate) fn smart_playlist_totals(
+    conn: &Connection,
+    playlist_id: i64,
+) -> Result<(u32, Duration), Box<dyn Error>> {
+    let rules = load_smart_playlist_rules(conn, playlist_id)?
+        .ok_or_else(|| format!("playlist {} is not smart", playlist_id))?;
+    let (sql, params) = smart_playlist_sql(&rules);
+    let sql = format!(
+        "SELECT COUNT(*), IFNULL(SUM(audio_length_seconds), 0) FROM ({}) AS audio_listing",
+        sql
+    );
+    Ok(conn.query_row(&sql, params_from_iter(params), |row| {
+        Ok((row.get(0)?, Duration::seconds(row.get(1)?)))
+    })?)
+}
+
+/// Select over `audio_listings` of the audios matching the rules, one row per audio.
+fn smart_playlist_sql(rules: &SmartPlaylistRules) -> (String, Vec<SqlValue>) {
+    let (sql, mut params) = rules.to_query(OffsetDateTime::now_utc()).to_sql();
+    // Audios stored at several paths are listed at their first, rather than grouped,
+    // so SQLite can still page this through an index, see query_page.
+    let mut sql = format!(
+        "SELECT audio_listing.* FROM ({}) AS audio_listing \
+         WHERE audio_listing.audio_file_id = (SELECT MIN(audio_files.rowid) FROM audio_files \
+         WHERE audio_files.file_hash = audio_listing.file_hash)",
+        sql
+    );
+    if let Some(limit) = rules.limit {
+        sql += " ORDER BY ";
+        sql += &order_by_sql(&order_keys(&rules.sort, &[]));
+        params.push(SqlValue::Integer(limit.into()));
+        sql += &format!(" LIMIT ?{}", params.len());
+    }
+    (sql, params)
+}
+
+fn get_smart_playlist_id(conn: &Connection, playlist_name: &str) -> rusqlite::Result<Option<i64>> {
+    let playlist_id = conn
+        .query_row(
+            include_str!("playlists/get_playlist_id.sql"),
+            named_params! {":playlist_name": playlist_name},
+            |row| row.get(0),
+        )
+        .optional()?;
+    match playlist_id {
+        Some(playlist_id) if is_smart_playlist(conn, playlist_id)? => Ok(Some(playlist_id)),
+        _ => Ok(None),
+    }
+}
+
+fn insert_smart_playlist_rules(
+    transaction: &Transaction,
+    playlist_id: i64,
+    rules: &SmartPlaylistRules,
+) -> Result<(), Box<dyn Error>> {
+    for rule in &rules.rules {
+        rule.check()?;
+    }
+    transaction.execute(
+        include_str!("smart_playlists/insert_smart_playlist.sql"),
+        named_params! {
+            ":playlist_id": playlist_id,
+            ":match_all": rules.rule_match == RuleMatch::All,
+            ":track_limit": rules.limit,
+        },
+    )?;
+    let mut statement = transaction.prepare_cached(include_str!(
+        "smart_playlists/insert_smart_playlist_rule.sql"
+    ))?;
+    for (rule_index, rule) in rules.rules.iter().enumerate() {
+        let (operator, value, high_value) = rule_test_to_sql(&rule.test);
+        statement.execute(named_params! {
+            ":playlist_id": playlist_id,
+            ":rule_index": rule_index,
+            ":field_name": rule.field.name(),
+            ":operator": operator,
+            ":negated": rule.negated,
+            ":rule_value": value,
+            ":high_value": high_value,
+        })?;
+    }
+    let mut statement = transaction.prepare_cached(include_str!(
+        "smart_playlists/insert_smart_playlist_sort_term.sql"
+    ))?;
+    for (sort_index, term) in rules.sort.iter().enumerate() {
+        statement.execute(named_params! {
+            ":playlist_id": playlist_id,
+            ":sort_index": sort_index,
+            ":field_name": term.field.name(),
+            ":descending": term.descending,
+        })?;
+    }
+    Ok(())
+}
+
+fn load_smart_playlist_rules(
+    conn: &Connection,
+    playlist_id: i64,
+) -> Result<Option<SmartPlaylistRules>, Box<dyn Error>> {
+    let Some((match_all, limit)) = conn
+        .query_row(
+            include_str!("smart_playlists/get_smart_playlist.sql"),
+            named_params! {":playlist_id": playlist_id},
+            |row| {
+                Ok((
+                    row.get::<usize, bool>(0)?,
+                    row.get::<usize, Option<u32>>(1)?,
+                ))
+            },
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let rules = conn
+        .prepare(include_str!("smart_playlists/get_smart_playlist_rules.sql"))?
+        .query_map(named_params! {":playlist_id": playlist_id}, |row| {
+            Ok((
+                row.get::<&str, String>("field_name")?,
+                row.get::<&str, String>("operator")?,
+                row.get::<&str, bool>("negated")?,
+                row.get::<&str, SqlValue>("rule_value")?,
+                row.get::<&str, SqlValue>("high_value")?,
+            ))
+        })?
+        .map(|row| {
+            let (field_name, operator, negated, value, high_value) = row?;
+            Ok(Rule {
+                field: field_from_name(&field_name)?,
+                test: rule_test_from_sql(&operator, value, high_value)?,
+                negated,
+            })
+        })
+        .collect::<Result<Vec<Rule>, Box<dyn Error>>>()?;
+    let sort = conn
+        .prepare(include_str!(
+            "smart_playlists/get_smart_playlist_sort_terms.sql"
+        ))?
+        .query_map(named_params! {":playlist_id": playlist_id}, |row| {
+            Ok((
+                row.get::<&str, String>("field_name")?,
+                row.get::<&str, bool>("descending")?,
+            ))
+        })?
+        .map(|row| {
+            let (field_name, descending) = row?;
+            Ok(SortTerm {
+                field: field_from_name(&field_name)?,
+                descending,
+            })
+        })
+        .collect::<Result<Vec<SortTerm>, Box<dyn Error>>>()?;
+    Ok(Some(SmartPlaylistRules {
+        rule_match: if match_all {
+            RuleMatch::All
+        } else {
+            RuleMatch::Any
+        },
+        rules,
+        sort,
+        limit,
+    }))
+}
+
+/// The operator name and values a rule test is stored as.
+fn rule_test_to_sql(test: &RuleTest) -> (&'static str, SqlValue, Option<SqlValue>) {
+    match test {
+        RuleTest::Compare(Comparison::Contains(text)) => {
+            ("contains", SqlValue::Text(text.clone()), None)
+        }
+        RuleTest::Compare(Comparison::Equals(value)) => ("equals", rule_value_to_sql(value), None),
+        RuleTest::Compare(Comparison::GreaterThan(value)) => {
+            ("greater_than", rule_value_to_sql(value), None)
+        }
+        RuleTest::Compare(Comparison::AtLeast(value)) => {
+            ("at_least", rule_value_to_sql(value), None)
+        }
+        RuleTest::Compare(Comparison::LessThan(value)) => {
+            ("less_than", rule_value_to_sql(value), None)
+        }
+        RuleTest::Compare(Comparison::AtMost(value)) => ("at_most", rule_value_to_sql(value), None),
+        RuleTest::Compare(Comparison::Between(low, high)) => (
+            "between",
+            rule_value_to_sql(low),
+            Some(rule_value_to_sql(high)),
+        ),
+        RuleTest::InLast(duration) => {
+            ("in_last", SqlValue::Integer(duration.whole_seconds()), None)
+        }
+    }
+}
+
+fn rule_test_from_sql(
+    operator: &str,
+    value: SqlValue,
+    high_value: SqlValue,
+) -> Result<RuleTest, Box<dyn Error>> {
+    let value = value_from_sql(value)?;
+    let comparison = match operator {
+        "contains" => match value {
+            Value::Text(text) => Comparison::Contains(text),
+            _ => return Err("contains rule with a number value".into()),
+        },
+        "equals" => Comparison::Equals(value),
+        "greater_than" => Comparison::GreaterThan(value),
+        "at_least" => Comparison::AtLeast(value),
+        "less_than" => Comparison::LessThan(value),
+        "at_most" => Comparison::AtMost(value),
+        "between" => Comparison::Between(value, value_from_sql(high_value)?),
+        "in_last" => match value {
+            Value::Integer(seconds) => return Ok(RuleTest::InLast(Duration::seconds(seconds))),
+            _ => return Err("in_last rule with a non integer value".into()),
+        },
+        _ => return Err(format!("unknown smart playlist rule operator {}", operator).into()),
+    };
+    Ok(RuleTest::Compare(comparison))
+}
+
+/// Ratings are stored in stars, so they read back as ratings rather than integers.
+fn rule_value_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Rating(rating) => SqlValue::Real(rating.stars()),
+        value => value.into(),
+    }
+}
+
+fn value_from_sql(value: SqlValue) -> Result<Value, Box<dyn Error>> {
+    match value {
+        SqlValue::Text(text) => Ok(Value::Text(text)),
+        SqlValue::Integer(n) => Ok(Value::Integer(n)),
+        SqlValue::Real(stars) => Rating::from_stars(stars)
+            .map(Value::Rating)
+            .ok_or_else(|| format!("smart playlist rule rating {} is out of range", stars).into()),
+        value => Err(format!("unexpected smart playlist rule value {:?}", value).into()),
+    }
+}
+
+/// Whether a value is of the type a field of `kind` holds.
+fn value_has_kind(value: &Value, kind: FieldKind) -> bool {
+    matches!(
+        (kind, value),
+        (FieldKind::Text, Value::Text(_))
+            | (FieldKind::Integer | FieldKind::Duration, Value::Integer(_))
+            | (FieldKind::Stars, Value::Rating(_))
+    )
+}
+
+fn field_from_name(name: &str) -> Result<Field, Box<dyn Error>> {
+    // Any has no name in queries, so from_name doesn't know it.
+    if name == Field::Any.name() {
+        return Ok(Field::Any);
+    }
+    Field::from_name(name).ok_or_else(|| format!("unknown field {}", name).into())
+}
+
+#[cfg(test)]
+mod test_smart_playlists_operations {
+    use super::{
+        create_smart_playlist, get_smart_playlist_rules, set_smart_playlist_rules, Rule, RuleMatch,
+        RuleTest, SmartPlaylistRules,
+    };
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::database::playlists::{
+        delete_playlist, get_audios_from_playlist, get_playlist, insert_audios_into_playlist,
+        list_playlists, remove_playlist_item,
+    };
+    use crate::database::query::{Comparison, Field, SortTerm, Value};
+    use crate::database::user_data::Rating;
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+    use time::{Duration, OffsetDateTime};
+
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        let tracks = [
+            ("Windowlicker", "Electronic", 367, 10),
+            ("Xtal", "Ambient", 291, 400),
+            ("Avril 14th", "Electronic", 125, 30),
+            ("Flim", "Electronic", 177, 5),
+            ("Alberto Balsalm", "Live", 311, 1),
+        ];
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        for (n, (title, genre, seconds, days_ago)) in tracks.into_iter().enumerate() {
+            let audio = AudioFile {
+                file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                audio_title: String::from(title),
+                artist_name: String::from("Aphex Twin"),
+                genre: String::from(genre),
+                audio_length: Duration::seconds(seconds),
+                ..AudioFile::default()
+            };
+            insert_temp_audio(&mut temp_audios_context, audio.clone());
+            temp_audios_context
+                .connection
+                .execute(
+                    "UPDATE audios SET date_added = ?1 WHERE file_hash = ?2",
+                    (
+                        now - Duration::days(days_ago).whole_seconds(),
+                        audio.file_hash.to_string(),
+                    ),
+                )
+                .unwrap();
+        }
+        temp_audios_context
+    }
+
+    /// Longest 2 audios added in the last 90 days that aren't live recordings.
+    fn recent_rules() -> SmartPlaylistRules {
+        SmartPlaylistRules {
+            rule_match: RuleMatch::All,
+            rules: vec![
+                Rule {
+                    field: Field::Added,
+                    test: RuleTest::InLast(Duration::days(90)),
+                    negated: false,
+                },
+                Rule {
+                    field: Field::Genre,
+                    test: RuleTest::Compare(Comparison::Equals(Value::Text(String::from("live")))),
+                    negated: true,
+                },
+            ],
+            sort: vec![SortTerm {
+                field: Field::Length,
+                descending: true,
+            }],
+            limit: Some(2),
+        }
+    }
+
+    fn titles(library: &mut TestInMemoryDBContext, options: &ListOptions) -> Vec<String> {
+        get_audios_from_playlist(&mut library.connection, "Recent", options)
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|a| a.audio_title)
+            .collect()
+    }
+
+    #[rstest]
+    fn test_smart_playlist_is_evaluated(mut library: TestInMemoryDBContext) {
+        create_smart_playlist(&mut library.connection, "Recent", "", &recent_rules()).unwrap();
+        assert_eq!(
+            titles(&mut library, &ListOptions::default()),
+            ["Windowlicker", "Flim"]
+        );
+        // The limit picks audios by the rules' sort, a caller's sort only reorders them.
+        let by_title = ListOptions {
+            sort: vec![SortTerm {
+                field: Field::Title,
+                descending: false,
+            }],
+            ..ListOptions::default()
+        };
+        assert_eq!(titles(&mut library, &by_title), ["Flim", "Windowlicker"]);
+
+        let playlists = list_playlists(&mut library.connection, &ListOptions::default())
+            .unwrap()
+            .items;
+        assert!(playlists[0].smart);
+        assert_eq!(playlists[0].track_count, None);
+        let playlist = get_playlist(&mut library.connection, "Recent")
+            .unwrap()
+            .unwrap();
+        assert_eq!(playlist.track_count, Some(2));
+        assert_eq!(playlist.audio_length, Some(Duration::seconds(367 + 177)));
+    }
+
+    #[rstest]
+    fn test_smart_playlist_pages(mut library: TestInMemoryDBContext) {
+        let rules = SmartPlaylistRules {
+            rule_match: RuleMatch::Any,
+            rules: vec![
+                Rule {
+                    field: Field::Genre,
+                    test: RuleTest::Compare(Comparison::Contains(String::from("ambient"))),
+                    negated: false,
+                },
+                Rule {
+                    field: Field::Length,
+                    test: RuleTest::Compare(Comparison::LessThan(Value::Integer(180))),
+                    negated: false,
+                },
+            ],
+            sort: Vec::new(),
+            limit: None,
+        };
+        create_smart_playlist(&mut library.connection, "Recent", "", &rules).unwrap();
+        let mut options = ListOptions {
+            sort: vec![SortTerm {
+                field: Field::Title,
+                descending: false,
+            }],
+            limit: Some(2),
+            after: None,
+        };
+        assert_eq!(titles(&mut library, &options), ["Avril 14th", "Flim"]);
+        options.after = get_audios_from_playlist(&mut library.connection, "Recent", &options)
+            .unwrap()
+            .next;
+        assert_eq!(titles(&mut library, &options), ["Xtal"]);
+    }
+
+    #[rstest]
+    fn test_smart_playlist_rules_round_trip(mut library: TestInMemoryDBContext) {
+        let conn = &mut library.connection;
+        create_smart_playlist(conn, "Recent", "", &recent_rules()).unwrap();
+        assert_eq!(
+            get_smart_playlist_rules(conn, "Recent").unwrap(),
+            Some(recent_rules())
+        );
+
+        let rules = SmartPlaylistRules {
+            rule_match: RuleMatch::Any,
+            rules: vec![
+                Rule {
+                    field: Field::Any,
+                    test: RuleTest::Compare(Comparison::Contains(String::from("aphex"))),
+                    negated: false,
+                },
+                Rule {
+                    field: Field::Year,
+                    test: RuleTest::Compare(Comparison::Between(
+                        Value::Integer(1990),
+                        Value::Integer(1999),
+                    )),
+                    negated: false,
+                },
+                Rule {
+                    field: Field::Rating,
+                    test: RuleTest::Compare(Comparison::LessThan(Value::Rating(
+                        Rating::from_stars(3.5).unwrap(),
+                    ))),
+                    negated: true,
+                },
+            ],
+            sort: Vec::new(),
+            limit: None,
+        };
+        set_smart_playlist_rules(conn, "Recent", &rules).unwrap();
+        assert_eq!(
+            get_smart_playlist_rules(conn, "Recent").unwrap(),
+            Some(rules)
+        );
+    }
+
+    /// Rules that a query couldn't express are refused, and the playlist isn't created.
+    #[rstest]
+    #[case(
+        Field::Rating,
+        RuleTest::Compare(Comparison::AtLeast(Value::Integer(3)))
+    )]
+    #[case(
+        Field::Plays,
+        RuleTest::Compare(Comparison::AtLeast(Value::Text(String::from("3"))))
+    )]
+    #[case(
+        Field::Length,
+        RuleTest::Compare(Comparison::Contains(String::from("5")))
+    )]
+    #[case(
+        Field::Year,
+        RuleTest::Compare(Comparison::Equals(Value::Text(String::from("1999"))))
+    )]
+    #[case(
+        Field::Any,
+        RuleTest::Compare(Comparison::Between(
+            Value::Text(String::from("a")),
+            Value::Text(String::from("b")),
+        ))
+    )]
+    #[case(Field::Genre, RuleTest::InLast(Duration::days(7)))]
+    fn test_smart_playlist_with_invalid_rule_fails(
+        mut library: TestInMemoryDBContext,
+        #[case] field: Field,
+        #[case] test: RuleTest,
+    ) {
+        let rules = SmartPlaylistRules {
+            rule_match: RuleMatch::All,
+            rules: vec![Rule {
+                field,
+                test,
+                negated: false,
+            }],
+            sort: Vec::new(),
+            limit: None,
+        };
+        let conn = &mut library.connection;
+        assert!(create_smart_playlist(conn, "Invalid", "", &rules).is_err());
+        assert_eq!(get_playlist(conn, "Invalid").unwrap(), None);
+        create_smart_playlist(conn, "Recent", "", &recent_rules()).unwrap();
+        assert!(set_smart_playlist_rules(conn, "Recent", &rules).is_err());
+        assert_eq!(
+            get_smart_playlist_rules(conn, "Recent").unwrap(),
+            Some(recent_rules())
+        );
+    }
+
+    #[rstest]
+    fn test_smart_playlist_items_cant_be_edited(mut library: TestInMemoryDBContext) {
+        let audios = library.audios.clone();
+        let conn = &mut library.connection;
+        create_smart_playlist(conn, "Recent", "", &recent_rules()).unwrap();
+        assert!(insert_audios_into_playlist(conn, "Recent", &audios).is_err());
+        assert!(remove_playlist_item(conn, "Recent", 0).is_err());
+        insert_audios_into_playlist(conn, "Static", &audios).unwrap();
+        assert!(set_smart_playlist_rules(conn, "Static", &recent_rules()).is_err());
+        assert_eq!(get_smart_playlist_rules(conn, "Static").unwrap(), None);
+
+        delete_playlist(conn, "Recent").unwrap();
+        assert_eq!(get_smart_playlist_rules(conn, "Recent").unwrap(), None);
+        let page = get_audios_from_playlist(conn, "Recent", &ListOptions::default()).unwrap();
+        assert!(page.items.is_empty());
+    }
+}
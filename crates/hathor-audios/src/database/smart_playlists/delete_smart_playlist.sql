@@ -0,0 +1,2 @@
+DELETE FROM smart_playlists
+WHERE playlist_id = :playlist_id;
@@ -0,0 +1,2 @@
+DELETE FROM smart_playlist_rules
+WHERE playlist_id = :playlist_id;
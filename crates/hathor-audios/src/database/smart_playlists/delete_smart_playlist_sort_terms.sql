@@ -0,0 +1,2 @@
+DELETE FROM smart_playlist_sort_terms
+WHERE playlist_id = :playlist_id;
@@ -0,0 +1,5 @@
+SELECT
+    smart_playlists.match_all
+    , smart_playlists.track_limit
+FROM smart_playlists
+WHERE smart_playlists.playlist_id = :playlist_id;
@@ -0,0 +1,9 @@
+SELECT
+    smart_playlist_rules.field_name
+    , smart_playlist_rules.operator
+    , smart_playlist_rules.negated
+    , smart_playlist_rules.rule_value
+    , smart_playlist_rules.high_value
+FROM smart_playlist_rules
+WHERE smart_playlist_rules.playlist_id = :playlist_id
+ORDER BY smart_playlist_rules.rule_index;
@@ -0,0 +1,6 @@
+SELECT
+    smart_playlist_sort_terms.field_name
+    , smart_playlist_sort_terms.descending
+FROM smart_playlist_sort_terms
+WHERE smart_playlist_sort_terms.playlist_id = :playlist_id
+ORDER BY smart_playlist_sort_terms.sort_index;
@@ -0,0 +1,25 @@
+-- A playlist with a row here is smart, its audios are chosen by its rules instead of items.
+CREATE TABLE IF NOT EXISTS smart_playlists (
+    playlist_id INTEGER PRIMARY KEY REFERENCES playlists (playlist_id)
+    , match_all BOOLEAN NOT NULL -- Otherwise any rule is enough.
+    , track_limit INTEGER -- NULL for no limit.
+);
+
+CREATE TABLE IF NOT EXISTS smart_playlist_rules (
+    playlist_id INTEGER NOT NULL REFERENCES smart_playlists (playlist_id)
+    , rule_index INTEGER NOT NULL
+    , field_name VARCHAR(32) NOT NULL
+    , operator VARCHAR(32) NOT NULL
+    , negated BOOLEAN NOT NULL
+    , rule_value NOT NULL -- Text, integer or real stars for ratings, depending on the field.
+    , high_value -- Upper end of between rules, NULL otherwise.
+    , PRIMARY KEY (playlist_id, rule_index)
+);
+
+CREATE TABLE IF NOT EXISTS smart_playlist_sort_terms (
+    playlist_id INTEGER NOT NULL REFERENCES smart_playlists (playlist_id)
+    , sort_index INTEGER NOT NULL
+    , field_name VARCHAR(32) NOT NULL
+    , descending BOOLEAN NOT NULL
+    , PRIMARY KEY (playlist_id, sort_index)
+);
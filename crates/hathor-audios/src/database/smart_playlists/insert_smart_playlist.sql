@@ -0,0 +1,2 @@
+INSERT OR REPLACE INTO smart_playlists (playlist_id, match_all, track_limit)
+VALUES (:playlist_id, :match_all, :track_limit);
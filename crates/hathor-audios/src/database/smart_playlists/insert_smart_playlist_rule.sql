@@ -0,0 +1,12 @@
+INSERT INTO smart_playlist_rules (
+    playlist_id, rule_index, field_name, operator, negated, rule_value, high_value
+)
+VALUES (
+    :playlist_id
+    , :rule_index
+    , :field_name
+    , :operator
+    , :negated
+    , :rule_value
+    , :high_value
+);
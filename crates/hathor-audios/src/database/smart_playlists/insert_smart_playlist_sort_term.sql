@@ -0,0 +1,4 @@
+INSERT INTO smart_playlist_sort_terms (
+    playlist_id, sort_index, field_name, descending
+)
+VALUES (:playlist_id, :sort_index, :field_name, :descending);
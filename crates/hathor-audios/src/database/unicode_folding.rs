@@ -0,0 +1,77 @@
+use caseless::default_case_fold_str;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Name of the SQL function wrapping [fold_text], registered on every Hathor connection.
+pub(crate) const FOLD_TEXT_FUNCTION: &str = "FOLD_TEXT";
+
+/// Folds text for case and diacritic insensitive matching,
+/// so "Björk" matches "bjork" and "Straße" matches "STRASSE".
+///
+/// Applies full Unicode case folding, then NFKD, then strips combining marks.
+/// Case folding goes first as it can produce new combining marks (e.g. "İ").
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::database::unicode_folding::fold_text;
+///
+/// assert_eq!(fold_text("SIGUR RÓS"), fold_text("Sigur Ros"));
+pub fn fold_text(text: &str) -> String {
+    default_case_fold_str(text)
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect()
+}
+
+/// Registers `FOLD_TEXT(text)` on the connection, NULL in gives NULL out.
+pub(crate) fn register_fold_text_function(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        FOLD_TEXT_FUNCTION,
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|text| fold_text(&text))),
+    )
+}
+
+#[cfg(test)]
+mod unicode_folding_tests {
+    use super::{fold_text, register_fold_text_function};
+    use rstest::rstest;
+    use rusqlite::Connection;
+
+    #[rstest]
+    #[case("Björk", "bjork")]
+    #[case("SIGUR RÓS", "sigur ros")]
+    #[case("Straße", "strasse")]
+    #[case("İstanbul", "istanbul")]
+    #[case("ΣΊΣΥΦΟΣ", "σισυφοσ")]
+    #[case("ﬁve", "five")]
+    #[case("Ｍｏｇｗａｉ", "mogwai")]
+    #[case("Emily Styler ✕", "emily styler ✕")]
+    fn test_fold_text(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(fold_text(text), expected);
+    }
+
+    #[test]
+    fn test_fold_text_sql_function() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_fold_text_function(&conn).unwrap();
+        let (folded, null) = conn
+            .query_row(
+                "SELECT FOLD_TEXT('Sigur Rós'), FOLD_TEXT(NULL)",
+                (),
+                |row| {
+                    Ok((
+                        row.get::<usize, String>(0)?,
+                        row.get::<usize, Option<String>>(1)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(folded, "sigur ros");
+        assert_eq!(null, None);
+    }
+}
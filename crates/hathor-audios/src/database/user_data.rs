@@ -0,0 +1,425 @@
+//! Ratings, favourites and tags set by the user, stored apart from the audios' own tags.
+//!
+//! All of them are also columns of library listings,
+//! see [Field::Rating](super::query::Field::Rating) and the fields after it.
+
+pub mod rating_tags;
+
+use blake3::Hash;
+use rusqlite::{named_params, Connection};
+use std::error::Error;
+
+/// A star rating from 0 to 5 in half stars.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::database::user_data::Rating;
+///
+/// let rating = Rating::from_stars(3.5).unwrap();
+/// assert_eq!(rating.half_stars(), 7);
+/// assert!(Rating::from_stars(5.5).is_none());
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub struct Rating(u8);
+
+impl Rating {
+    pub const MAX_HALF_STARS: u8 = 10;
+
+    /// None if over [Rating::MAX_HALF_STARS].
+    pub fn from_half_stars(half_stars: u8) -> Option<Rating> {
+        (half_stars <= Rating::MAX_HALF_STARS).then_some(Rating(half_stars))
+    }
+
+    /// Rounds to the nearest half star, None if outside 0 to 5.
+    pub fn from_stars(stars: f64) -> Option<Rating> {
+        if !(0.0..=5.0).contains(&stars) {
+            return None;
+        }
+        Rating::from_half_stars((stars * 2.0).round() as u8)
+    }
+
+    pub fn half_stars(&self) -> u8 {
+        self.0
+    }
+
+    pub fn stars(&self) -> f64 {
+        f64::from(self.0) / 2.0
+    }
+}
+
+/// Everything the user has set on one audio.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct UserData {
+    /// None if unrated, which differs from a rating of 0 stars.
+    pub rating: Option<Rating>,
+    pub favourite: bool,
+    /// In name order.
+    pub tags: Vec<String>,
+}
+
+/// A tag and how many audios have it.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct TagCount {
+    pub tag: String,
+    pub audio_count: u32,
+}
+
+/// Rates an audio, replacing any previous rating.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `hash` - Hash of the audio.
+/// * `rating` - The new rating.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::{set_rating, Rating};
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// set_rating(&mut conn, &hash, Rating::from_stars(4.5).unwrap());
+pub fn set_rating(
+    conn: &mut Connection,
+    hash: &Hash,
+    rating: Rating,
+) -> Result<(), Box<dyn Error>> {
+    conn.execute(
+        include_str!("user_data/set_rating.sql"),
+        named_params! {
+            ":file_hash": hash.to_string(),
+            ":half_stars": rating.half_stars(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Removes the rating of an audio, leaving it unrated.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `hash` - Hash of the audio.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::clear_rating;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// clear_rating(&mut conn, &hash);
+pub fn clear_rating(conn: &mut Connection, hash: &Hash) -> Result<(), Box<dyn Error>> {
+    conn.execute(
+        include_str!("user_data/clear_rating.sql"),
+        named_params! { ":file_hash": hash.to_string() },
+    )?;
+    Ok(())
+}
+
+/// Adds an audio to or removes it from the user's favourites.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `hash` - Hash of the audio.
+/// * `favourite` - Whether the audio is a favourite.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::set_favourite;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// set_favourite(&mut conn, &hash, true);
+pub fn set_favourite(
+    conn: &mut Connection,
+    hash: &Hash,
+    favourite: bool,
+) -> Result<(), Box<dyn Error>> {
+    let sql = if favourite {
+        include_str!("user_data/set_favourite.sql")
+    } else {
+        include_str!("user_data/clear_favourite.sql")
+    };
+    conn.execute(sql, named_params! { ":file_hash": hash.to_string() })?;
+    Ok(())
+}
+
+/// Tags an audio. Tags are trimmed, and adding one the audio has in any case does nothing.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `hash` - Hash of the audio.
+/// * `tag` - The tag, it must not be blank.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::add_tag;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// add_tag(&mut conn, &hash, "late night");
+pub fn add_tag(conn: &mut Connection, hash: &Hash, tag: &str) -> Result<(), Box<dyn Error>> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("tags must not be blank".into());
+    }
+    conn.execute(
+        include_str!("user_data/add_tag.sql"),
+        named_params! {
+            ":file_hash": hash.to_string(),
+            ":tag": tag,
+        },
+    )?;
+    Ok(())
+}
+
+/// Removes a tag from an audio, ignoring case.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `hash` - Hash of the audio.
+/// * `tag` - The tag to remove.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::remove_tag;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// remove_tag(&mut conn, &hash, "Late Night");
+pub fn remove_tag(conn: &mut Connection, hash: &Hash, tag: &str) -> Result<(), Box<dyn Error>> {
+    conn.execute(
+        include_str!("user_data/remove_tag.sql"),
+        named_params! {
+            ":file_hash": hash.to_string(),
+            ":tag": tag.trim(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Retvieve the rating, favourite flag and tags of an audio.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `hash` - Hash of the audio.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::get_user_data;
+/// use blake3::Hash;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let hash = Hash::from_hex(format!("{:064}", 0)).unwrap();
+/// let tags = get_user_data(&mut conn, &hash).unwrap().tags;
+pub fn get_user_data(conn: &mut Connection, hash: &Hash) -> Result<UserData, Box<dyn Error>> {
+    let (half_stars, favourite) = conn.query_row(
+        include_str!("user_data/get_user_data.sql"),
+        named_params! { ":file_hash": hash.to_string() },
+        |row| {
+            Ok((
+                row.get::<&str, Option<u8>>("half_stars")?,
+                row.get("favourite")?,
+            ))
+        },
+    )?;
+    let tags = conn
+        .prepare(include_str!("user_data/get_tags.sql"))?
+        .query_map(named_params! { ":file_hash": hash.to_string() }, |row| {
+            row.get("tag")
+        })?
+        .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+    Ok(UserData {
+        rating: half_stars.and_then(Rating::from_half_stars),
+        favourite,
+        tags,
+    })
+}
+
+/// Retvieve every tag in use, in name order.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::list_tags;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// for tag in list_tags(&mut conn).unwrap() {
+///     println!("{} ({})", tag.tag, tag.audio_count);
+/// }
+pub fn list_tags(conn: &mut Connection) -> Result<Vec<TagCount>, Box<dyn Error>> {
+    let tags = conn
+        .prepare(include_str!("user_data/list_tags.sql"))?
+        .query_map((), |row| {
+            Ok(TagCount {
+                tag: row.get("tag")?,
+                audio_count: row.get("audio_count")?,
+            })
+        })?
+        .collect::<Result<Vec<TagCount>, rusqlite::Error>>()?;
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod test_user_data_operations {
+    use super::{
+        add_tag, clear_rating, get_user_data, list_tags, remove_tag, set_favourite, set_rating,
+        Rating, TagCount, UserData,
+    };
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::database::query::{get_audios_by_query, Query};
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::{fixture, rstest};
+
+    #[rstest]
+    #[case(0.0, Some(0))]
+    #[case(2.4, Some(5))]
+    #[case(2.75, Some(6))]
+    #[case(5.0, Some(10))]
+    #[case(-0.5, None)]
+    #[case(5.5, None)]
+    fn test_rating_from_stars(#[case] stars: f64, #[case] half_stars: Option<u8>) {
+        assert_eq!(
+            Rating::from_stars(stars).map(|rating| rating.half_stars()),
+            half_stars
+        );
+    }
+
+    /// Roads rated 5 stars and tagged chill, Sour Times rated 0 stars and a favourite,
+    /// Glory Box untouched.
+    #[fixture]
+    fn library(mut temp_audios_context: TestInMemoryDBContext) -> TestInMemoryDBContext {
+        for (n, title) in ["Roads", "Sour Times", "Glory Box"].into_iter().enumerate() {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let hashes = temp_audios_context
+            .audios
+            .iter()
+            .map(|audio| audio.file_hash)
+            .collect::<Vec<Hash>>();
+        let conn = &mut temp_audios_context.connection;
+        set_rating(conn, &hashes[0], Rating::from_stars(5.0).unwrap()).unwrap();
+        add_tag(conn, &hashes[0], "Chill").unwrap();
+        add_tag(conn, &hashes[0], " late night ").unwrap();
+        set_rating(conn, &hashes[1], Rating::from_stars(0.0).unwrap()).unwrap();
+        set_favourite(conn, &hashes[1], true).unwrap();
+        add_tag(conn, &hashes[1], "chill").unwrap();
+        temp_audios_context
+    }
+
+    #[rstest]
+    fn test_get_user_data(mut library: TestInMemoryDBContext) {
+        let hashes = library
+            .audios
+            .iter()
+            .map(|audio| audio.file_hash)
+            .collect::<Vec<Hash>>();
+        let conn = &mut library.connection;
+        assert_eq!(
+            get_user_data(conn, &hashes[0]).unwrap(),
+            UserData {
+                rating: Rating::from_half_stars(10),
+                favourite: false,
+                tags: vec![String::from("Chill"), String::from("late night")],
+            }
+        );
+        assert_eq!(
+            get_user_data(conn, &hashes[2]).unwrap(),
+            UserData::default()
+        );
+
+        // Tags are unique ignoring case, and removed ignoring case.
+        add_tag(conn, &hashes[0], "CHILL").unwrap();
+        remove_tag(conn, &hashes[0], "LATE NIGHT").unwrap();
+        assert!(add_tag(conn, &hashes[0], "  ").is_err());
+        clear_rating(conn, &hashes[1]).unwrap();
+        set_favourite(conn, &hashes[1], false).unwrap();
+        assert_eq!(get_user_data(conn, &hashes[0]).unwrap().tags, ["Chill"]);
+        assert_eq!(
+            get_user_data(conn, &hashes[1]).unwrap(),
+            UserData {
+                tags: vec![String::from("chill")],
+                ..UserData::default()
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_list_tags(mut library: TestInMemoryDBContext) {
+        assert_eq!(
+            list_tags(&mut library.connection).unwrap(),
+            [
+                TagCount {
+                    tag: String::from("Chill"),
+                    audio_count: 2,
+                },
+                TagCount {
+                    tag: String::from("late night"),
+                    audio_count: 1,
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("sort:-rating", vec!["Roads", "Sour Times", "Glory Box"])]
+    #[case("rating:>=0 sort:title", vec!["Roads", "Sour Times"])]
+    #[case("rating:>=4.5", vec!["Roads"])]
+    #[case("rating:..2.5", vec!["Sour Times"])]
+    #[case("-rating:..2.5 sort:title", vec!["Glory Box", "Roads"])]
+    #[case("favourite:1", vec!["Sour Times"])]
+    #[case("tag:=chill sort:title", vec!["Roads", "Sour Times"])]
+    #[case("tag:nigh", vec!["Roads"])]
+    #[case("-tag:chill", vec!["Glory Box"])]
+    #[case("sort:-tag,title", vec!["Roads", "Sour Times", "Glory Box"])]
+    fn test_query_by_user_data(
+        mut library: TestInMemoryDBContext,
+        #[case] query: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        let query = Query::parse(query).unwrap();
+        let audios = get_audios_by_query(&mut library.connection, &query, &ListOptions::default())
+            .unwrap()
+            .items;
+        let titles = audios
+            .iter()
+            .map(|audio| audio.audio_title.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, expected);
+    }
+}
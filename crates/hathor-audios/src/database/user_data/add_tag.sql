@@ -0,0 +1,2 @@
+INSERT OR IGNORE INTO audio_tags (file_hash, tag)
+VALUES (:file_hash, :tag);
@@ -0,0 +1,2 @@
+DELETE FROM audio_favourites
+WHERE audio_favourites.file_hash = :file_hash;
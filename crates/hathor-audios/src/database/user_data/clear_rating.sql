@@ -0,0 +1,2 @@
+DELETE FROM audio_ratings
+WHERE audio_ratings.file_hash = :file_hash;
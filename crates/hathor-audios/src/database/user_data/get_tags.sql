@@ -0,0 +1,4 @@
+SELECT audio_tags.tag
+FROM audio_tags
+WHERE audio_tags.file_hash = :file_hash
+ORDER BY audio_tags.tag COLLATE NOCASE;
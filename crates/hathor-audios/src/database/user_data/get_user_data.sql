@@ -0,0 +1,8 @@
+SELECT
+    audio_ratings.half_stars
+    , audio_favourites.file_hash IS NOT NULL AS favourite
+FROM (SELECT :file_hash AS file_hash) AS audio
+    LEFT JOIN audio_ratings
+        ON audio.file_hash = audio_ratings.file_hash
+    LEFT JOIN audio_favourites
+        ON audio.file_hash = audio_favourites.file_hash;
@@ -0,0 +1,24 @@
+-- Per-audio data set by the user rather than read from the files' tags.
+-- Keyed by file hash, so it follows audios when they move.
+CREATE TABLE IF NOT EXISTS audio_ratings (
+    file_hash CHAR(64) PRIMARY KEY
+    , half_stars INT(8) NOT NULL CHECK (half_stars BETWEEN 0 AND 10)
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS audio_favourites (
+    file_hash CHAR(64) PRIMARY KEY
+    , favourited_at INT(64) NOT NULL -- Unix seconds.
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS audio_tags (
+    file_hash CHAR(64) NOT NULL
+    , tag VARCHAR(256) NOT NULL
+    , PRIMARY KEY (file_hash, tag)
+) WITHOUT ROWID;
+
+-- Tags are unique per audio ignoring case, the first spelling added is kept.
+CREATE UNIQUE INDEX IF NOT EXISTS audio_tags_nocase
+ON audio_tags (file_hash, tag COLLATE NOCASE);
+
+CREATE INDEX IF NOT EXISTS audio_tags_by_tag
+ON audio_tags (tag COLLATE NOCASE);
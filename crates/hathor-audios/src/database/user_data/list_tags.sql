@@ -0,0 +1,7 @@
+-- Every tag in use and how many audios have it, spellings differing in case count as one.
+SELECT
+    MIN(audio_tags.tag) AS tag
+    , COUNT(*) AS audio_count
+FROM audio_tags
+GROUP BY audio_tags.tag COLLATE NOCASE
+ORDER BY audio_tags.tag COLLATE NOCASE;
@@ -0,0 +1,336 @@
+//! Ratings in the audio files' own tags, so they carry over from and to other players.
+//!
+//! ID3v2 files (MP3, WAV and AIFF) hold them in `POPM` frames and a `FMPS_Rating` `TXXX` frame.
+//! Other formats are read from a `FMPS_RATING` comment, but can't be written.
+
+use crate::audio::AudioFile;
+use crate::database::audio_files::{count_audio_locations, rehash_audio};
+use crate::database::user_data::{get_user_data, set_rating, Rating};
+use blake3::Hash;
+use id3::frame::{ExtendedText, Popularimeter};
+use id3::{Tag, TagLike, Version};
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, Tag as SymphoniaTag};
+use symphonia::core::probe::Hint;
+
+/// `POPM` rating bytes for each half star, the scale MediaMonkey writes and most players read.
+/// 0 means unrated in `POPM`, so a rating of 0 stars can't be written there.
+const POPM_BY_HALF_STARS: [u8; 11] = [0, 13, 1, 54, 64, 118, 128, 186, 196, 242, 255];
+
+/// Identifies the `POPM` frame Hathor adds when a file has none.
+const POPM_USER: &str = "Hathor";
+
+const FMPS_RATING: &str = "FMPS_Rating";
+
+/// Audio types whose ratings are read and written as ID3v2 frames.
+const ID3_TYPES: &[&str] = &["aif", "aiff", "mp3", "wav"];
+
+/// Reads the rating from an audio file's tags, None if it has none.
+/// `FMPS_Rating` is preferred, as it can hold 0 stars.
+///
+/// # Arguments
+///
+/// * `audio_path` - Path to the audio file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::rating_tags::read_rating_tag;
+/// use std::path::Path;
+///
+/// let rating = read_rating_tag(Path::new("/home/me/music/track.mp3")).unwrap();
+pub fn read_rating_tag(audio_path: &Path) -> Result<Option<Rating>, Box<dyn Error>> {
+    if !ID3_TYPES.contains(&extension(audio_path).as_str()) {
+        return read_fmps_comment(audio_path);
+    }
+    let Some(tag) = id3::no_tag_ok(Tag::read_from_path(audio_path))? else {
+        return Ok(None);
+    };
+    let fmps_rating = tag
+        .extended_texts()
+        .find(|text| text.description.eq_ignore_ascii_case(FMPS_RATING))
+        .and_then(|text| rating_from_fmps(&text.value));
+    let popm_rating = || {
+        tag.frames()
+            .filter_map(|frame| frame.content().popularimeter())
+            .find_map(|popm| rating_from_popm(popm.rating))
+    };
+    Ok(fmps_rating.or_else(popm_rating))
+}
+
+/// Writes a rating into an ID3v2 tagged audio file, or removes it for None.
+/// Every `POPM` frame is updated, keeping their play counters.
+///
+/// # Arguments
+///
+/// * `audio_path` - Path to the audio file.
+/// * `rating` - The rating to write.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_data::rating_tags::write_rating_tag;
+/// use hathor_audios::database::user_data::Rating;
+/// use std::path::Path;
+///
+/// let rating = Rating::from_stars(4.0);
+/// write_rating_tag(Path::new("/home/me/music/track.mp3"), rating);
+pub fn write_rating_tag(audio_path: &Path, rating: Option<Rating>) -> Result<(), Box<dyn Error>> {
+    if !ID3_TYPES.contains(&extension(audio_path).as_str()) {
+        return Err(format!("can't write ratings to {}", audio_path.display()).into());
+    }
+    let existing_tag = id3::no_tag_ok(Tag::read_from_path(audio_path))?;
+    let version = existing_tag.as_ref().map_or(Version::Id3v24, Tag::version);
+    let mut tag = existing_tag.unwrap_or_default();
+
+    let popm_rating = rating.map_or(0, |rating| {
+        POPM_BY_HALF_STARS[usize::from(rating.half_stars())]
+    });
+    let mut popms = tag
+        .remove("POPM")
+        .into_iter()
+        .filter_map(|frame| frame.content().popularimeter().cloned())
+        .collect::<Vec<Popularimeter>>();
+    if popms.is_empty() && rating.is_some() {
+        popms.push(Popularimeter {
+            user: String::from(POPM_USER),
+            rating: 0,
+            counter: 0,
+        });
+    }
+    for popm in popms {
+        tag.add_frame(Popularimeter {
+            rating: popm_rating,
+            ..popm
+        });
+    }
+    tag.remove_extended_text(Some(FMPS_RATING), None);
+    if let Some(rating) = rating {
+        tag.add_frame(ExtendedText {
+            description: String::from(FMPS_RATING),
+            value: fmps_from_rating(rating),
+        });
+    }
+    tag.write_to_path(audio_path, version)?;
+    Ok(())
+}
+
+/// Sets an audio's rating in the DB from its file's tags.
+/// Audios whose files have no rating keep the one they have in the DB.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `audio` - The audio to import the rating of.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::audio::AudioFile;
+/// use hathor_audios::database::user_data::rating_tags::import_rating_tag;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let audio = AudioFile::from_file(Path::new("/home/me/music/track.mp3")).unwrap();
+/// let rating = import_rating_tag(&mut conn, &audio).unwrap();
+pub fn import_rating_tag(
+    conn: &mut Connection,
+    audio: &AudioFile,
+) -> Result<Option<Rating>, Box<dyn Error>> {
+    let rating = read_rating_tag(&audio.audio_path)?;
+    if let Some(rating) = rating {
+        set_rating(conn, &audio.file_hash, rating)?;
+    }
+    Ok(rating)
+}
+
+/// Writes an audio's rating in the DB to its file's tags, removing it there if it is unrated.
+/// As the file's contents change, the audio is moved over to the file's new hash,
+/// which is returned.
+/// CUE sheet tracks and audios stored at several paths are refused,
+/// as the change would reach other audios too.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `audio` - The audio to export the rating of.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::audio::AudioFile;
+/// use hathor_audios::database::audio_files::get_audio_by_path;
+/// use hathor_audios::database::user_data::rating_tags::export_rating_tag;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let audio_path = Path::new("/home/me/music/track.mp3");
+/// let audio = get_audio_by_path(&mut conn, audio_path).unwrap().unwrap();
+/// let new_hash = export_rating_tag(&mut conn, &audio).unwrap();
+pub fn export_rating_tag(conn: &mut Connection, audio: &AudioFile) -> Result<Hash, Box<dyn Error>> {
+    if !audio.start_offset.is_zero() || audio.end_offset.is_some() {
+        return Err("CUE sheet tracks share their file, so can't have ratings written".into());
+    }
+    if count_audio_locations(conn, &audio.file_hash)? > 1 {
+        return Err("audio is stored at several paths, so can't have ratings written".into());
+    }
+    let rating = get_user_data(conn, &audio.file_hash)?.rating;
+    write_rating_tag(&audio.audio_path, rating)?;
+    let new_hash = AudioFile::get_file_hash(&audio.audio_path)?;
+    if new_hash != audio.file_hash {
+        rehash_audio(conn, &audio.file_hash, &new_hash)?;
+    }
+    Ok(new_hash)
+}
+
+fn extension(audio_path: &Path) -> String {
+    audio_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Reads `FMPS_RATING` from the comments of formats without ID3v2 tags, e.g. FLAC and Ogg.
+fn read_fmps_comment(audio_path: &Path) -> Result<Option<Rating>, Box<dyn Error>> {
+    let mut hint = Hint::new();
+    hint.with_extension(&extension(audio_path));
+    let source = MediaSourceStream::new(Box::new(File::open(audio_path)?), Default::default());
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let find_rating = |tags: &[SymphoniaTag]| {
+        tags.iter()
+            .find(|tag| tag.key.eq_ignore_ascii_case(FMPS_RATING))
+            .and_then(|tag| rating_from_fmps(&tag.value.to_string()))
+    };
+    if let Some(rating) = probed
+        .format
+        .metadata()
+        .current()
+        .and_then(|m| find_rating(m.tags()))
+    {
+        return Ok(Some(rating));
+    }
+    Ok(probed
+        .metadata
+        .get()
+        .as_ref()
+        .and_then(|metadata| metadata.current())
+        .and_then(|m| find_rating(m.tags())))
+}
+
+/// The nearest half star on the [POPM_BY_HALF_STARS] scale, None for 0.
+fn rating_from_popm(popm_rating: u8) -> Option<Rating> {
+    if popm_rating == 0 {
+        return None;
+    }
+    let half_stars = (1..POPM_BY_HALF_STARS.len())
+        .min_by_key(|&half_stars| POPM_BY_HALF_STARS[half_stars].abs_diff(popm_rating))?;
+    Rating::from_half_stars(half_stars as u8)
+}
+
+/// `FMPS_Rating` is a fraction from 0.0 to 1.0.
+fn rating_from_fmps(value: &str) -> Option<Rating> {
+    let fraction = value.trim().parse::<f64>().ok()?;
+    Rating::from_stars(fraction * 5.0)
+}
+
+fn fmps_from_rating(rating: Rating) -> String {
+    (f64::from(rating.half_stars()) / f64::from(Rating::MAX_HALF_STARS)).to_string()
+}
+
+#[cfg(test)]
+mod rating_tags_tests {
+    use super::{export_rating_tag, import_rating_tag, rating_from_popm, read_rating_tag};
+    use crate::audio::AudioFile;
+    use crate::database::audio_files::{get_audio_by_path, insert_audios};
+    use crate::database::listing::ListOptions;
+    use crate::database::playlists::{get_playlist_items, insert_audios_into_playlist};
+    use crate::database::user_data::{clear_rating, get_user_data, set_rating, Rating};
+    use crate::fixtures::{temp_audios_context, TestInMemoryDBContext};
+    use rstest::rstest;
+    use std::{fs, slice};
+
+    const TEST_AUDIO: &str = r"/../../test_media_files/audio/albums/album/test.mp3";
+
+    #[rstest]
+    #[case(0, None)]
+    #[case(1, Some(2))]
+    #[case(13, Some(1))]
+    #[case(64, Some(4))]
+    #[case(100, Some(5))]
+    #[case(196, Some(8))]
+    #[case(255, Some(10))]
+    fn test_rating_from_popm(#[case] popm_rating: u8, #[case] half_stars: Option<u8>) {
+        assert_eq!(
+            rating_from_popm(popm_rating).map(|rating| rating.half_stars()),
+            half_stars
+        );
+    }
+
+    /// Export a rating to a copy of the test audio, check the audio follows the file's new hash,
+    /// then import it back.
+    #[rstest]
+    #[case(0)]
+    #[case(7)]
+    fn test_export_then_import_rating(
+        mut temp_audios_context: TestInMemoryDBContext,
+        #[case] half_stars: u8,
+    ) {
+        let audio_path = temp_audios_context.temp_audio_dir.join("rated.mp3");
+        fs::copy(
+            env!("CARGO_MANIFEST_DIR").to_owned() + TEST_AUDIO,
+            &audio_path,
+        )
+        .unwrap();
+        let audio = AudioFile::from_file(&audio_path).unwrap();
+        let conn = &mut temp_audios_context.connection;
+        insert_audios(conn, slice::from_ref(&audio)).unwrap();
+        insert_audios_into_playlist(conn, "Rated", slice::from_ref(&audio)).unwrap();
+        let rating = Rating::from_half_stars(half_stars).unwrap();
+        set_rating(conn, &audio.file_hash, rating).unwrap();
+
+        let new_hash = export_rating_tag(conn, &audio).unwrap();
+        assert_ne!(new_hash, audio.file_hash);
+        assert_eq!(
+            new_hash,
+            AudioFile::from_file(&audio_path).unwrap().file_hash
+        );
+        assert_eq!(read_rating_tag(&audio_path).unwrap(), Some(rating));
+        let moved = get_audio_by_path(conn, &audio_path).unwrap().unwrap();
+        assert_eq!(moved.file_hash, new_hash);
+        assert_eq!(get_user_data(conn, &new_hash).unwrap().rating, Some(rating));
+        let items = get_playlist_items(conn, "Rated", &ListOptions::default())
+            .unwrap()
+            .items;
+        assert_eq!(items[0].audio.as_ref().unwrap().file_hash, new_hash);
+
+        clear_rating(conn, &new_hash).unwrap();
+        assert_eq!(import_rating_tag(conn, &moved).unwrap(), Some(rating));
+        assert_eq!(get_user_data(conn, &new_hash).unwrap().rating, Some(rating));
+
+        // Exporting no rating removes it from the file.
+        clear_rating(conn, &new_hash).unwrap();
+        export_rating_tag(conn, &moved).unwrap();
+        assert_eq!(read_rating_tag(&audio_path).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_export_rating_of_cue_track_is_refused(mut temp_audios_context: TestInMemoryDBContext) {
+        let audio = AudioFile {
+            end_offset: Some(time::Duration::seconds(5)),
+            ..AudioFile::default()
+        };
+        assert!(export_rating_tag(&mut temp_audios_context.connection, &audio).is_err());
+    }
+}
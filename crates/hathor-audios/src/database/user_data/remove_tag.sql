@@ -0,0 +1,4 @@
+DELETE FROM audio_tags
+WHERE
+    audio_tags.file_hash = :file_hash
+    AND audio_tags.tag = :tag COLLATE NOCASE;
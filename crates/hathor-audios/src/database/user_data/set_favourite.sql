@@ -0,0 +1,2 @@
+INSERT OR IGNORE INTO audio_favourites (file_hash, favourited_at)
+VALUES (:file_hash, UNIXEPOCH());
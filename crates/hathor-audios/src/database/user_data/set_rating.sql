@@ -0,0 +1,3 @@
+INSERT INTO audio_ratings (file_hash, half_stars)
+VALUES (:file_hash, :half_stars)
+ON CONFLICT (file_hash) DO UPDATE SET half_stars = excluded.half_stars;
@@ -1 +1,495 @@
+use crate::database::os_paths::{path_from_bytes, path_to_bytes, path_to_display};
+use crate::database::stored_path_to_absolute;
+use rusqlite::{named_params, Connection, ErrorCode, ToSql};
+use std::error::Error;
+use std::path::{Path, PathBuf};
 
+/// A library root folder. Audio paths under a root are stored relative to it,
+/// so the whole library can be moved by relocating the root.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct UserMediaFolder {
+    pub folder_id: i64,
+    pub folder_path: PathBuf,
+}
+
+/// Adds a media root folder to the DB and returns its ID.
+/// Adding a folder that already exists returns the existing ID.
+/// Paths are stored relative to the deepest root containing them, so audio files already
+/// stored under the folder are re-stored relative to it, unless a root inside it holds them.
+/// Covers are re-stored the same way, apart from their audios.
+/// Fails if an audio file would then be stored twice at the same location.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `folder_path` - Path to the root folder, must exist.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_media_folders::add_user_media_folder;
+/// use std::path::Path;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let folder_id = add_user_media_folder(&mut conn, Path::new("/mnt/music"));
+pub fn add_user_media_folder(
+    conn: &mut Connection,
+    folder_path: &Path,
+) -> Result<i64, Box<dyn Error>> {
+    let folder_path = folder_path.canonicalize()?;
+    let transaction = conn.transaction()?;
+    transaction.execute(
+        include_str!("user_media_folders/insert_user_media_folder.sql"),
+        named_params! {
+            ":folder_path": path_to_bytes(&folder_path),
+            ":folder_path_display": path_to_display(&folder_path),
+        },
+    )?;
+    let folder_id = transaction.query_row(
+        include_str!("user_media_folders/get_user_media_folder_id.sql"),
+        named_params! {":folder_path": path_to_bytes(&folder_path)},
+        |row| row.get::<usize, i64>(0),
+    )?;
+    rebase_audio_files(&transaction, folder_id, &folder_path)?;
+    transaction.commit()?;
+    Ok(folder_id)
+}
+
+/// Retrieve all media root folders, in the order they were added.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to read from.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_media_folders::get_user_media_folders;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let folders = get_user_media_folders(&mut conn);
+pub fn get_user_media_folders(
+    conn: &mut Connection,
+) -> Result<Vec<UserMediaFolder>, Box<dyn Error>> {
+    query_user_media_folders(conn)
+}
+
+/// Remaps a media root to a new location, e.g. after the drive was mounted elsewhere.
+/// Every audio file stored relative to the root follows it without rescanning.
+/// Roots nested inside it are roots of their own, so they have to be relocated separately.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to update.
+/// * `folder_id` - ID of the root to move.
+/// * `new_folder_path` - Where the root now lives. It is canonicalized if it exists.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::database::user_media_folders::relocate_user_media_folder;
+/// use std::path::Path;
+/// use rusqlite::Connection;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// relocate_user_media_folder(&mut conn, 1, Path::new("/media/nas/music"));
+pub fn relocate_user_media_folder(
+    conn: &mut Connection,
+    folder_id: i64,
+    new_folder_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let new_folder_path = new_folder_path
+        .canonicalize()
+        .unwrap_or_else(|_| new_folder_path.to_path_buf());
+    let transaction = conn.transaction()?;
+    let updated = transaction.execute(
+        include_str!("user_media_folders/relocate_user_media_folder.sql"),
+        named_params! {
+            ":folder_id": folder_id,
+            ":folder_path": path_to_bytes(&new_folder_path),
+            ":folder_path_display": path_to_display(&new_folder_path),
+        },
+    )?;
+    if updated == 0 {
+        return Err(format!("no user media folder with ID {}", folder_id).into());
+    }
+    update_display_paths(&transaction, folder_id, &new_folder_path)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+pub(crate) fn query_user_media_folders(
+    conn: &Connection,
+) -> Result<Vec<UserMediaFolder>, Box<dyn Error>> {
+    Ok(conn
+        .prepare(include_str!(
+            "user_media_folders/get_user_media_folders.sql"
+        ))?
+        .query_map((), |row| {
+            Ok(UserMediaFolder {
+                folder_id: row.get(0)?,
+                folder_path: path_from_bytes(row.get(1)?),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Splits an absolute path into the deepest root containing it,
+/// and the path relative to that root.
+/// If no root contains the path it is returned unchanged with no root.
+pub(crate) fn split_at_user_media_folder<'a>(
+    folders: &'a [UserMediaFolder],
+    path: &Path,
+) -> (Option<&'a UserMediaFolder>, PathBuf) {
+    folders
+        .iter()
+        .filter_map(|f| {
+            path.strip_prefix(&f.folder_path)
+                .ok()
+                .map(|relative| (f, relative))
+        })
+        .max_by_key(|(f, _)| f.folder_path.components().count())
+        .map_or((None, path.to_path_buf()), |(f, relative)| {
+            (Some(f), relative.to_path_buf())
+        })
+}
+
+/// Re-stores the audio and cover paths under `folder_path` relative to it,
+/// except those stored relative to a root inside it already.
+/// Fails if an audio file would then be stored twice at the same location.
+pub(crate) fn rebase_audio_files(
+    conn: &Connection,
+    folder_id: i64,
+    folder_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let audio_file_locations = conn
+        .prepare(include_str!(
+            "user_media_folders/get_audio_file_locations.sql"
+        ))?
+        .query_map(named_params! {":folder_id": folder_id}, |row| {
+            Ok((
+                row.get::<usize, i64>(0)?,
+                row.get::<usize, Option<Vec<u8>>>(1)?,
+                row.get::<usize, Vec<u8>>(2)?,
+                row.get::<usize, Option<Vec<u8>>>(3)?,
+                row.get::<usize, Option<Vec<u8>>>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rebase_audio =
+        conn.prepare_cached(include_str!("user_media_folders/rebase_audio_file.sql"))?;
+    let mut rebase_img =
+        conn.prepare_cached(include_str!("user_media_folders/rebase_img_file.sql"))?;
+    for (audio_file_id, root_path, audio_path, img_root_path, img_path) in audio_file_locations {
+        if let Some(relative_audio_path) = rebased_path(folder_path, root_path, audio_path) {
+            let params = named_params! {
+                ":audio_file_id": audio_file_id,
+                ":folder_id": folder_id,
+                ":audio_path": path_to_bytes(&relative_audio_path),
+            };
+            if let Err(e) = rebase_audio.execute(params) {
+                return Err(match e.sqlite_error_code() {
+                    Some(ErrorCode::ConstraintViolation) => format!(
+                        "audio file {} is stored twice under {}",
+                        relative_audio_path.display(),
+                        folder_path.display()
+                    )
+                    .into(),
+                    _ => e.into(),
+                });
+            }
+        }
+        let Some(img_path) = img_path else {
+            continue;
+        };
+        if let Some(relative_img_path) = rebased_path(folder_path, img_root_path, img_path) {
+            rebase_img.execute(named_params! {
+                ":audio_file_id": audio_file_id,
+                ":folder_id": folder_id,
+                ":img_path": path_to_bytes(&relative_img_path),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// A stored path relative to `folder_path`, None if it isn't under the folder
+/// or is stored relative to a root inside it.
+fn rebased_path(folder_path: &Path, root_path: Option<Vec<u8>>, path: Vec<u8>) -> Option<PathBuf> {
+    if root_path
+        .clone()
+        .map(path_from_bytes)
+        .is_some_and(|root| root.starts_with(folder_path))
+    {
+        return None;
+    }
+    stored_path_to_absolute(root_path, path)
+        .strip_prefix(folder_path)
+        .ok()
+        .map(Path::to_path_buf)
+}
+
+/// Rewrites the display paths of the audio files and covers stored relative to a root,
+/// as they show the absolute paths.
+fn update_display_paths(
+    conn: &Connection,
+    folder_id: i64,
+    folder_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let to_display = |path| path_to_display(&folder_path.join(path_from_bytes(path)));
+    for (select, update, display_param) in [
+        (
+            include_str!("user_media_folders/get_user_media_folder_audio_files.sql"),
+            include_str!("user_media_folders/set_audio_file_display_path.sql"),
+            ":audio_path_display",
+        ),
+        (
+            include_str!("user_media_folders/get_user_media_folder_img_files.sql"),
+            include_str!("user_media_folders/set_img_file_display_path.sql"),
+            ":img_path_display",
+        ),
+    ] {
+        let paths = conn
+            .prepare(select)?
+            .query_map(named_params! {":folder_id": folder_id}, |row| {
+                Ok((row.get::<usize, i64>(0)?, row.get::<usize, Vec<u8>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut statement = conn.prepare_cached(update)?;
+        for (audio_file_id, path) in paths {
+            statement.execute(&[
+                (":audio_file_id", &audio_file_id as &dyn ToSql),
+                (display_param, &to_display(path)),
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_user_media_folders_operations {
+    use super::{
+        add_user_media_folder, get_user_media_folders, relocate_user_media_folder,
+        split_at_user_media_folder, UserMediaFolder,
+    };
+    use crate::audio::AudioFile;
+    use crate::database::audio_files::{get_audio_by_hash, insert_audios};
+    use crate::database::os_paths::{path_from_bytes, path_to_bytes};
+    use crate::fixtures::{playlist_db_in_memory, temp_audios_context, TestInMemoryDBContext};
+    use rstest::rstest;
+    use std::path::{Path, PathBuf};
+
+    fn stored_location(context: &TestInMemoryDBContext, n: usize) -> (Option<i64>, PathBuf) {
+        context
+            .connection
+            .query_row(
+                r"SELECT folder_id, audio_path FROM audio_files WHERE file_hash = :file_hash",
+                rusqlite::named_params! {":file_hash": context.audios[n].file_hash.to_string()},
+                |row| Ok((row.get(0)?, path_from_bytes(row.get(1)?))),
+            )
+            .unwrap()
+    }
+
+    #[rstest]
+    fn test_add_user_media_folder_is_idempotent(mut temp_audios_context: TestInMemoryDBContext) {
+        let folder = temp_audios_context.temp_audio_dir.clone();
+        let first_id = add_user_media_folder(&mut temp_audios_context.connection, &folder).unwrap();
+        let second_id =
+            add_user_media_folder(&mut temp_audios_context.connection, &folder).unwrap();
+        assert_eq!(first_id, second_id);
+        assert_eq!(
+            get_user_media_folders(&mut temp_audios_context.connection).unwrap(),
+            vec![UserMediaFolder {
+                folder_id: first_id,
+                folder_path: folder.canonicalize().unwrap(),
+            }]
+        );
+    }
+
+    /// Audios inserted after their root was added are stored relative to it.
+    #[rstest]
+    fn test_insert_audios_under_root_stored_relative(
+        mut temp_audios_context: TestInMemoryDBContext,
+    ) {
+        let folder = temp_audios_context.temp_audio_dir.clone();
+        let folder_id =
+            add_user_media_folder(&mut temp_audios_context.connection, &folder).unwrap();
+        let mut audio_path = folder.canonicalize().unwrap();
+        audio_path.push("relative.mp3");
+        std::fs::File::create(&audio_path).unwrap();
+        temp_audios_context.audios.push(AudioFile {
+            audio_path,
+            ..AudioFile::default()
+        });
+        let audios = temp_audios_context.audios.clone();
+        insert_audios(&mut temp_audios_context.connection, &audios).unwrap();
+
+        assert_eq!(
+            stored_location(&temp_audios_context, 0),
+            (Some(folder_id), PathBuf::from("relative.mp3"))
+        );
+        assert_eq!(
+            get_audio_by_hash(&mut temp_audios_context.connection, &audios[0].file_hash),
+            audios[0]
+        );
+    }
+
+    /// Audios inserted before their root existed are rebased when the root is added,
+    /// and follow the root when it is relocated.
+    #[rstest]
+    fn test_relocate_user_media_folder(mut playlist_db_in_memory: TestInMemoryDBContext) {
+        let folder = playlist_db_in_memory.temp_audio_dir.clone();
+        let folder_id =
+            add_user_media_folder(&mut playlist_db_in_memory.connection, &folder).unwrap();
+        assert_eq!(
+            stored_location(&playlist_db_in_memory, 0),
+            (Some(folder_id), PathBuf::from("0.mp3"))
+        );
+
+        let new_root = Path::new("/mnt/hathor_relocated_library");
+        relocate_user_media_folder(&mut playlist_db_in_memory.connection, folder_id, new_root)
+            .unwrap();
+        let audio = get_audio_by_hash(
+            &mut playlist_db_in_memory.connection,
+            &playlist_db_in_memory.audios[0].file_hash,
+        );
+        assert_eq!(audio.audio_path, new_root.join("0.mp3"));
+        assert_eq!(audio.img_path, Some(new_root.join("0.png")));
+        let display_paths = playlist_db_in_memory
+            .connection
+            .query_row(
+                r"SELECT audio_path_display, img_path_display FROM audio_files
+                WHERE file_hash = :file_hash",
+                rusqlite::named_params! {":file_hash": audio.file_hash.to_string()},
+                |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            display_paths,
+            (
+                String::from("/mnt/hathor_relocated_library/0.mp3"),
+                String::from("/mnt/hathor_relocated_library/0.png")
+            )
+        );
+    }
+
+    /// Audios are stored relative to the deepest root containing them,
+    /// whichever order the roots are added in.
+    #[rstest]
+    fn test_add_nested_user_media_folder(mut temp_audios_context: TestInMemoryDBContext) {
+        let outer_folder = temp_audios_context.temp_audio_dir.canonicalize().unwrap();
+        let nested_folder = outer_folder.join("nested");
+        std::fs::create_dir_all(&nested_folder).unwrap();
+        let audio_path = nested_folder.join("nested.mp3");
+        std::fs::File::create(&audio_path).unwrap();
+        let img_path = outer_folder.join("cover.png");
+        std::fs::File::create(&img_path).unwrap();
+        temp_audios_context.audios.push(AudioFile {
+            audio_path,
+            img_path: Some(img_path),
+            ..AudioFile::default()
+        });
+        let audios = temp_audios_context.audios.clone();
+        let conn = &mut temp_audios_context.connection;
+        let outer_id = add_user_media_folder(conn, &outer_folder).unwrap();
+        insert_audios(conn, &audios).unwrap();
+        assert_eq!(
+            stored_location(&temp_audios_context, 0),
+            (Some(outer_id), PathBuf::from("nested/nested.mp3"))
+        );
+
+        let conn = &mut temp_audios_context.connection;
+        let nested_id = add_user_media_folder(conn, &nested_folder).unwrap();
+        add_user_media_folder(conn, &outer_folder).unwrap();
+        assert_eq!(
+            stored_location(&temp_audios_context, 0),
+            (Some(nested_id), PathBuf::from("nested.mp3"))
+        );
+        assert_eq!(
+            get_audio_by_hash(&mut temp_audios_context.connection, &audios[0].file_hash),
+            audios[0]
+        );
+
+        // The cover is stored relative to the outer root, so it follows that root.
+        let new_outer_folder = Path::new("/mnt/hathor_relocated_outer");
+        relocate_user_media_folder(
+            &mut temp_audios_context.connection,
+            outer_id,
+            new_outer_folder,
+        )
+        .unwrap();
+        let audio = get_audio_by_hash(&mut temp_audios_context.connection, &audios[0].file_hash);
+        assert_eq!(audio.audio_path, audios[0].audio_path);
+        assert_eq!(audio.img_path, Some(new_outer_folder.join("cover.png")));
+    }
+
+    /// Rebasing never leaves a location behind silently.
+    #[rstest]
+    fn test_add_user_media_folder_with_duplicate_location_fails(
+        mut playlist_db_in_memory: TestInMemoryDBContext,
+    ) {
+        let folder = playlist_db_in_memory.temp_audio_dir.canonicalize().unwrap();
+        let conn = &mut playlist_db_in_memory.connection;
+        add_user_media_folder(conn, &folder).unwrap();
+        conn.execute(
+            r"INSERT INTO audio_files (file_hash, audio_path)
+            SELECT file_hash, :audio_path FROM audio_files WHERE audio_path = CAST('0.mp3' AS BLOB)",
+            rusqlite::named_params! {":audio_path": path_to_bytes(&folder.join("0.mp3"))},
+        )
+        .unwrap();
+        assert_eq!(
+            add_user_media_folder(conn, &folder)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "audio file 0.mp3 is stored twice under {}",
+                folder.display()
+            )
+        );
+        let absolute_locations = conn
+            .query_row(
+                r"SELECT COUNT(*) FROM audio_files WHERE folder_id IS NULL",
+                (),
+                |row| row.get::<usize, i64>(0),
+            )
+            .unwrap();
+        assert_eq!(absolute_locations, 1);
+    }
+
+    #[rstest]
+    fn test_relocate_unknown_user_media_folder_errors(
+        mut temp_audios_context: TestInMemoryDBContext,
+    ) {
+        assert!(relocate_user_media_folder(
+            &mut temp_audios_context.connection,
+            42,
+            Path::new("/mnt/music")
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case("/music/a.mp3", (Some(1), "a.mp3"))]
+    #[case("/music/nested/b.mp3", (Some(2), "b.mp3"))]
+    #[case("/other/c.mp3", (None, "/other/c.mp3"))]
+    #[case("/musical/d.mp3", (None, "/musical/d.mp3"))]
+    fn test_split_at_user_media_folder(#[case] path: &str, #[case] expected: (Option<i64>, &str)) {
+        let folders = [
+            UserMediaFolder {
+                folder_id: 1,
+                folder_path: PathBuf::from("/music"),
+            },
+            UserMediaFolder {
+                folder_id: 2,
+                folder_path: PathBuf::from("/music/nested"),
+            },
+        ];
+        let (folder, relative_path) = split_at_user_media_folder(&folders, Path::new(path));
+        assert_eq!(
+            (folder.map(|f| f.folder_id), relative_path),
+            (expected.0, PathBuf::from(expected.1))
+        );
+    }
+}
@@ -0,0 +1,14 @@
+SELECT
+    audio_files.rowid AS audio_file_id
+    , audio_folders.folder_path
+    , audio_files.audio_path
+    , img_folders.folder_path AS img_folder_path
+    , audio_files.img_path
+FROM audio_files
+    LEFT JOIN user_media_folders AS audio_folders
+        ON audio_files.folder_id = audio_folders.folder_id
+    LEFT JOIN user_media_folders AS img_folders
+        ON audio_files.img_folder_id = img_folders.folder_id
+WHERE
+    audio_files.folder_id IS NOT :folder_id
+    OR audio_files.img_folder_id IS NOT :folder_id;
@@ -0,0 +1,5 @@
+SELECT
+    audio_files.rowid AS audio_file_id
+    , audio_files.audio_path
+FROM audio_files
+WHERE audio_files.folder_id = :folder_id;
@@ -0,0 +1,3 @@
+SELECT user_media_folders.folder_id
+FROM user_media_folders
+WHERE user_media_folders.folder_path = :folder_path;
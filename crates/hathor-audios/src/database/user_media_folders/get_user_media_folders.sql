@@ -0,0 +1,5 @@
+SELECT
+    user_media_folders.folder_id
+    , user_media_folders.folder_path
+FROM user_media_folders
+ORDER BY user_media_folders.folder_id;
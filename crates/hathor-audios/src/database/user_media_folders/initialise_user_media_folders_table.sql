@@ -1,3 +1,5 @@
 CREATE TABLE IF NOT EXISTS user_media_folders (
-    folder_path VARCHAR(256) PRIMARY KEY -- Windows path limit.
-) WITHOUT ROWID;
+    folder_id INTEGER PRIMARY KEY
+    , folder_path BLOB UNIQUE -- Raw OS path bytes, no length limit.
+    , folder_path_display TEXT -- Lossy UTF-8 form of folder_path, display only.
+);
@@ -0,0 +1,7 @@
+INSERT OR IGNORE INTO user_media_folders (
+    folder_path
+    , folder_path_display
+) VALUES (
+    :folder_path
+    , :folder_path_display
+);
@@ -0,0 +1,5 @@
+UPDATE audio_files
+SET
+    folder_id = :folder_id
+    , audio_path = :audio_path
+WHERE audio_files.rowid = :audio_file_id;
@@ -0,0 +1,5 @@
+UPDATE audio_files
+SET
+    img_folder_id = :folder_id
+    , img_path = :img_path
+WHERE audio_files.rowid = :audio_file_id;
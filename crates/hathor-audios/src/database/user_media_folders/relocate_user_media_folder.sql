@@ -0,0 +1,5 @@
+UPDATE user_media_folders
+SET
+    folder_path = :folder_path
+    , folder_path_display = :folder_path_display
+WHERE user_media_folders.folder_id = :folder_id;
@@ -0,0 +1,3 @@
+UPDATE audio_files
+SET audio_path_display = :audio_path_display
+WHERE audio_files.rowid = :audio_file_id;
@@ -0,0 +1,3 @@
+UPDATE audio_files
+SET img_path_display = :img_path_display
+WHERE audio_files.rowid = :audio_file_id;
@@ -1,3 +1,7 @@
+use crate::audio::cue_sheet::CueSheet;
+use crate::audio::AudioFile;
+use log::warn;
+use std::collections::HashSet;
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
@@ -7,6 +11,8 @@ const COMPATIBLE_AUDIO_TYPES: &[&str] = &[
     "webm",
 ];
 
+const CUE_SHEET_TYPE: &str = "cue";
+
 /// Recursively finds audio file paths.
 ///
 /// # Arguments
@@ -22,7 +28,73 @@ const COMPATIBLE_AUDIO_TYPES: &[&str] = &[
 /// let p = Path::new(r"C:\audios\");
 /// let audio_file_paths = get_all_audio_file_paths_at_path(&p);
 pub fn get_all_audio_file_paths_at_path(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-    let mut audio_file_paths = Vec::new();
+    Ok(get_all_file_paths_with_extensions(
+        path,
+        COMPATIBLE_AUDIO_TYPES,
+    ))
+}
+
+/// Recursively finds CUE sheet paths.
+///
+/// # Arguments
+///
+/// * `path` - Path to a file or directory containing files.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::file_management::get_all_cue_sheet_paths_at_path;
+/// use std::path::Path;
+///
+/// let p = Path::new(r"C:\audios\");
+/// let cue_sheet_paths = get_all_cue_sheet_paths_at_path(&p);
+pub fn get_all_cue_sheet_paths_at_path(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    Ok(get_all_file_paths_with_extensions(path, &[CUE_SHEET_TYPE]))
+}
+
+/// Recursively reads every audio, splitting images described by a CUE sheet into their tracks.
+/// Images are only read as a whole when no readable sheet refers to them,
+/// broken sheets are logged and skipped.
+///
+/// # Arguments
+///
+/// * `path` - Path to a file or directory containing files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::file_management::get_all_audios_at_path;
+/// use std::path::Path;
+///
+/// let p = Path::new(r"C:\audios\");
+/// let audios = get_all_audios_at_path(&p);
+pub fn get_all_audios_at_path(path: &Path) -> Result<Vec<AudioFile>, Box<dyn Error>> {
+    let mut audios = Vec::new();
+    let mut image_paths = HashSet::new();
+    for cue_path in get_all_cue_sheet_paths_at_path(path)? {
+        let tracks = CueSheet::from_file(&cue_path).and_then(|sheet| sheet.audios(&cue_path));
+        match tracks {
+            Ok(tracks) => {
+                image_paths.extend(tracks.iter().map(|track| track.audio_path.clone()));
+                audios.extend(tracks);
+            }
+            Err(e) => warn!("Skipping CUE sheet {}: {}", cue_path.display(), e),
+        }
+    }
+    for audio_path in get_all_audio_file_paths_at_path(path)? {
+        if image_paths.contains(&audio_path.canonicalize()?) {
+            continue;
+        }
+        match AudioFile::from_file(&audio_path) {
+            Ok(audio) => audios.push(audio),
+            Err(e) => warn!("Skipping audio {}: {}", audio_path.display(), e),
+        }
+    }
+    Ok(audios)
+}
+
+fn get_all_file_paths_with_extensions(path: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let mut file_paths = Vec::new();
     for entry in WalkDir::new(path)
         .follow_links(true)
         .into_iter()
@@ -31,21 +103,20 @@ pub fn get_all_audio_file_paths_at_path(path: &Path) -> Result<Vec<PathBuf>, Box
         let extension = entry.path().extension();
 
         if let Some(extension) = extension {
-            if COMPATIBLE_AUDIO_TYPES.contains(&extension.to_str().unwrap().to_lowercase().as_str())
-            {
-                audio_file_paths.push(PathBuf::from(&entry.path()));
+            if extensions.contains(&extension.to_string_lossy().to_lowercase().as_str()) {
+                file_paths.push(PathBuf::from(&entry.path()));
             }
         }
     }
 
-    Ok(audio_file_paths)
+    file_paths
 }
 
 #[cfg(test)]
 mod file_management_tests {
     const TEST_AUDIO_FOLDER: &str = r"/../../test_media_files/audio/albums";
 
-    use super::{get_all_audio_file_paths_at_path, COMPATIBLE_AUDIO_TYPES};
+    use super::{get_all_audio_file_paths_at_path, get_all_audios_at_path, COMPATIBLE_AUDIO_TYPES};
     use rstest::rstest;
     use std::path::PathBuf;
 
@@ -86,4 +157,13 @@ mod file_management_tests {
         file_names.sort_unstable();
         assert_eq!(file_names, expected_found_file_names);
     }
+
+    /// Files that can't be read as audio are skipped rather than failing the scan.
+    #[rstest]
+    fn test_get_all_audios_skips_bad_files() {
+        let folder_path = PathBuf::from(
+            env!("CARGO_MANIFEST_DIR").to_owned() + TEST_AUDIO_FOLDER + "/album_bad_files",
+        );
+        assert_eq!(get_all_audios_at_path(&folder_path).unwrap(), []);
+    }
 }
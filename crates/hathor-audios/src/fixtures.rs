@@ -59,8 +59,8 @@ pub(crate) fn db_in_file() -> TestContext {
 
 #[fixture]
 pub(crate) fn temp_audios_context() -> TestInMemoryDBContext {
-    let conn = Connection::open_in_memory().expect("Failed to create test database.");
-    init_db(&conn).expect("Failed to initialise test database.");
+    let mut conn = Connection::open_in_memory().expect("Failed to create test database.");
+    init_db(&mut conn).expect("Failed to initialise test database.");
     let mut audio_temp_dir = temp_dir();
     audio_temp_dir.push(format!("hathor_tests_{:?}", thread::current().id()));
     std::fs::create_dir_all(&audio_temp_dir).unwrap();
@@ -139,3 +139,22 @@ pub(crate) fn playlist_db_in_memory(
     insert_audios(&mut audio_fake_multiple.connection, audios_2).unwrap();
     audio_fake_multiple
 }
+
+/// Creates an empty file for the audio in the context's temp dir, inserts it into the db
+/// and adds it to `context.audios`.
+/// The file is named after the audio's hash, so give each audio a distinct hash.
+pub(crate) fn insert_temp_audio(
+    context: &mut TestInMemoryDBContext,
+    audio: AudioFile,
+) -> AudioFile {
+    let mut audio_path = context.temp_audio_dir.clone().canonicalize().unwrap();
+    audio_path.push(format!("{}.mp3", audio.file_hash));
+    fs::File::create(&audio_path).unwrap();
+    let audio = AudioFile {
+        audio_path,
+        ..audio
+    };
+    insert_audios(&mut context.connection, std::slice::from_ref(&audio)).unwrap();
+    context.audios.push(audio.clone());
+    audio
+}
@@ -3,3 +3,4 @@ pub mod database;
 pub mod file_management;
 #[cfg(test)]
 mod fixtures;
+pub mod playlist_files;
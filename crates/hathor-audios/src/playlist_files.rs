@@ -0,0 +1,305 @@
+//! Reading and writing playlists in the file formats other players use.
+//!
+//! Each format parses to and writes from [PlaylistEntry]s,
+//! the DB side of importing and exporting is shared here.
+
+pub mod m3u;
+pub mod pls;
+pub mod xspf;
+
+use crate::audio::AudioFile;
+use crate::database::audio_files::get_audio_by_path;
+use crate::database::listing::ListOptions;
+use crate::database::playlists::{
+    create_playlist, get_audios_from_playlist, get_playlist, insert_audios_into_playlist,
+};
+use crate::database::query::{
+    get_audios_by_query, Comparison, Condition, Expr, Field, Query, Value,
+};
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use time::Duration;
+
+/// Most a stale entry's length may differ from the audio it is matched to.
+const LENGTH_TOLERANCE_SECONDS: i64 = 2;
+
+/// One entry of a playlist file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PlaylistEntry {
+    /// As written in the file, relative paths are relative to the file's directory.
+    pub path: PathBuf,
+    /// Track title, or `Artist - Title` in formats with a single display title.
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub length: Option<Duration>,
+}
+
+/// The outcome of importing a playlist file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ImportReport {
+    /// Entries added to the playlist.
+    pub imported: usize,
+    /// Entries that matched no audio in the DB, in file order.
+    pub unresolved: Vec<PlaylistEntry>,
+}
+
+/// Resolves entries to audios in the DB and appends them to the playlist,
+/// creating it if needed.
+/// Entries are found by path, or else by title, artist and length, see [find_by_tags].
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `entries` - Entries parsed from the playlist file.
+/// * `base_dir` - Directory relative entry paths start from.
+pub(crate) fn import_entries(
+    conn: &mut Connection,
+    playlist_name: &str,
+    entries: Vec<PlaylistEntry>,
+    base_dir: &Path,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let mut audios = Vec::new();
+    let mut unresolved = Vec::new();
+    for entry in entries {
+        // Missing files fail to resolve, they are reported rather than failing the import.
+        let by_path = match get_audio_by_path(conn, &base_dir.join(&entry.path)) {
+            Ok(audio) => audio,
+            Err(e) if is_not_found(e.as_ref()) => None,
+            Err(e) => return Err(e),
+        };
+        let audio = match by_path {
+            Some(audio) => Some(audio),
+            None => find_by_tags(conn, &entry)?,
+        };
+        match audio {
+            Some(audio) => audios.push(audio),
+            None => unresolved.push(entry),
+        }
+    }
+    if get_playlist(conn, playlist_name)?.is_none() {
+        create_playlist(conn, playlist_name, "")?;
+    }
+    insert_audios_into_playlist(conn, playlist_name, &audios)?;
+    Ok(ImportReport {
+        imported: audios.len(),
+        unresolved,
+    })
+}
+
+fn is_not_found(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+/// Reads a text file as UTF-8, falling back to Latin-1 for files that aren't valid UTF-8,
+/// as older players and ripping software wrote them.
+///
+/// # Arguments
+///
+/// * `path` - The file to read.
+pub(crate) fn read_text_file(path: &Path) -> Result<String, io::Error> {
+    Ok(match String::from_utf8(fs::read(path)?) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+    })
+}
+
+/// Entries for every audio of the playlist, in playlist order.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - Title of the playlist.
+/// * `relative_to` - Directory paths are written relative to, None for absolute paths.
+pub(crate) fn export_entries(
+    conn: &mut Connection,
+    playlist_name: &str,
+    relative_to: Option<&Path>,
+) -> Result<Vec<PlaylistEntry>, Box<dyn Error>> {
+    if get_playlist(conn, playlist_name)?.is_none() {
+        return Err(format!("no playlist named {}", playlist_name).into());
+    }
+    let relative_to = relative_to.map(Path::canonicalize).transpose()?;
+    let audios = get_audios_from_playlist(conn, playlist_name, &ListOptions::default())?.items;
+    Ok(audios
+        .into_iter()
+        .map(|audio| PlaylistEntry {
+            path: relative_to
+                .as_deref()
+                .and_then(|base| relative_path(&audio.audio_path, base))
+                .unwrap_or_else(|| audio.audio_path.clone()),
+            title: Some(audio.audio_title),
+            artist: Some(audio.artist_name).filter(|artist| !artist.is_empty()),
+            length: Some(audio.audio_length),
+        })
+        .collect())
+}
+
+/// Finds the audio an entry refers to by its tags, for entries whose path is stale.
+/// Titles and artists must match exactly, ignoring case and diacritics,
+/// and lengths within [LENGTH_TOLERANCE_SECONDS] when the entry has one.
+/// An `Artist - Title` title without an artist is tried split as well as whole.
+fn find_by_tags(
+    conn: &mut Connection,
+    entry: &PlaylistEntry,
+) -> Result<Option<AudioFile>, Box<dyn Error>> {
+    let Some(title) = &entry.title else {
+        return Ok(None);
+    };
+    let mut candidates = vec![(title.as_str(), entry.artist.as_deref())];
+    if entry.artist.is_none() {
+        if let Some((artist, title)) = title.split_once(" - ") {
+            candidates.insert(0, (title, Some(artist)));
+        }
+    }
+    for (title, artist) in candidates {
+        let mut conditions = vec![equals(Field::Title, Value::Text(title.trim().to_string()))];
+        if let Some(artist) = artist {
+            conditions.push(equals(
+                Field::Artist,
+                Value::Text(artist.trim().to_string()),
+            ));
+        }
+        if let Some(length) = entry.length {
+            let seconds = length.whole_seconds();
+            conditions.push(Expr::Condition(Condition {
+                field: Field::Length,
+                comparison: Comparison::Between(
+                    Value::Integer(seconds.saturating_sub(LENGTH_TOLERANCE_SECONDS)),
+                    Value::Integer(seconds.saturating_add(LENGTH_TOLERANCE_SECONDS)),
+                ),
+            }));
+        }
+        let query = Query {
+            filter: Some(Expr::And(conditions)),
+            sort: Vec::new(),
+        };
+        let options = ListOptions {
+            limit: Some(1),
+            ..ListOptions::default()
+        };
+        if let Some(audio) = get_audios_by_query(conn, &query, &options)?.items.pop() {
+            return Ok(Some(audio));
+        }
+    }
+    Ok(None)
+}
+
+fn equals(field: Field, value: Value) -> Expr {
+    Expr::Condition(Condition {
+        field,
+        comparison: Comparison::Equals(value),
+    })
+}
+
+/// The path from `base` to `path`, both absolute, e.g. `../other/track.mp3`.
+/// None if they share no root, like paths on different Windows drives.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        return None;
+    }
+    while path_components.peek().is_some() && path_components.peek() == base_components.peek() {
+        path_components.next();
+        base_components.next();
+    }
+    let mut relative = PathBuf::new();
+    for component in base_components {
+        if let Component::Normal(_) = component {
+            relative.push("..");
+        }
+    }
+    relative.extend(path_components);
+    Some(relative)
+}
+
+#[cfg(test)]
+mod playlist_files_tests {
+    use super::{import_entries, relative_path, PlaylistEntry};
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::database::playlists::get_playlist_items;
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use blake3::Hash;
+    use rstest::rstest;
+    use std::path::{Path, PathBuf};
+    use time::Duration;
+
+    #[rstest]
+    #[case("/music/a/track.mp3", "/music/a", Some("track.mp3"))]
+    #[case("/music/a/b/track.mp3", "/music/a", Some("b/track.mp3"))]
+    #[case("/music/a/track.mp3", "/music/b/c", Some("../../a/track.mp3"))]
+    #[case("/music/track.mp3", "/", Some("music/track.mp3"))]
+    fn test_relative_path(#[case] path: &str, #[case] base: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            relative_path(Path::new(path), Path::new(base)),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    /// Entries whose files moved are matched by their tags instead.
+    #[rstest]
+    fn test_import_stale_entries_by_tags(mut temp_audios_context: TestInMemoryDBContext) {
+        for (n, (title, artist)) in [("Teardrop", "Massive Attack"), ("Angel", "Massive Attack")]
+            .into_iter()
+            .enumerate()
+        {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: String::from(title),
+                    artist_name: String::from(artist),
+                    audio_length: Duration::seconds(330),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let stale = |title: &str, artist: Option<&str>, seconds: i64| PlaylistEntry {
+            path: PathBuf::from("/old/library/track.mp3"),
+            title: Some(String::from(title)),
+            artist: artist.map(String::from),
+            length: Some(Duration::seconds(seconds)),
+        };
+        let entries = vec![
+            stale("angel", Some("MASSIVE ATTACK"), 331),
+            stale("Massive Attack - Teardrop", None, 329),
+            stale("Teardrop", None, 330),
+            stale("Teardrop", Some("Massive Attack"), 200),
+            stale("Teardrop", Some("Portishead"), 330),
+            // A PLS `Length1=9223372036854775807` matches nothing rather than overflowing.
+            stale("Teardrop", Some("Massive Attack"), i64::MAX),
+        ];
+        let conn = &mut temp_audios_context.connection;
+        let report = import_entries(conn, "Stale", entries, Path::new("/")).unwrap();
+        assert_eq!(report.imported, 3);
+        assert_eq!(report.unresolved.len(), 3);
+        let titles = get_playlist_items(conn, "Stale", &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| item.audio.unwrap().audio_title)
+            .collect::<Vec<String>>();
+        assert_eq!(titles, ["Angel", "Teardrop", "Teardrop"]);
+    }
+
+    /// Only missing files go unresolved, other failures abort the import.
+    #[rstest]
+    fn test_import_fails_on_db_errors(mut temp_audios_context: TestInMemoryDBContext) {
+        let conn = &mut temp_audios_context.connection;
+        conn.execute_batch("DROP TABLE user_media_folders").unwrap();
+        let entry = PlaylistEntry {
+            path: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"),
+            title: None,
+            artist: None,
+            length: None,
+        };
+        assert!(import_entries(conn, "Broken", vec![entry], Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! M3U and extended M3U8 playlists: one path per line,
+//! optionally preceded by `#EXTINF:<seconds>,<title>`.
+
+use crate::playlist_files::{
+    export_entries, import_entries, read_text_file, ImportReport, PlaylistEntry,
+};
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::Duration;
+
+/// Parses the entries of an M3U playlist, extended or not.
+/// Comments and unknown directives are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::m3u::parse_m3u;
+///
+/// let entries = parse_m3u("#EXTM3U\n#EXTINF:215,Portishead - Roads\nDummy/11 Roads.mp3\n");
+/// assert_eq!(entries[0].title.as_deref(), Some("Portishead - Roads"));
+pub fn parse_m3u(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut info = None;
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            info = Some(parse_extinf(extinf));
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let (length, title) = info.take().unwrap_or((None, None));
+            entries.push(PlaylistEntry {
+                path: PathBuf::from(line),
+                title,
+                artist: None,
+                length,
+            });
+        }
+    }
+    entries
+}
+
+/// Writes entries as an extended M3U playlist, titled `Artist - Title`.
+/// Paths that aren't valid UTF-8 are written lossily, as the format is text.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::m3u::{parse_m3u, write_m3u};
+///
+/// let entries = parse_m3u("#EXTINF:215,Portishead - Roads\nDummy/11 Roads.mp3\n");
+/// assert_eq!(parse_m3u(&write_m3u(&entries)), entries);
+pub fn write_m3u(entries: &[PlaylistEntry]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for entry in entries {
+        if entry.title.is_some() || entry.length.is_some() {
+            // -1 is the format's unknown length.
+            let seconds = entry.length.map_or(-1, |length| length.whole_seconds());
+            m3u += &format!("#EXTINF:{},{}\n", seconds, display_title(entry));
+        }
+        m3u += &entry.path.to_string_lossy();
+        m3u += "\n";
+    }
+    m3u
+}
+
+/// Imports an M3U or M3U8 file into the named playlist, creating it if needed.
+/// Entries are appended to an existing playlist.
+/// Files that aren't UTF-8 are read as Latin-1, like older players wrote them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `m3u_path` - The playlist file, relative entries start from its directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::m3u::import_m3u;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let report = import_m3u(&mut conn, "Road trip", Path::new("/home/me/road_trip.m3u8")).unwrap();
+/// for entry in report.unresolved {
+///     println!("Not in the library: {}", entry.path.display());
+/// }
+pub fn import_m3u(
+    conn: &mut Connection,
+    playlist_name: &str,
+    m3u_path: &Path,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let text = read_text_file(m3u_path)?;
+    let base_dir = m3u_path.parent().unwrap_or(Path::new(""));
+    import_entries(conn, playlist_name, parse_m3u(&text), base_dir)
+}
+
+/// Exports the named playlist to an extended M3U file in UTF-8.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - Title of the playlist.
+/// * `m3u_path` - The file to write, it is replaced if it exists.
+/// * `relative_to` - Directory paths are written relative to, usually the file's own,
+///   None for absolute paths.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::m3u::export_m3u;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let m3u_path = Path::new("/media/player/road_trip.m3u8");
+/// export_m3u(&mut conn, "Road trip", m3u_path, m3u_path.parent());
+pub fn export_m3u(
+    conn: &mut Connection,
+    playlist_name: &str,
+    m3u_path: &Path,
+    relative_to: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let entries = export_entries(conn, playlist_name, relative_to)?;
+    fs::write(m3u_path, write_m3u(&entries))?;
+    Ok(())
+}
+
+/// `Artist - Title`, the single title M3U has for an entry.
+pub(crate) fn display_title(entry: &PlaylistEntry) -> String {
+    let title = entry.title.as_deref().unwrap_or_default();
+    match &entry.artist {
+        Some(artist) => format!("{} - {}", artist, title),
+        None => title.to_string(),
+    }
+}
+
+/// Splits `<seconds>[ attributes],<title>`, the length is None when unknown.
+fn parse_extinf(extinf: &str) -> (Option<Duration>, Option<String>) {
+    let (length, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let length = length
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .filter(|seconds| *seconds >= 0.0)
+        // Lengths too long for a Duration, or infinite, are unknown too.
+        .and_then(Duration::checked_seconds_f64);
+    let title = Some(title.trim().to_string()).filter(|title| !title.is_empty());
+    (length, title)
+}
+
+#[cfg(test)]
+mod m3u_tests {
+    use super::{export_m3u, import_m3u, parse_m3u};
+    use crate::audio::AudioFile;
+    use crate::database::listing::ListOptions;
+    use crate::database::playlists::{
+        get_playlist, get_playlist_items, insert_audios_into_playlist,
+    };
+    use crate::fixtures::{insert_temp_audio, temp_audios_context, TestInMemoryDBContext};
+    use crate::playlist_files::PlaylistEntry;
+    use blake3::Hash;
+    use rstest::rstest;
+    use std::fs;
+    use std::path::PathBuf;
+    use time::Duration;
+
+    #[rstest]
+    #[case("a.mp3\r\n\r\n# comment\r\nb/c.flac", vec![("a.mp3", None, None), ("b/c.flac", None, None)])]
+    #[case(
+        "\u{feff}#EXTM3U\n#EXTINF:-1,Unknown length\n/music/a.mp3\n",
+        vec![("/music/a.mp3", Some("Unknown length"), None)]
+    )]
+    #[case(
+        "#EXTINF:61.5 tvg-id=\"x\",Björk - Jóga\n#EXTALB:Homogenic\nJóga.mp3\nnext.mp3",
+        vec![("Jóga.mp3", Some("Björk - Jóga"), Some(61.5)), ("next.mp3", None, None)]
+    )]
+    #[case("#EXTINF:1e300,Huge
+huge.mp3", vec![("huge.mp3", Some("Huge"), None)])]
+    #[case("#EXTINF:inf,Infinite
+inf.mp3", vec![("inf.mp3", Some("Infinite"), None)])]
+    #[case("#EXTINF:NaN,Not a number
+nan.mp3", vec![("nan.mp3", Some("Not a number"), None)])]
+    fn test_parse_m3u(#[case] m3u: &str, #[case] expected: Vec<(&str, Option<&str>, Option<f64>)>) {
+        let expected = expected
+            .into_iter()
+            .map(|(path, title, seconds)| PlaylistEntry {
+                path: PathBuf::from(path),
+                title: title.map(String::from),
+                artist: None,
+                length: seconds.map(Duration::seconds_f64),
+            })
+            .collect::<Vec<PlaylistEntry>>();
+        assert_eq!(parse_m3u(m3u), expected);
+    }
+
+    #[rstest]
+    fn test_export_then_import_m3u(mut temp_audios_context: TestInMemoryDBContext) {
+        for n in 0..3 {
+            insert_temp_audio(
+                &mut temp_audios_context,
+                AudioFile {
+                    file_hash: Hash::from_hex(format!("{:064}", n)).unwrap(),
+                    audio_title: format!("Track {}", n),
+                    artist_name: String::from("Artist"),
+                    ..AudioFile::default()
+                },
+            );
+        }
+        let audios = temp_audios_context.audios.clone();
+        let conn = &mut temp_audios_context.connection;
+        insert_audios_into_playlist(
+            conn,
+            "Mix",
+            &[&audios[2], &audios[0], &audios[2]].map(Clone::clone),
+        )
+        .unwrap();
+
+        let playlist_dir = temp_audios_context.temp_audio_dir.join("playlists");
+        fs::create_dir_all(&playlist_dir).unwrap();
+        let m3u_path = playlist_dir.join("mix.m3u8");
+        export_m3u(conn, "Mix", &m3u_path, Some(playlist_dir.as_path())).unwrap();
+        let m3u = fs::read_to_string(&m3u_path).unwrap();
+        assert!(m3u.starts_with("#EXTM3U\n#EXTINF:0,Artist - Track 2\n../"));
+
+        fs::write(
+            &m3u_path,
+            m3u + &format!("missing.mp3\n{}\n", audios[1].audio_path.display()),
+        )
+        .unwrap();
+        let report = import_m3u(conn, "Imported", &m3u_path).unwrap();
+        assert_eq!(report.imported, 4);
+        assert_eq!(report.unresolved.len(), 1);
+        assert_eq!(report.unresolved[0].path, PathBuf::from("missing.mp3"));
+        let titles = get_playlist_items(conn, "Imported", &ListOptions::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| item.audio.unwrap().audio_title)
+            .collect::<Vec<String>>();
+        assert_eq!(titles, ["Track 2", "Track 0", "Track 2", "Track 1"]);
+    }
+
+    #[rstest]
+    fn test_import_latin1_m3u(mut temp_audios_context: TestInMemoryDBContext) {
+        let m3u_path = temp_audios_context.temp_audio_dir.join("legacy.m3u");
+        fs::write(&m3u_path, b"#EXTINF:10,Caf\xe9\nmissing.mp3\n").unwrap();
+        let report = import_m3u(&mut temp_audios_context.connection, "Legacy", &m3u_path).unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.unresolved[0].title.as_deref(), Some("Café"));
+        // The playlist is created even when nothing resolved.
+        assert!(get_playlist(&mut temp_audios_context.connection, "Legacy")
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,183 @@
+//! PLS playlists, the INI style format of Winamp and Shoutcast:
+//! numbered `FileN`, `TitleN` and `LengthN` keys under a `[playlist]` section.
+
+use crate::playlist_files::m3u::display_title;
+use crate::playlist_files::{
+    export_entries, import_entries, read_text_file, ImportReport, PlaylistEntry,
+};
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::Duration;
+
+/// Parses the entries of a PLS playlist, in the order of their numbers.
+/// Numbers without a `FileN` key are skipped, as are unknown keys.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::pls::parse_pls;
+///
+/// let entries = parse_pls("[playlist]\nFile1=Dummy/11 Roads.mp3\nTitle1=Portishead - Roads\n");
+/// assert_eq!(entries[0].title.as_deref(), Some("Portishead - Roads"));
+pub fn parse_pls(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = BTreeMap::<u32, PlaylistEntry>::new();
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let Some((name, number)) = ["file", "title", "length"]
+            .into_iter()
+            .find_map(|name| Some((name, key.strip_prefix(name)?.parse::<u32>().ok()?)))
+        else {
+            continue;
+        };
+        let entry = entries.entry(number).or_insert_with(|| PlaylistEntry {
+            path: PathBuf::new(),
+            title: None,
+            artist: None,
+            length: None,
+        });
+        match name {
+            "file" => entry.path = PathBuf::from(value),
+            "title" => entry.title = Some(value.to_string()).filter(|title| !title.is_empty()),
+            _ => {
+                entry.length = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|seconds| *seconds >= 0)
+                    .map(Duration::seconds)
+            }
+        }
+    }
+    entries
+        .into_values()
+        .filter(|entry| !entry.path.as_os_str().is_empty())
+        .collect()
+}
+
+/// Writes entries as a version 2 PLS playlist, titled `Artist - Title`.
+/// Paths that aren't valid UTF-8 are written lossily, as the format is text.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::pls::{parse_pls, write_pls};
+///
+/// let entries = parse_pls("[playlist]\nFile1=Dummy/11 Roads.mp3\nTitle1=Portishead - Roads\n");
+/// assert_eq!(parse_pls(&write_pls(&entries)), entries);
+pub fn write_pls(entries: &[PlaylistEntry]) -> String {
+    let mut pls = String::from("[playlist]\n");
+    for (entry, number) in entries.iter().zip(1..) {
+        pls += &format!("File{}={}\n", number, entry.path.to_string_lossy());
+        if entry.title.is_some() {
+            pls += &format!("Title{}={}\n", number, display_title(entry));
+        }
+        // -1 is the format's unknown length.
+        let seconds = entry.length.map_or(-1, |length| length.whole_seconds());
+        pls += &format!("Length{}={}\n", number, seconds);
+    }
+    pls += &format!("NumberOfEntries={}\nVersion=2\n", entries.len());
+    pls
+}
+
+/// Imports a PLS file into the named playlist, creating it if needed.
+/// Entries are appended to an existing playlist.
+/// Files that aren't UTF-8 are read as Latin-1, like Winamp wrote them.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `pls_path` - The playlist file, relative entries start from its directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::pls::import_pls;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let report = import_pls(&mut conn, "Winamp", Path::new("/home/me/winamp.pls")).unwrap();
+pub fn import_pls(
+    conn: &mut Connection,
+    playlist_name: &str,
+    pls_path: &Path,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let text = read_text_file(pls_path)?;
+    let base_dir = pls_path.parent().unwrap_or(Path::new(""));
+    import_entries(conn, playlist_name, parse_pls(&text), base_dir)
+}
+
+/// Exports the named playlist to a PLS file in UTF-8.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - Title of the playlist.
+/// * `pls_path` - The file to write, it is replaced if it exists.
+/// * `relative_to` - Directory paths are written relative to, None for absolute paths.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::pls::export_pls;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let pls_path = Path::new("/home/me/winamp.pls");
+/// export_pls(&mut conn, "Winamp", pls_path, pls_path.parent());
+pub fn export_pls(
+    conn: &mut Connection,
+    playlist_name: &str,
+    pls_path: &Path,
+    relative_to: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let entries = export_entries(conn, playlist_name, relative_to)?;
+    fs::write(pls_path, write_pls(&entries))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod pls_tests {
+    use super::parse_pls;
+    use crate::playlist_files::PlaylistEntry;
+    use std::path::PathBuf;
+    use time::Duration;
+
+    #[test]
+    fn test_parse_pls() {
+        let pls = "[playlist]\r\n\
+            File2=C:\\Music\\b.mp3\r\n\
+            Title2=\r\n\
+            file1 = a.mp3\r\n\
+            Title1=Air - La femme d'argent\r\n\
+            Length1=429\r\n\
+            Length3=-1\r\n\
+            NumberOfEntries=2\r\n\
+            Version=2\r\n";
+        assert_eq!(
+            parse_pls(pls),
+            [
+                PlaylistEntry {
+                    path: PathBuf::from("a.mp3"),
+                    title: Some(String::from("Air - La femme d'argent")),
+                    artist: None,
+                    length: Some(Duration::seconds(429)),
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("C:\\Music\\b.mp3"),
+                    title: None,
+                    artist: None,
+                    length: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! XSPF playlists, the XML format of VLC: `<track>`s with a `<location>` URI,
+//! `<title>`, `<creator>` and `<duration>` in milliseconds.
+
+use crate::playlist_files::{export_entries, import_entries, ImportReport, PlaylistEntry};
+use percent_encoding::{percent_decode_str, AsciiSet, CONTROLS};
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::Duration;
+
+/// Bytes escaped in the path of a location URI, `/` is kept as the separator.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Parses the tracks of an XSPF playlist.
+/// Tracks without a local location get an empty path, so they can only match by tags.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::xspf::parse_xspf;
+/// use std::path::Path;
+///
+/// let xspf = r#"<playlist version="1" xmlns="http://xspf.org/ns/0/"><trackList>
+///     <track><location>file:///music/11%20Roads.mp3</location><title>Roads</title></track>
+/// </trackList></playlist>"#;
+/// let entries = parse_xspf(xspf).unwrap();
+/// assert_eq!(entries[0].path, Path::new("/music/11 Roads.mp3"));
+pub fn parse_xspf(text: &str) -> Result<Vec<PlaylistEntry>, Box<dyn Error>> {
+    let document = roxmltree::Document::parse(text.trim_start_matches('\u{feff}'))?;
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(String::from)
+    };
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "track")
+        .map(|track| PlaylistEntry {
+            path: child_text(track, "location")
+                .and_then(|location| location_to_path(&location))
+                .unwrap_or_default(),
+            title: child_text(track, "title"),
+            artist: child_text(track, "creator"),
+            length: child_text(track, "duration")
+                .and_then(|milliseconds| milliseconds.parse::<i64>().ok())
+                .map(Duration::milliseconds),
+        })
+        .collect())
+}
+
+/// Writes entries as an XSPF playlist.
+///
+/// # Examples
+///
+/// ```
+/// use hathor_audios::playlist_files::xspf::{parse_xspf, write_xspf};
+///
+/// let xspf = r#"<playlist version="1" xmlns="http://xspf.org/ns/0/"><trackList>
+///     <track><location>Dummy/11%20Roads.mp3</location><creator>Portishead</creator></track>
+/// </trackList></playlist>"#;
+/// let entries = parse_xspf(xspf).unwrap();
+/// assert_eq!(parse_xspf(&write_xspf(&entries)).unwrap(), entries);
+pub fn write_xspf(entries: &[PlaylistEntry]) -> String {
+    let mut xspf = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+        "  <trackList>\n",
+    ));
+    for entry in entries {
+        xspf += "    <track>\n";
+        let location = path_to_location(&entry.path);
+        xspf += &format!("      <location>{}</location>\n", escape_xml(&location));
+        if let Some(title) = &entry.title {
+            xspf += &format!("      <title>{}</title>\n", escape_xml(title));
+        }
+        if let Some(artist) = &entry.artist {
+            xspf += &format!("      <creator>{}</creator>\n", escape_xml(artist));
+        }
+        if let Some(length) = entry.length {
+            let milliseconds = length.whole_milliseconds();
+            xspf += &format!("      <duration>{}</duration>\n", milliseconds);
+        }
+        xspf += "    </track>\n";
+    }
+    xspf += "  </trackList>\n</playlist>\n";
+    xspf
+}
+
+/// Imports an XSPF file into the named playlist, creating it if needed.
+/// Entries are appended to an existing playlist.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to insert into.
+/// * `playlist_name` - Title of the playlist.
+/// * `xspf_path` - The playlist file, relative locations start from its directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::xspf::import_xspf;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// let report = import_xspf(&mut conn, "VLC", Path::new("/home/me/vlc.xspf")).unwrap();
+pub fn import_xspf(
+    conn: &mut Connection,
+    playlist_name: &str,
+    xspf_path: &Path,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let entries = parse_xspf(&fs::read_to_string(xspf_path)?)?;
+    let base_dir = xspf_path.parent().unwrap_or(Path::new(""));
+    import_entries(conn, playlist_name, entries, base_dir)
+}
+
+/// Exports the named playlist to an XSPF file.
+///
+/// # Arguments
+///
+/// * `conn` - The open database connection to query.
+/// * `playlist_name` - Title of the playlist.
+/// * `xspf_path` - The file to write, it is replaced if it exists.
+/// * `relative_to` - Directory locations are written relative to, None for `file://` URIs.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hathor_audios::playlist_files::xspf::export_xspf;
+/// use rusqlite::Connection;
+/// use std::path::Path;
+///
+/// let mut conn = Connection::open_in_memory().unwrap();
+/// export_xspf(&mut conn, "VLC", Path::new("/home/me/vlc.xspf"), None);
+pub fn export_xspf(
+    conn: &mut Connection,
+    playlist_name: &str,
+    xspf_path: &Path,
+    relative_to: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let entries = export_entries(conn, playlist_name, relative_to)?;
+    fs::write(xspf_path, write_xspf(&entries))?;
+    Ok(())
+}
+
+/// The path of a `file://` or relative location URI, None for other schemes like `http://`.
+fn location_to_path(location: &str) -> Option<PathBuf> {
+    let path = match location.strip_prefix("file://") {
+        // The host, if any, is dropped along with the scheme.
+        Some(rest) => &rest[rest.find('/')?..],
+        None if location.contains("://") => return None,
+        None => location,
+    };
+    let bytes = percent_decode_str(path).collect::<Vec<u8>>();
+    Some(path_from_uri_bytes(bytes))
+}
+
+fn path_to_location(path: &Path) -> String {
+    let encoded = encode_uri_path(path);
+    if !path.is_absolute() {
+        encoded
+    } else if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        // Windows paths start with a drive, e.g. file:///C:/Music.
+        format!("file:///{}", encoded)
+    }
+}
+
+#[cfg(unix)]
+fn path_from_uri_bytes(bytes: Vec<u8>) -> PathBuf {
+    crate::database::os_paths::path_from_bytes(bytes)
+}
+
+#[cfg(windows)]
+fn path_from_uri_bytes(bytes: Vec<u8>) -> PathBuf {
+    let path = String::from_utf8_lossy(&bytes);
+    let path = match path.strip_prefix('/') {
+        Some(drive_path) if drive_path.get(1..2) == Some(":") => drive_path,
+        _ => &path,
+    };
+    PathBuf::from(path)
+}
+
+/// Unix paths are encoded byte for byte, so paths that aren't UTF-8 survive.
+#[cfg(unix)]
+fn encode_uri_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    percent_encoding::percent_encode(path.as_os_str().as_bytes(), PATH_SEGMENT).to_string()
+}
+
+#[cfg(windows)]
+fn encode_uri_path(path: &Path) -> String {
+    percent_encoding::utf8_percent_encode(&path.to_string_lossy().replace('\\', "/"), PATH_SEGMENT)
+        .to_string()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod xspf_tests {
+    use super::{parse_xspf, write_xspf};
+    use crate::playlist_files::PlaylistEntry;
+    use rstest::rstest;
+    use std::path::PathBuf;
+    use time::Duration;
+
+    #[test]
+    fn test_parse_xspf() {
+        let xspf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Ignored</title>
+              <trackList>
+                <track>
+                  <location>file://localhost/music/Sigur%20R%C3%B3s/Hopp%C3%ADpolla.flac</location>
+                  <title>Hoppípolla</title>
+                  <creator>Sigur Rós</creator>
+                  <duration>268000</duration>
+                </track>
+                <track><location>http://radio.example/stream</location><title>Radio</title></track>
+                <track><location>relative/a%26b.mp3</location></track>
+              </trackList>
+            </playlist>"#;
+        assert_eq!(
+            parse_xspf(xspf).unwrap(),
+            [
+                PlaylistEntry {
+                    path: PathBuf::from("/music/Sigur Rós/Hoppípolla.flac"),
+                    title: Some(String::from("Hoppípolla")),
+                    artist: Some(String::from("Sigur Rós")),
+                    length: Some(Duration::seconds(268)),
+                },
+                PlaylistEntry {
+                    path: PathBuf::new(),
+                    title: Some(String::from("Radio")),
+                    artist: None,
+                    length: None,
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("relative/a&b.mp3"),
+                    title: None,
+                    artist: None,
+                    length: None,
+                },
+            ]
+        );
+        assert!(parse_xspf("<playlist><trackList>").is_err());
+    }
+
+    #[rstest]
+    #[case(PathBuf::from("/music/AC/DC & <Friends>/#1 100%.mp3"))]
+    #[case(PathBuf::from("../relative/Jóga.mp3"))]
+    fn test_write_xspf_round_trip(#[case] path: PathBuf) {
+        let entries = [PlaylistEntry {
+            path,
+            title: Some(String::from("Title & \"quotes\"")),
+            artist: None,
+            length: Some(Duration::milliseconds(1500)),
+        }];
+        assert_eq!(parse_xspf(&write_xspf(&entries)).unwrap(), entries);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let entries = [PlaylistEntry {
+            path: PathBuf::from(OsStr::from_bytes(b"/music/caf\xe9.mp3")),
+            title: None,
+            artist: None,
+            length: None,
+        }];
+        let xspf = write_xspf(&entries);
+        assert!(xspf.contains("<location>file:///music/caf%E9.mp3</location>"));
+        assert_eq!(parse_xspf(&xspf).unwrap(), entries);
+    }
+}